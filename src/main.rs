@@ -1,6 +1,6 @@
 use anyhow::Result;
 use rmcp::{transport::stdio, ServiceExt};
-use solscan_mcp::SolscanApi;
+use solscan_mcp::{config::Config, labels::LabelStore, SolscanApi};
 use tracing_subscriber::{self, EnvFilter};
 
 mod solscan_mcp;
@@ -14,13 +14,27 @@ async fn main() -> Result<()> {
         .with_ansi(false)
         .init();
 
-    // Get API key from env
-    let api_key =
-        std::env::var("SOLSCAN_API_KEY").expect("SOLSCAN_API_KEY environment variable is required");
+    // Load settings from the config file, environment and command line
+    // Configuration errors are user errors, so report them without a backtrace
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::error!("Invalid configuration: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    let labels = match LabelStore::load(&config.labels) {
+        Ok(labels) => labels,
+        Err(e) => {
+            tracing::error!("Invalid label file: {}", e);
+            std::process::exit(2);
+        }
+    };
 
     tracing::info!("Starting Solscan MCP server");
 
-    let service = SolscanApi::new(api_key)
+    let service = SolscanApi::new(config, labels)?
         .serve(stdio())
         .await
         .inspect_err(|e| {
@@ -0,0 +1,415 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use serde_json::{json, Value};
+
+use super::{f64_field, i64_field, str_field};
+
+// Shared counterparties listed per link as evidence
+const MAX_EVIDENCE_COUNTERPARTIES: usize = 5;
+
+/// Thresholds for linking two wallets
+#[derive(Debug, Clone, Copy)]
+pub struct ClusterRules {
+    /// Wallets first funded within this many seconds of each other are linked
+    pub time_window: i64,
+    /// Wallets sharing at least this many counterparties are linked
+    pub min_shared_counterparties: usize,
+    /// Counterparties seen with more of the wallets than this are hubs, such as exchanges or
+    /// popular programs, and link nothing. Funders are exempt: one source funding many of the
+    /// wallets is the pattern being looked for.
+    pub max_hub_degree: usize,
+    /// Funders of more of the wallets than this are ignored; without it, only labeled
+    /// funders are
+    pub max_funder_degree: Option<usize>,
+}
+
+/// Transfers gathered for one wallet
+pub struct WalletTransfers {
+    pub address: String,
+    /// Earliest inbound SOL transfers, oldest first, enriched
+    pub funding: Vec<Value>,
+    /// Latest transfers in any token and direction
+    pub recent: Vec<Value>,
+}
+
+struct Funding {
+    funder: String,
+    time: Option<i64>,
+    amount: Option<f64>,
+    trans_id: Option<String>,
+}
+
+struct Wallet {
+    address: String,
+    funding: Option<Funding>,
+    counterparties: BTreeSet<String>,
+}
+
+impl Wallet {
+    fn new(transfers: &WalletTransfers) -> Self {
+        let funding = transfers.funding.iter().find_map(|transfer| {
+            let funder = str_field(transfer, "from_address")?;
+            (funder != transfers.address).then(|| Funding {
+                funder: funder.to_string(),
+                time: i64_field(transfer, "block_time"),
+                amount: f64_field(transfer, "ui_amount"),
+                trans_id: str_field(transfer, "trans_id").map(str::to_string),
+            })
+        });
+
+        let counterparties = transfers
+            .funding
+            .iter()
+            .chain(&transfers.recent)
+            .flat_map(|transfer| {
+                [
+                    str_field(transfer, "from_address"),
+                    str_field(transfer, "to_address"),
+                ]
+            })
+            .flatten()
+            .filter(|address| *address != transfers.address)
+            .map(str::to_string)
+            .collect();
+
+        Self {
+            address: transfers.address.clone(),
+            funding,
+            counterparties,
+        }
+    }
+}
+
+/// Funders and counterparties of the wallets, to be checked against the label store
+pub fn addresses(wallets: &[WalletTransfers]) -> Vec<String> {
+    let addresses: BTreeSet<String> = wallets
+        .iter()
+        .map(Wallet::new)
+        .flat_map(|wallet| {
+            wallet
+                .funding
+                .map(|funding| funding.funder)
+                .into_iter()
+                .chain(wallet.counterparties)
+        })
+        .collect();
+    addresses.into_iter().collect()
+}
+
+/// Union-find over wallet indexes
+struct Clusters {
+    parent: Vec<usize>,
+}
+
+impl Clusters {
+    fn new(size: usize) -> Self {
+        Self {
+            parent: (0..size).collect(),
+        }
+    }
+
+    fn find(&mut self, node: usize) -> usize {
+        let mut root = node;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        self.parent[node] = root;
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[a.max(b)] = a.min(b);
+        }
+    }
+}
+
+/// Group wallets linked by a shared funder, close first-funding times or shared
+/// counterparties. Labeled addresses never link wallets, nor do hub counterparties or funders
+/// above `max_funder_degree`. A shared funder or direct
+/// transfers link two wallets on their own; close funding times and shared counterparties
+/// are weak signals that link a pair only together. Links that join no cluster are kept
+/// as `weak_links`.
+pub fn cluster(
+    wallets: &[WalletTransfers],
+    rules: ClusterRules,
+    labeled: &HashSet<String>,
+) -> Value {
+    let mut wallets: Vec<Wallet> = wallets.iter().map(Wallet::new).collect();
+
+    // Number of wallets each funder or counterparty is seen with
+    let mut degrees: BTreeMap<String, usize> = BTreeMap::new();
+    for wallet in &wallets {
+        let funder = wallet.funding.as_ref().map(|funding| &funding.funder);
+        let seen: BTreeSet<&String> = wallet.counterparties.iter().chain(funder).collect();
+        for address in seen {
+            *degrees.entry(address.clone()).or_default() += 1;
+        }
+    }
+    let members: HashSet<&String> = wallets.iter().map(|wallet| &wallet.address).collect();
+    let mut ignored: BTreeMap<String, Value> = degrees
+        .into_iter()
+        .filter(|(address, _)| !members.contains(address))
+        .filter_map(|(address, degree)| {
+            let reason = if labeled.contains(&address) {
+                "labeled"
+            } else if degree > rules.max_hub_degree {
+                "hub"
+            } else {
+                return None;
+            };
+            Some((address, json!({ "reason": reason, "wallets": degree })))
+        })
+        .collect();
+    for wallet in &mut wallets {
+        wallet
+            .counterparties
+            .retain(|address| !ignored.contains_key(address));
+    }
+
+    let mut clusters = Clusters::new(wallets.len());
+    // Evidence with the wallet indexes it links
+    let mut links: Vec<(Vec<usize>, Value)> = Vec::new();
+    // Weak signals seen per pair of wallets
+    let mut weak: BTreeMap<(usize, usize), usize> = BTreeMap::new();
+
+    let mut funders: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (index, wallet) in wallets.iter().enumerate() {
+        if let Some(funding) = &wallet.funding {
+            if !labeled.contains(&funding.funder) {
+                funders.entry(&funding.funder).or_default().push(index);
+            }
+        }
+    }
+    if let Some(max_funder_degree) = rules.max_funder_degree {
+        funders.retain(|funder, funded| {
+            let keep = funded.len() <= max_funder_degree;
+            if !keep {
+                ignored.insert(
+                    funder.to_string(),
+                    json!({ "reason": "hub", "wallets": funded.len() }),
+                );
+            }
+            keep
+        });
+    }
+    for (funder, funded) in funders.into_iter().filter(|(_, funded)| funded.len() > 1) {
+        let trans_ids: Vec<&Option<String>> = funded
+            .iter()
+            .filter_map(|index| wallets[*index].funding.as_ref())
+            .map(|funding| &funding.trans_id)
+            .collect();
+        for pair in funded.windows(2) {
+            clusters.union(pair[0], pair[1]);
+        }
+        links.push((
+            funded.clone(),
+            json!({
+                "type": "shared_funder",
+                "funder": funder,
+                "wallets": funded.iter().map(|index| &wallets[*index].address).collect::<Vec<_>>(),
+                "trans_ids": trans_ids,
+            }),
+        ));
+    }
+
+    for a in 0..wallets.len() {
+        for b in a + 1..wallets.len() {
+            let (wallet_a, wallet_b) = (&wallets[a], &wallets[b]);
+
+            let times = (
+                wallet_a.funding.as_ref().and_then(|funding| funding.time),
+                wallet_b.funding.as_ref().and_then(|funding| funding.time),
+            );
+            if let (Some(time_a), Some(time_b)) = times {
+                let gap = (time_a - time_b).abs();
+                if gap <= rules.time_window {
+                    *weak.entry((a, b)).or_default() += 1;
+                    links.push((
+                        vec![a, b],
+                        json!({
+                            "type": "funding_time",
+                            "wallets": [wallet_a.address, wallet_b.address],
+                            "funded_times": [time_a, time_b],
+                            "gap_seconds": gap,
+                        }),
+                    ));
+                }
+            }
+
+            // Transfers between the two wallets count as a shared counterparty of each
+            let shared: Vec<&String> = wallet_a
+                .counterparties
+                .intersection(&wallet_b.counterparties)
+                .collect();
+            let direct = wallet_a.counterparties.contains(&wallet_b.address);
+            if direct || shared.len() >= rules.min_shared_counterparties {
+                if direct {
+                    clusters.union(a, b);
+                } else {
+                    *weak.entry((a, b)).or_default() += 1;
+                }
+                links.push((
+                    vec![a, b],
+                    json!({
+                        "type": "shared_counterparties",
+                        "wallets": [wallet_a.address, wallet_b.address],
+                        "direct_transfers": direct,
+                        "shared": shared.len(),
+                        "counterparties": shared
+                            .iter()
+                            .take(MAX_EVIDENCE_COUNTERPARTIES)
+                            .collect::<Vec<_>>(),
+                    }),
+                ));
+            }
+        }
+    }
+
+    // A pair with both weak signals is linked; a single weak signal links nothing
+    for ((a, b), signals) in weak {
+        if signals > 1 {
+            clusters.union(a, b);
+        }
+    }
+
+    let mut groups: BTreeMap<usize, (Vec<usize>, Vec<Value>)> = BTreeMap::new();
+    for index in 0..wallets.len() {
+        let root = clusters.find(index);
+        groups.entry(root).or_default().0.push(index);
+    }
+    let mut weak_links = Vec::new();
+    for (linked, evidence) in links {
+        let root = clusters.find(linked[0]);
+        if linked[1..]
+            .iter()
+            .all(|index| clusters.find(*index) == root)
+        {
+            if let Some((_, group)) = groups.get_mut(&root) {
+                group.push(evidence);
+            }
+        } else {
+            weak_links.push(evidence);
+        }
+    }
+
+    let (clustered, unclustered): (Vec<_>, Vec<_>) = groups
+        .into_values()
+        .partition(|(members, _)| members.len() > 1);
+
+    let mut clustered: Vec<Value> = clustered
+        .into_iter()
+        .map(|(members, evidence)| {
+            let mut signals: BTreeSet<&str> = BTreeSet::new();
+            for item in &evidence {
+                if let Some(signal) = str_field(item, "type") {
+                    signals.insert(signal);
+                }
+            }
+            json!({
+                "size": members.len(),
+                "wallets": members.iter().map(|index| &wallets[*index].address).collect::<Vec<_>>(),
+                "signals": signals,
+                "evidence": evidence,
+            })
+        })
+        .collect();
+    clustered
+        .sort_by_key(|cluster| std::cmp::Reverse(cluster["size"].as_u64().unwrap_or_default()));
+
+    let summaries: Vec<Value> = wallets
+        .iter()
+        .map(|wallet| {
+            json!({
+                "address": wallet.address,
+                "funder": wallet.funding.as_ref().map(|funding| &funding.funder),
+                "funded_time": wallet.funding.as_ref().and_then(|funding| funding.time),
+                "funding_amount_sol": wallet.funding.as_ref().and_then(|funding| funding.amount),
+                "funding_trans_id": wallet.funding.as_ref().and_then(|funding| funding.trans_id.as_ref()),
+                "counterparties": wallet.counterparties.len(),
+            })
+        })
+        .collect();
+
+    json!({
+        "clusters": clustered,
+        "unclustered": unclustered
+            .into_iter()
+            .flat_map(|(members, _)| members)
+            .map(|index| &wallets[index].address)
+            .collect::<Vec<_>>(),
+        "wallets": summaries,
+        "weak_links": weak_links,
+        "ignored": ignored,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FUNDER: &str = "FundeR1111111111111111111111111111111111111";
+
+    fn rules() -> ClusterRules {
+        ClusterRules {
+            time_window: 600,
+            min_shared_counterparties: 2,
+            max_hub_degree: 10,
+            max_funder_degree: None,
+        }
+    }
+
+    // A wallet whose only transfer is its funding by `funder`, a day after the previous one
+    fn funded_wallet(index: i64, funder: &str) -> WalletTransfers {
+        let address = format!("Wallet{index}");
+        WalletTransfers {
+            funding: vec![json!({
+                "trans_id": format!("sig{index}"),
+                "block_time": 1_700_000_000 + index * 86_400,
+                "from_address": funder,
+                "to_address": address,
+                "ui_amount": 0.1,
+            })],
+            recent: Vec::new(),
+            address,
+        }
+    }
+
+    #[test]
+    fn clusters_wallets_of_a_funder_above_the_hub_degree() {
+        let wallets: Vec<WalletTransfers> = (0..12).map(|i| funded_wallet(i, FUNDER)).collect();
+
+        let result = cluster(&wallets, rules(), &HashSet::new());
+
+        let clusters = result["clusters"].as_array().unwrap();
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0]["size"], 12);
+        assert_eq!(clusters[0]["signals"], json!(["shared_funder"]));
+    }
+
+    #[test]
+    fn ignores_labeled_funders() {
+        let wallets: Vec<WalletTransfers> = (0..12).map(|i| funded_wallet(i, FUNDER)).collect();
+        let labeled = HashSet::from([FUNDER.to_string()]);
+
+        let result = cluster(&wallets, rules(), &labeled);
+
+        assert_eq!(result["clusters"], json!([]));
+        assert_eq!(result["ignored"][FUNDER]["reason"], "labeled");
+    }
+
+    #[test]
+    fn ignores_funders_above_the_funder_degree() {
+        let wallets: Vec<WalletTransfers> = (0..12).map(|i| funded_wallet(i, FUNDER)).collect();
+        let rules = ClusterRules {
+            max_funder_degree: Some(11),
+            ..rules()
+        };
+
+        let result = cluster(&wallets, rules, &HashSet::new());
+
+        assert_eq!(result["clusters"], json!([]));
+        assert_eq!(result["ignored"][FUNDER]["reason"], "hub");
+    }
+}
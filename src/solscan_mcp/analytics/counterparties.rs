@@ -0,0 +1,150 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde_json::{json, Value};
+
+use super::{f64_field, i64_field, round, str_field, usd_value};
+
+/// Order of the counterparties in the result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    ValueUsd,
+    Transactions,
+    LastTime,
+    FirstTime,
+}
+
+impl std::str::FromStr for SortBy {
+    type Err = String;
+
+    fn from_str(sort_by: &str) -> Result<Self, Self::Err> {
+        match sort_by.to_ascii_lowercase().as_str() {
+            "value_usd" | "value" => Ok(Self::ValueUsd),
+            "transactions" | "count" => Ok(Self::Transactions),
+            "last_time" => Ok(Self::LastTime),
+            "first_time" => Ok(Self::FirstTime),
+            _ => Err(format!(
+                "unknown sort_by {:?}, expected value_usd, transactions, last_time or first_time",
+                sort_by
+            )),
+        }
+    }
+}
+
+#[derive(Default)]
+struct TokenFlow {
+    in_amount: f64,
+    out_amount: f64,
+    in_transfers: usize,
+    out_transfers: usize,
+}
+
+#[derive(Default)]
+struct Counterparty {
+    transactions: HashSet<String>,
+    transfers: usize,
+    inbound_usd: f64,
+    outbound_usd: f64,
+    first_time: Option<i64>,
+    last_time: Option<i64>,
+    tokens: BTreeMap<String, TokenFlow>,
+}
+
+impl Counterparty {
+    fn value_usd(&self) -> f64 {
+        self.inbound_usd + self.outbound_usd
+    }
+}
+
+/// Aggregate an account's enriched transfers per counterparty address.
+///
+/// `symbols` maps token addresses to symbols for display; `limit` caps the number of
+/// counterparties returned after sorting.
+pub fn aggregate(
+    transfers: &[Value],
+    symbols: &HashMap<String, String>,
+    sort_by: SortBy,
+    limit: usize,
+) -> Vec<Value> {
+    let mut counterparties: HashMap<&str, Counterparty> = HashMap::new();
+
+    for transfer in transfers {
+        let (counterparty, inbound) = match str_field(transfer, "flow") {
+            Some("in") => (str_field(transfer, "from_address"), true),
+            Some("out") => (str_field(transfer, "to_address"), false),
+            _ => continue,
+        };
+        let Some(counterparty) = counterparty else {
+            continue;
+        };
+
+        let entry = counterparties.entry(counterparty).or_default();
+        let usd = usd_value(transfer).unwrap_or_default();
+        let amount = f64_field(transfer, "ui_amount").unwrap_or_default();
+
+        entry.transfers += 1;
+        if let Some(trans_id) = str_field(transfer, "trans_id") {
+            entry.transactions.insert(trans_id.to_string());
+        }
+        if let Some(time) = i64_field(transfer, "block_time") {
+            entry.first_time = Some(entry.first_time.map_or(time, |first| first.min(time)));
+            entry.last_time = Some(entry.last_time.map_or(time, |last| last.max(time)));
+        }
+
+        let token = str_field(transfer, "token_address").unwrap_or_default();
+        let flow = entry.tokens.entry(token.to_string()).or_default();
+        if inbound {
+            entry.inbound_usd += usd;
+            flow.in_amount += amount;
+            flow.in_transfers += 1;
+        } else {
+            entry.outbound_usd += usd;
+            flow.out_amount += amount;
+            flow.out_transfers += 1;
+        }
+    }
+
+    let mut counterparties: Vec<(&str, Counterparty)> = counterparties.into_iter().collect();
+    counterparties.sort_by(|(a_address, a), (b_address, b)| {
+        let order = match sort_by {
+            SortBy::ValueUsd => b.value_usd().total_cmp(&a.value_usd()),
+            SortBy::Transactions => b.transactions.len().cmp(&a.transactions.len()),
+            SortBy::LastTime => b.last_time.cmp(&a.last_time),
+            SortBy::FirstTime => a.first_time.cmp(&b.first_time),
+        };
+        order.then(a_address.cmp(b_address))
+    });
+
+    counterparties
+        .into_iter()
+        .take(limit)
+        .map(|(address, counterparty)| {
+            let tokens: Vec<Value> = counterparty
+                .tokens
+                .iter()
+                .map(|(token, flow)| {
+                    json!({
+                        "token_address": token,
+                        "symbol": symbols.get(token),
+                        "in_amount": flow.in_amount,
+                        "out_amount": flow.out_amount,
+                        "net_amount": flow.in_amount - flow.out_amount,
+                        "in_transfers": flow.in_transfers,
+                        "out_transfers": flow.out_transfers,
+                    })
+                })
+                .collect();
+
+            json!({
+                "address": address,
+                "transactions": counterparty.transactions.len(),
+                "transfers": counterparty.transfers,
+                "value_usd": round(counterparty.value_usd(), 2),
+                "inbound_usd": round(counterparty.inbound_usd, 2),
+                "outbound_usd": round(counterparty.outbound_usd, 2),
+                "first_time": counterparty.first_time,
+                "last_time": counterparty.last_time,
+                "tokens": tokens,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,559 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use serde_json::{json, Value};
+
+use super::liquidity::program_name;
+use super::sandwich::{self, SwapLeg};
+use super::{f64_field, round, str_field};
+use crate::solscan_mcp::enrich::{as_u32, SOL_ADDRESS, SOL_DECIMALS};
+
+// Programs most transactions go through, named when Solscan does not name them
+const CORE_PROGRAMS: [(&str, &str); 13] = [
+    ("11111111111111111111111111111111", "System Program"),
+    (
+        "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+        "Token Program",
+    ),
+    (
+        "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb",
+        "Token-2022 Program",
+    ),
+    (
+        "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL",
+        "Associated Token Account Program",
+    ),
+    (
+        "ComputeBudget111111111111111111111111111111",
+        "Compute Budget Program",
+    ),
+    (
+        "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr",
+        "Memo Program",
+    ),
+    (
+        "Memo1UhkJRfHyvLMcVucJwxXeuD728EqVDDwQDxFMNo",
+        "Memo Program v1",
+    ),
+    (
+        "Stake11111111111111111111111111111111111111",
+        "Stake Program",
+    ),
+    (
+        "Vote111111111111111111111111111111111111111",
+        "Vote Program",
+    ),
+    (
+        "AddressLookupTab1e1111111111111111111111111",
+        "Address Lookup Table Program",
+    ),
+    ("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4", "Jupiter v6"),
+    (
+        "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s",
+        "Metaplex Token Metadata",
+    ),
+    ("6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P", "Pump.fun"),
+];
+
+/// A token movement that is not a leg of a swap, amount in token units
+#[derive(Debug, Clone)]
+struct Transfer {
+    from: Option<String>,
+    to: Option<String>,
+    token: String,
+    /// In token units, or the raw integer amount when the decimals are unknown
+    amount: f64,
+    raw: bool,
+}
+
+/// An account created or closed by the transaction: `counterparty` funded the creation or
+/// received the rent of the closure
+#[derive(Debug, Clone)]
+struct AccountChange {
+    account: String,
+    counterparty: Option<String>,
+    program: Option<String>,
+}
+
+/// What a transaction did, decoded from `/transaction/detail` and `/transaction/actions`
+#[derive(Debug, Default)]
+pub struct Transaction {
+    block_time: Option<i64>,
+    signer: Option<String>,
+    fee: Option<f64>,
+    succeeded: bool,
+    error: Option<String>,
+    /// Program ids in order of first invocation
+    programs: Vec<(String, Option<String>)>,
+    swaps: Vec<SwapLeg>,
+    /// Swaps left out because the decimals of one of their tokens are unknown
+    unscaled_swaps: usize,
+    transfers: Vec<Transfer>,
+    created: Vec<AccountChange>,
+    closed: Vec<AccountChange>,
+}
+
+impl Transaction {
+    /// Decode a transaction from its detail and, when available, its parsed actions
+    pub fn parse(detail: &Value, actions: Option<&Value>) -> Self {
+        let data = &detail["data"];
+        let action_data = actions.map(|actions| &actions["data"]);
+        let instructions = instructions(data);
+
+        let mut programs: Vec<(String, Option<String>)> = Vec::new();
+        for instruction in &instructions {
+            let Some(program) = str_field(instruction, "program_id") else {
+                continue;
+            };
+            if programs.iter().all(|(id, _)| id != program) {
+                let name = str_field(instruction, "program")
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_string)
+                    .or_else(|| known_program(program).map(str::to_string));
+                programs.push((program.to_string(), name));
+            }
+        }
+        if programs.is_empty() {
+            programs = data
+                .get("programs_involved")
+                .and_then(Value::as_array)
+                .map(Vec::as_slice)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(Value::as_str)
+                .map(|program| {
+                    let name = known_program(program).map(str::to_string);
+                    (program.to_string(), name)
+                })
+                .collect();
+        }
+
+        let activities = action_data
+            .and_then(|data| data.get("activities"))
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let swap_instructions: HashSet<i64> = activities
+            .iter()
+            .filter(|activity| activity_type(activity).contains("swap"))
+            .filter_map(top_instruction)
+            .collect();
+
+        let mut transfers = Vec::new();
+        let (mut created, mut closed) = account_changes(&instructions);
+        for transfer in action_data
+            .and_then(|data| data.get("transfers"))
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+        {
+            let kind = activity_type(transfer);
+            let from = str_field(transfer, "source_owner")
+                .or_else(|| str_field(transfer, "source"))
+                .map(str::to_string);
+            let to = str_field(transfer, "destination_owner")
+                .or_else(|| str_field(transfer, "destination"))
+                .map(str::to_string);
+
+            // Rent moved by account creations and closures is reported with the account
+            if kind.contains("create_account") {
+                if let Some(account) = str_field(transfer, "destination") {
+                    add_change(&mut created, account, from, None);
+                }
+                continue;
+            }
+            if kind.contains("close_account") {
+                if let Some(account) = str_field(transfer, "source") {
+                    add_change(&mut closed, account, to, None);
+                }
+                continue;
+            }
+            if top_instruction(transfer).is_some_and(|index| swap_instructions.contains(&index)) {
+                continue;
+            }
+
+            let Some(token) = str_field(transfer, "token_address") else {
+                continue;
+            };
+            let decimals = transfer
+                .get("decimals")
+                .and_then(as_u32)
+                .or_else(|| (token == SOL_ADDRESS).then_some(SOL_DECIMALS));
+            let Some(amount) = f64_field(transfer, "amount") else {
+                continue;
+            };
+            transfers.push(Transfer {
+                from,
+                to,
+                token: token.to_string(),
+                amount: decimals.map_or(amount, |decimals| amount / 10f64.powi(decimals as i32)),
+                raw: decimals.is_none(),
+            });
+        }
+        for activity in activities {
+            let kind = activity_type(activity);
+            let data = &activity["data"];
+            let program = str_field(activity, "program_id").map(str::to_string);
+            let account = str_field(data, "account").or_else(|| str_field(data, "new_account"));
+            match account {
+                Some(account) if kind.contains("create_account") => {
+                    let funder = str_field(data, "source").map(str::to_string);
+                    add_change(&mut created, account, funder, program);
+                }
+                Some(account) if kind.contains("close_account") => {
+                    let destination = str_field(data, "destination").map(str::to_string);
+                    add_change(&mut closed, account, destination, program);
+                }
+                _ => {}
+            }
+        }
+
+        let (succeeded, error) = outcome(data);
+        let (swaps, unscaled_swaps) = actions.map(sandwich::swap_legs).unwrap_or_default();
+        Self {
+            block_time: data
+                .get("block_time")
+                .or_else(|| action_data?.get("block_time"))
+                .and_then(Value::as_i64),
+            signer: sandwich::signer(data)
+                .or_else(|| data.get("list_signer")?.as_array()?.first()?.as_str())
+                .map(str::to_string),
+            fee: f64_field(data, "fee")
+                .or_else(|| f64_field(action_data?, "fee"))
+                .map(|fee| fee / 10f64.powi(SOL_DECIMALS as i32)),
+            succeeded,
+            error,
+            programs,
+            swaps,
+            unscaled_swaps,
+            transfers,
+            created,
+            closed,
+        }
+    }
+
+    /// What could not be decoded: swaps and transfers whose token decimals are unknown
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.unscaled_swaps > 0 {
+            warnings.push(format!(
+                "{} swaps left out: token decimals unknown",
+                self.unscaled_swaps
+            ));
+        }
+        let raw = self
+            .transfers
+            .iter()
+            .filter(|transfer| transfer.raw)
+            .count();
+        if raw > 0 {
+            warnings.push(format!(
+                "{} transfers in raw units: token decimals unknown",
+                raw
+            ));
+        }
+        warnings
+    }
+
+    /// Tokens swapped or transferred, to be named with their symbols
+    pub fn tokens(&self) -> BTreeSet<String> {
+        self.swaps
+            .iter()
+            .flat_map(|swap| [swap.token_in.clone(), swap.token_out.clone()])
+            .chain(self.transfers.iter().map(|transfer| transfer.token.clone()))
+            .collect()
+    }
+
+    /// The decoded transaction with a `narrative` that tells, in a fixed order, who signed
+    /// and paid for it, the programs it invoked, its swaps, transfers, account creations and
+    /// closures, and whether it succeeded. Tokens are named with `symbols` when known.
+    pub fn explain(&self, tx: &str, symbols: &HashMap<String, String>) -> Value {
+        let symbol = |token: &str| {
+            symbols
+                .get(token)
+                .cloned()
+                .unwrap_or_else(|| token.to_string())
+        };
+        let mut sentences = Vec::new();
+
+        match (&self.signer, self.fee) {
+            (Some(signer), Some(fee)) => sentences.push(format!(
+                "Signed by {} with a fee of {} SOL.",
+                signer,
+                amount_text(fee)
+            )),
+            (Some(signer), None) => sentences.push(format!("Signed by {}.", signer)),
+            (None, Some(fee)) => sentences.push(format!("Fee paid: {} SOL.", amount_text(fee))),
+            (None, None) => {}
+        }
+
+        let program_names: Vec<String> = self
+            .programs
+            .iter()
+            .map(|(id, name)| name.clone().unwrap_or_else(|| id.clone()))
+            .collect();
+        if !program_names.is_empty() {
+            sentences.push(format!("Invoked {}.", program_names.join(", ")));
+        }
+
+        for swap in &self.swaps {
+            let venue = swap
+                .program
+                .as_deref()
+                .and_then(known_program)
+                .map(|name| format!("on {} pool {}", name, swap.pool))
+                .unwrap_or_else(|| format!("in pool {}", swap.pool));
+            sentences.push(format!(
+                "Swapped {} {} for {} {} {}.",
+                amount_text(swap.amount_in),
+                symbol(&swap.token_in),
+                amount_text(swap.amount_out),
+                symbol(&swap.token_out),
+                venue
+            ));
+        }
+
+        for transfer in &self.transfers {
+            let amount = if transfer.raw {
+                format!("{} raw units of", amount_text(transfer.amount))
+            } else {
+                amount_text(transfer.amount)
+            };
+            sentences.push(format!(
+                "Transferred {} {} from {} to {}.",
+                amount,
+                symbol(&transfer.token),
+                transfer.from.as_deref().unwrap_or("an unknown account"),
+                transfer.to.as_deref().unwrap_or("an unknown account")
+            ));
+        }
+
+        for change in &self.created {
+            sentences.push(match &change.counterparty {
+                Some(funder) => {
+                    format!("Created account {}, funded by {}.", change.account, funder)
+                }
+                None => format!("Created account {}.", change.account),
+            });
+        }
+        for change in &self.closed {
+            sentences.push(match &change.counterparty {
+                Some(destination) => format!(
+                    "Closed account {}, returning its rent to {}.",
+                    change.account, destination
+                ),
+                None => format!("Closed account {}.", change.account),
+            });
+        }
+
+        sentences.push(match (self.succeeded, &self.error) {
+            (true, _) => "The transaction succeeded.".to_string(),
+            (false, Some(error)) => format!("The transaction failed: {}.", error),
+            (false, None) => "The transaction failed.".to_string(),
+        });
+
+        let programs: Vec<Value> = self
+            .programs
+            .iter()
+            .map(|(id, name)| json!({ "program_id": id, "name": name }))
+            .collect();
+        let swaps: Vec<Value> = self
+            .swaps
+            .iter()
+            .map(|swap| {
+                let mut value = sandwich::leg_value(swap);
+                value["symbol_in"] = json!(symbols.get(&swap.token_in));
+                value["symbol_out"] = json!(symbols.get(&swap.token_out));
+                value
+            })
+            .collect();
+        let transfers: Vec<Value> = self
+            .transfers
+            .iter()
+            .map(|transfer| {
+                json!({
+                    "from_address": transfer.from,
+                    "to_address": transfer.to,
+                    "token_address": transfer.token,
+                    "symbol": symbols.get(&transfer.token),
+                    "ui_amount": (!transfer.raw).then(|| round(transfer.amount, 9)),
+                    "raw_amount": transfer.raw.then_some(transfer.amount),
+                })
+            })
+            .collect();
+        let changes = |changes: &[AccountChange], counterparty: &str| -> Vec<Value> {
+            changes
+                .iter()
+                .map(|change| {
+                    json!({
+                        "account": change.account,
+                        counterparty: change.counterparty,
+                        "program_id": change.program,
+                    })
+                })
+                .collect()
+        };
+
+        json!({
+            "tx": tx,
+            "block_time": self.block_time,
+            "status": if self.succeeded { "success" } else { "failed" },
+            "error": self.error,
+            "signer": self.signer,
+            "fee_sol": self.fee,
+            "programs": programs,
+            "swaps": swaps,
+            "transfers": transfers,
+            "accounts_created": changes(&self.created, "funded_by"),
+            "accounts_closed": changes(&self.closed, "rent_to"),
+            "narrative": sentences.join(" "),
+        })
+    }
+}
+
+fn known_program(program: &str) -> Option<&'static str> {
+    CORE_PROGRAMS
+        .iter()
+        .find(|(id, _)| *id == program)
+        .map(|(_, name)| *name)
+        .or_else(|| program_name(program))
+}
+
+/// Instructions of a `/transaction/detail` response with their inner instructions, in
+/// execution order
+fn instructions(data: &Value) -> Vec<&Value> {
+    fn walk<'a>(instructions: &'a [Value], all: &mut Vec<&'a Value>) {
+        for instruction in instructions {
+            all.push(instruction);
+            if let Some(inner) = instruction
+                .get("inner_instructions")
+                .and_then(Value::as_array)
+            {
+                walk(inner, all);
+            }
+        }
+    }
+
+    let mut all = Vec::new();
+    if let Some(instructions) = data.get("parsed_instructions").and_then(Value::as_array) {
+        walk(instructions, &mut all);
+    }
+    all
+}
+
+/// `activity_type` (or `transfer_type`, `name`) in lower case
+fn activity_type(activity: &Value) -> String {
+    str_field(activity, "activity_type")
+        .or_else(|| str_field(activity, "transfer_type"))
+        .or_else(|| str_field(activity, "name"))
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+/// Index of the top-level instruction an activity or transfer belongs to
+fn top_instruction(item: &Value) -> Option<i64> {
+    match item.get("outer_ins_index").and_then(Value::as_i64) {
+        Some(outer) if outer >= 0 => Some(outer),
+        _ => item.get("ins_index").and_then(Value::as_i64),
+    }
+}
+
+fn add_change(
+    changes: &mut Vec<AccountChange>,
+    account: &str,
+    counterparty: Option<String>,
+    program: Option<String>,
+) {
+    match changes.iter_mut().find(|change| change.account == account) {
+        Some(change) => {
+            change.counterparty = change.counterparty.take().or(counterparty);
+            change.program = change.program.take().or(program);
+        }
+        None => changes.push(AccountChange {
+            account: account.to_string(),
+            counterparty,
+            program,
+        }),
+    }
+}
+
+/// Accounts created and closed by the parsed System, Token and Associated Token instructions
+fn account_changes(instructions: &[&Value]) -> (Vec<AccountChange>, Vec<AccountChange>) {
+    let (mut created, mut closed) = (Vec::new(), Vec::new());
+    for instruction in instructions {
+        let kind = str_field(instruction, "parsed_type")
+            .or_else(|| str_field(instruction, "type"))
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let info = &instruction["data_raw"]["info"];
+        let program = str_field(instruction, "program_id").map(str::to_string);
+        let text = |key: &str| str_field(info, key).map(str::to_string);
+
+        match kind.as_str() {
+            "createaccount" | "createaccountwithseed" => {
+                if let Some(account) = str_field(info, "newAccount") {
+                    add_change(&mut created, account, text("source"), program);
+                }
+            }
+            "create" | "createidempotent" => {
+                if let Some(account) = str_field(info, "account") {
+                    add_change(&mut created, account, text("source"), program);
+                }
+            }
+            "closeaccount" => {
+                if let Some(account) = str_field(info, "account") {
+                    add_change(&mut closed, account, text("destination"), program);
+                }
+            }
+            _ => {}
+        }
+    }
+    (created, closed)
+}
+
+/// Whether the transaction succeeded, and its error. Solscan gives `status` as `1` or
+/// "Success" for a successful transaction; the error is the `err` field or, failing that,
+/// the last log line reporting a failure.
+fn outcome(data: &Value) -> (bool, Option<String>) {
+    let status_ok = match data.get("status") {
+        Some(Value::Number(status)) => status.as_i64() == Some(1),
+        Some(Value::String(status)) => matches!(
+            status.to_ascii_lowercase().as_str(),
+            "success" | "succeeded" | "1"
+        ),
+        Some(Value::Bool(status)) => *status,
+        _ => true,
+    };
+    let error = ["err", "error"]
+        .iter()
+        .filter_map(|key| data.get(*key))
+        .find(|error| !error.is_null())
+        .map(|error| match error {
+            Value::String(error) => error.clone(),
+            error => error.to_string(),
+        });
+    let succeeded = status_ok && error.is_none();
+
+    let error = error.or_else(|| {
+        if succeeded {
+            return None;
+        }
+        data.get("log_message")
+            .and_then(Value::as_array)?
+            .iter()
+            .filter_map(Value::as_str)
+            .rev()
+            .find(|line| line.contains("failed") || line.contains("Error"))
+            .map(str::to_string)
+    });
+    (succeeded, error)
+}
+
+/// Token amount without float noise or trailing zeros
+fn amount_text(amount: f64) -> String {
+    let amount = round(amount, 9);
+    if amount.fract() == 0.0 {
+        format!("{:.0}", amount)
+    } else {
+        amount.to_string()
+    }
+}
@@ -0,0 +1,576 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use chrono::{DateTime, NaiveDateTime};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use super::pnl::Period;
+use super::round;
+use crate::solscan_mcp::enrich::SOL_DECIMALS;
+
+// Headers quoted in a missing column error, enough to recognise an error body
+const MAX_QUOTED_HEADER_CHARS: usize = 200;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ExportError {
+    #[error("CSV parsing error: {0}")]
+    Csv(#[from] csv::Error),
+    #[error("the {export} export has no {missing} column (headers: {headers})")]
+    MissingColumn {
+        export: &'static str,
+        missing: &'static str,
+        headers: String,
+    },
+}
+
+/// One row of an `/account/reward/export` CSV, amounts in SOL
+#[derive(Debug, Clone, Serialize)]
+pub struct RewardRow {
+    pub epoch: Option<u64>,
+    #[serde(rename = "block_time")]
+    pub time: i64,
+    #[serde(rename = "reward_sol")]
+    pub amount: f64,
+    /// Balance of the stake account after the reward
+    #[serde(rename = "post_balance_sol")]
+    pub post_balance: Option<f64>,
+}
+
+/// One row of an `/account/transfer/export` CSV, the amount in token units
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferRow {
+    pub trans_id: Option<String>,
+    #[serde(rename = "block_time")]
+    pub time: i64,
+    #[serde(rename = "activity_type")]
+    pub action: Option<String>,
+    #[serde(rename = "from_address")]
+    pub from: Option<String>,
+    #[serde(rename = "to_address")]
+    pub to: Option<String>,
+    #[serde(rename = "token_address")]
+    pub token: Option<String>,
+    #[serde(rename = "ui_amount")]
+    pub amount: f64,
+    pub flow: Option<String>,
+    pub value_usd: Option<f64>,
+}
+
+/// What the rows of an export are grouped by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    Token,
+    Counterparty,
+    Action,
+    Flow,
+    Period(Period),
+}
+
+impl std::str::FromStr for GroupBy {
+    type Err = String;
+
+    fn from_str(group_by: &str) -> Result<Self, Self::Err> {
+        match group_by.to_ascii_lowercase().as_str() {
+            "token" => Ok(Self::Token),
+            "counterparty" => Ok(Self::Counterparty),
+            "action" | "activity_type" => Ok(Self::Action),
+            "flow" => Ok(Self::Flow),
+            period => period.parse().map(Self::Period).map_err(|_| {
+                format!(
+                    "unknown group_by {:?}, expected token, counterparty, action, flow, day, \
+                     week or month",
+                    group_by
+                )
+            }),
+        }
+    }
+}
+
+/// Client-side filters applied to the parsed transfer rows
+#[derive(Debug, Default)]
+pub struct TransferFilter {
+    /// Only transfers sent to or received from this address
+    pub counterparty: Option<String>,
+    /// Only transfers of at least this many token units
+    pub min_amount: Option<f64>,
+}
+
+impl TransferFilter {
+    pub fn matches(&self, row: &TransferRow) -> bool {
+        let counterparty = self.counterparty.as_deref().is_none_or(|address| {
+            row.from.as_deref() == Some(address) || row.to.as_deref() == Some(address)
+        });
+        counterparty && self.min_amount.is_none_or(|min| row.amount >= min)
+    }
+}
+
+/// Column lookup by header name. Export headers are meant for people, so each column is
+/// matched against candidate names in order of preference, anywhere in the header with
+/// `find` or as its first words with `find_word` (`from` matches "From Address" but not
+/// "Token Address").
+struct Columns {
+    headers: Vec<String>,
+}
+
+impl Columns {
+    fn new(headers: &csv::StringRecord) -> Self {
+        Self {
+            headers: headers
+                .iter()
+                .map(|header| header.trim().to_ascii_lowercase())
+                .collect(),
+        }
+    }
+
+    fn find(&self, names: &[&str]) -> Option<usize> {
+        names
+            .iter()
+            .find_map(|name| self.headers.iter().position(|header| header.contains(name)))
+    }
+
+    fn find_word(&self, names: &[&str]) -> Option<usize> {
+        names.iter().find_map(|name| {
+            self.headers
+                .iter()
+                .position(|header| header == name || header.starts_with(&format!("{} ", name)))
+        })
+    }
+
+    fn is_lamports(&self, column: Option<usize>) -> bool {
+        column.is_some_and(|column| self.headers[column].contains("lamport"))
+    }
+
+    /// Whether an amount column holds raw integer amounts, for the whole export: as its
+    /// header says ("raw", "lamports" or "ui"), otherwise when a decimals column is there to
+    /// scale them
+    fn is_raw(&self, amount: usize, decimals: Option<usize>) -> bool {
+        let header = &self.headers[amount];
+        let has_word = |word: &str| {
+            header
+                .split(|c: char| !c.is_ascii_alphanumeric())
+                .any(|part| part == word)
+        };
+        if has_word("raw") || header.contains("lamport") {
+            true
+        } else if has_word("ui") {
+            false
+        } else {
+            decimals.is_some()
+        }
+    }
+
+    /// A column every row needs: a renamed header or an error body in place of the CSV
+    /// fails here instead of yielding no rows
+    fn required(
+        &self,
+        export: &'static str,
+        missing: &'static str,
+        column: Option<usize>,
+    ) -> Result<usize, ExportError> {
+        column.ok_or_else(|| ExportError::MissingColumn {
+            export,
+            missing,
+            headers: self
+                .headers
+                .join(",")
+                .chars()
+                .take(MAX_QUOTED_HEADER_CHARS)
+                .collect(),
+        })
+    }
+}
+
+fn field(record: &csv::StringRecord, column: Option<usize>) -> Option<&str> {
+    column
+        .and_then(|column| record.get(column))
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+fn number(record: &csv::StringRecord, column: Option<usize>) -> Option<f64> {
+    field(record, column)?.replace(',', "").parse().ok()
+}
+
+/// Parse a reward export into rows
+pub fn reward_rows(export: impl Read) -> Result<Vec<RewardRow>, ExportError> {
+    let mut rows = Vec::new();
+    read_reward_rows(export, |row| {
+        rows.push(row);
+        Ok(())
+    })?;
+    Ok(rows)
+}
+
+/// Parse a reward export one row at a time, handing each to `visit`. Amounts in lamports,
+/// when the header says so, are converted to SOL. Fails when the amount or time column is
+/// missing; rows with an empty amount or time are skipped.
+pub fn read_reward_rows(
+    export: impl Read,
+    mut visit: impl FnMut(RewardRow) -> Result<(), ExportError>,
+) -> Result<(), ExportError> {
+    let mut reader = csv::Reader::from_reader(export);
+    let columns = Columns::new(reader.headers()?);
+
+    let amount = Some(columns.required("reward", "amount", columns.find(&["reward", "amount"]))?);
+    let time = Some(columns.required("reward", "time", columns.find(&["time", "date"]))?);
+    let epoch = columns.find(&["epoch"]);
+    let post_balance = columns.find(&["post", "balance"]);
+    let scale = |column: Option<usize>| {
+        if columns.is_lamports(column) {
+            10f64.powi(SOL_DECIMALS as i32)
+        } else {
+            1.0
+        }
+    };
+    let (amount_scale, balance_scale) = (scale(amount), scale(post_balance));
+
+    for record in reader.records() {
+        let record = record?;
+        let (Some(reward), Some(block_time)) = (
+            number(&record, amount),
+            field(&record, time).and_then(parse_time),
+        ) else {
+            continue;
+        };
+
+        visit(RewardRow {
+            epoch: field(&record, epoch).and_then(|epoch| epoch.parse().ok()),
+            time: block_time,
+            amount: reward / amount_scale,
+            post_balance: number(&record, post_balance).map(|balance| balance / balance_scale),
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Parse a transfer export one row at a time, handing each to `visit`. Whether amounts are
+/// raw is decided once for the export from its header; raw amounts are scaled by the
+/// decimals column. Fails when the amount or time column is missing; rows with an empty
+/// amount or time are skipped.
+pub fn read_transfer_rows(
+    export: impl Read,
+    mut visit: impl FnMut(TransferRow) -> Result<(), ExportError>,
+) -> Result<(), ExportError> {
+    let mut reader = csv::Reader::from_reader(export);
+    let columns = Columns::new(reader.headers()?);
+
+    let trans_id = columns.find(&["signature", "trans id", "tx hash", "transaction"]);
+    // Prefer the Unix timestamp over the human readable date when the export has both
+    let time = columns.find(&["block time", "time", "date"]);
+    let time = Some(columns.required("transfer", "time", time)?);
+    let action = columns.find(&["action", "activity"]);
+    let from = columns.find_word(&["from address", "from"]);
+    let to = columns.find_word(&["to address", "to"]);
+    let token = columns.find(&["token address", "token"]);
+    let amount = columns.required("transfer", "amount", columns.find_word(&["amount"]))?;
+    let decimals = columns.find(&["decimals"]);
+    let is_raw = columns.is_raw(amount, decimals);
+    let amount = Some(amount);
+    let flow = columns.find_word(&["flow"]);
+    let value = columns.find_word(&["value"]);
+
+    let text = |record: &csv::StringRecord, column| field(record, column).map(str::to_string);
+
+    for record in reader.records() {
+        let record = record?;
+        let (Some(raw_amount), Some(block_time)) = (
+            number(&record, amount),
+            field(&record, time).and_then(parse_time),
+        ) else {
+            continue;
+        };
+
+        let scale = match field(&record, decimals).and_then(|value| value.parse::<i32>().ok()) {
+            Some(decimals) if is_raw => 10f64.powi(decimals),
+            _ => 1.0,
+        };
+
+        visit(TransferRow {
+            trans_id: text(&record, trans_id),
+            time: block_time,
+            action: text(&record, action),
+            from: text(&record, from),
+            to: text(&record, to),
+            token: text(&record, token),
+            amount: raw_amount / scale,
+            flow: field(&record, flow).map(str::to_ascii_lowercase),
+            value_usd: number(&record, value),
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Unix timestamp, RFC 3339 or `YYYY-MM-DD HH:MM:SS` in UTC
+fn parse_time(text: &str) -> Option<i64> {
+    let text = text.trim().trim_end_matches(" UTC");
+    text.parse()
+        .ok()
+        .or_else(|| Some(DateTime::parse_from_rfc3339(text).ok()?.timestamp()))
+        .or_else(|| {
+            Some(
+                NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S")
+                    .ok()?
+                    .and_utc()
+                    .timestamp(),
+            )
+        })
+}
+
+/// Totals of the rows sharing one group key
+#[derive(Default)]
+struct Group {
+    rows: usize,
+    first_time: Option<i64>,
+    last_time: Option<i64>,
+    value_usd: f64,
+    /// Amount received and sent per token
+    tokens: BTreeMap<String, (f64, f64)>,
+}
+
+impl Group {
+    fn add(&mut self, time: i64, token: &str, amount: f64, outgoing: bool) {
+        self.rows += 1;
+        self.first_time = Some(self.first_time.map_or(time, |first| first.min(time)));
+        self.last_time = Some(self.last_time.map_or(time, |last| last.max(time)));
+        let totals = self.tokens.entry(token.to_string()).or_default();
+        if outgoing {
+            totals.1 += amount;
+        } else {
+            totals.0 += amount;
+        }
+    }
+}
+
+/// Whether a transfer left `address`: the `flow` column when present, the sender otherwise
+fn is_outgoing(row: &TransferRow, address: &str) -> bool {
+    match row.flow.as_deref() {
+        Some(flow) => flow == "out",
+        None => row.from.as_deref() == Some(address),
+    }
+}
+
+/// The transfers of `address` grouped by `group_by`, added one row at a time
+pub struct TransferGroups {
+    address: String,
+    group_by: GroupBy,
+    groups: BTreeMap<String, Group>,
+}
+
+impl TransferGroups {
+    pub fn new(address: &str, group_by: GroupBy) -> Self {
+        Self {
+            address: address.to_string(),
+            group_by,
+            groups: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, row: &TransferRow) {
+        let outgoing = is_outgoing(row, &self.address);
+        let key = match self.group_by {
+            GroupBy::Token => row.token.clone(),
+            GroupBy::Counterparty if outgoing => row.to.clone(),
+            GroupBy::Counterparty => row.from.clone(),
+            GroupBy::Action => row.action.clone(),
+            GroupBy::Flow => Some(if outgoing { "out" } else { "in" }.to_string()),
+            GroupBy::Period(period) => Some(period.key(row.time)),
+        };
+        let group = self
+            .groups
+            .entry(key.unwrap_or_else(|| "unknown".to_string()))
+            .or_default();
+        group.add(
+            row.time,
+            row.token.as_deref().unwrap_or("unknown"),
+            row.amount,
+            outgoing,
+        );
+        group.value_usd += row.value_usd.unwrap_or_default();
+    }
+
+    /// Largest groups first: row count, first and last time, summed USD value and the
+    /// amount received and sent of every token
+    pub fn into_values(self) -> Vec<Value> {
+        group_values(self.groups)
+    }
+}
+
+fn group_values(groups: BTreeMap<String, Group>) -> Vec<Value> {
+    let mut groups: Vec<(String, Group)> = groups.into_iter().collect();
+    groups.sort_by_key(|(_, group)| std::cmp::Reverse(group.rows));
+    groups
+        .into_iter()
+        .map(|(key, group)| {
+            let tokens: Vec<Value> = group
+                .tokens
+                .iter()
+                .map(|(token, (received, sent))| {
+                    json!({
+                        "token_address": token,
+                        "received": received,
+                        "sent": sent,
+                        "net": received - sent,
+                    })
+                })
+                .collect();
+            json!({
+                "key": key,
+                "rows": group.rows,
+                "first_time": group.first_time,
+                "last_time": group.last_time,
+                "value_usd": round(group.value_usd, 2),
+                "tokens": tokens,
+            })
+        })
+        .collect()
+}
+
+/// Rewards summed per period, added one row at a time
+pub struct RewardGroups {
+    period: Period,
+    /// Rows, reward and first and last epoch per period key
+    groups: BTreeMap<String, (usize, f64, Option<u64>, Option<u64>)>,
+}
+
+impl RewardGroups {
+    pub fn new(period: Period) -> Self {
+        Self {
+            period,
+            groups: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, row: &RewardRow) {
+        let group = self.groups.entry(self.period.key(row.time)).or_default();
+        group.0 += 1;
+        group.1 += row.amount;
+        if let Some(epoch) = row.epoch {
+            group.2 = Some(group.2.map_or(epoch, |first| first.min(epoch)));
+            group.3 = Some(group.3.map_or(epoch, |last| last.max(epoch)));
+        }
+    }
+
+    /// Newest period first
+    pub fn into_values(self) -> Vec<Value> {
+        self.groups
+            .into_iter()
+            .rev()
+            .map(|(key, (rows, amount, first_epoch, last_epoch))| {
+                json!({
+                    "key": key,
+                    "rows": rows,
+                    "reward_sol": round(amount, 9),
+                    "first_epoch": first_epoch,
+                    "last_epoch": last_epoch,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer_rows(export: impl Read) -> Result<Vec<TransferRow>, ExportError> {
+        let mut rows = Vec::new();
+        read_transfer_rows(export, |row| {
+            rows.push(row);
+            Ok(())
+        })?;
+        Ok(rows)
+    }
+
+    // Header and rows as `/account/reward/export` returns them
+    const REWARD_EXPORT: &str = "\
+Epoch,Effective Time,Reward Amount (SOL),Post Balance
+520,2023-11-15 12:00:00,0.05,10.05
+519,2023-11-13 02:00:00,0.048,10.0
+";
+
+    // Header and rows as `/account/transfer/export` returns them, amounts in raw units
+    const TRANSFER_EXPORT: &str = "\
+Signature,Block Time,Human Time,Action,From Address,To Address,Token Address,Decimals,Amount,Flow,Value
+sig1,1700000000,2023-11-14 22:13:20,TRANSFER,W1,B2,EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v,6,250000000,out,250
+sig2,1700086400,2023-11-15 22:13:20,TRANSFER,B2,W1,EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v,6,100000000,in,100
+";
+
+    const ERROR_BODY: &str = r#"{"success":false,"errors":{"code":1100,"message":"Unauthorized"}}"#;
+
+    #[test]
+    fn reads_reward_export() {
+        let rows = reward_rows(REWARD_EXPORT.as_bytes()).unwrap();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].epoch, Some(520));
+        assert_eq!(rows[0].time, 1700049600);
+        assert_eq!(rows[0].amount, 0.05);
+        assert_eq!(rows[0].post_balance, Some(10.05));
+    }
+
+    #[test]
+    fn reads_transfer_export() {
+        let rows = transfer_rows(TRANSFER_EXPORT.as_bytes()).unwrap();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].trans_id.as_deref(), Some("sig1"));
+        assert_eq!(rows[0].time, 1700000000);
+        assert_eq!(rows[0].action.as_deref(), Some("TRANSFER"));
+        assert_eq!(rows[0].from.as_deref(), Some("W1"));
+        assert_eq!(rows[0].to.as_deref(), Some("B2"));
+        assert_eq!(
+            rows[0].token.as_deref(),
+            Some("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v")
+        );
+        assert_eq!(rows[0].amount, 250.0);
+        assert_eq!(rows[0].flow.as_deref(), Some("out"));
+        assert_eq!(rows[0].value_usd, Some(250.0));
+    }
+
+    #[test]
+    fn reads_transfer_export_amounts_in_one_unit() {
+        // UI amounts with a decimals column: whole amounts must not be scaled like raw ones
+        let export = TRANSFER_EXPORT
+            .replace(",Amount,", ",Amount (UI),")
+            .replace(",250000000,", ",250,")
+            .replace(",100000000,", ",0.5,");
+        let rows = transfer_rows(export.as_bytes()).unwrap();
+
+        assert_eq!(rows[0].amount, 250.0);
+        assert_eq!(rows[1].amount, 0.5);
+    }
+
+    #[test]
+    fn rejects_error_body() {
+        assert!(matches!(
+            reward_rows(ERROR_BODY.as_bytes()),
+            Err(ExportError::MissingColumn {
+                missing: "amount",
+                ..
+            })
+        ));
+        assert!(matches!(
+            transfer_rows(ERROR_BODY.as_bytes()),
+            Err(ExportError::MissingColumn {
+                missing: "time",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_renamed_amount_column() {
+        let export = TRANSFER_EXPORT.replace(",Amount,", ",Quantity,");
+
+        assert!(matches!(
+            transfer_rows(export.as_bytes()),
+            Err(ExportError::MissingColumn {
+                missing: "amount",
+                ..
+            })
+        ));
+    }
+}
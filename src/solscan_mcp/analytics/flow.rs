@@ -0,0 +1,400 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+use serde_json::{json, Value};
+
+use super::{f64_field, i64_field, round, str_field, usd_value};
+
+// Transaction ids kept per edge as evidence
+const MAX_EDGE_TRANSACTIONS: usize = 3;
+
+/// Which side of an account's transfers a trace follows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Out,
+    In,
+    Both,
+}
+
+impl std::str::FromStr for Direction {
+    type Err = String;
+
+    fn from_str(direction: &str) -> Result<Self, Self::Err> {
+        match direction.to_ascii_lowercase().as_str() {
+            "out" => Ok(Self::Out),
+            "in" => Ok(Self::In),
+            "both" => Ok(Self::Both),
+            _ => Err(format!(
+                "unknown direction {:?}, expected out, in or both",
+                direction
+            )),
+        }
+    }
+}
+
+impl Direction {
+    /// The `flow` value of `account_transfer` matching this direction
+    pub fn flow(self) -> Option<&'static str> {
+        match self {
+            Self::Out => Some("out"),
+            Self::In => Some("in"),
+            Self::Both => None,
+        }
+    }
+}
+
+/// Transfers between two addresses in one token, aggregated
+#[derive(Debug, Default)]
+pub struct Edge {
+    pub transfers: usize,
+    /// Sum of the amounts in token units, `None` when no amount could be scaled
+    pub amount: Option<f64>,
+    pub value_usd: Option<f64>,
+    pub first_time: Option<i64>,
+    pub last_time: Option<i64>,
+    pub trans_ids: Vec<String>,
+}
+
+/// Directed transfer graph, one edge per sender, receiver and token
+#[derive(Debug, Default)]
+pub struct FlowGraph {
+    edges: BTreeMap<(String, String, String), Edge>,
+    /// Transfers already counted; the same transfer shows up in the history of both sides
+    seen: HashSet<String>,
+}
+
+impl FlowGraph {
+    /// Add an enriched `account_transfer` item
+    pub fn add_transfer(&mut self, transfer: &Value) {
+        let (Some(from), Some(to)) = (
+            str_field(transfer, "from_address"),
+            str_field(transfer, "to_address"),
+        ) else {
+            return;
+        };
+        let token = str_field(transfer, "token_address").unwrap_or_default();
+        if from == to {
+            return;
+        }
+
+        if let Some(trans_id) = str_field(transfer, "trans_id") {
+            let key = format!(
+                "{}:{}:{}:{}:{}:{}",
+                trans_id,
+                from,
+                to,
+                token,
+                transfer.get("amount").unwrap_or(&Value::Null),
+                transfer.get("ins_index").unwrap_or(&Value::Null)
+            );
+            if !self.seen.insert(key) {
+                return;
+            }
+        }
+
+        let edge = self
+            .edges
+            .entry((from.to_string(), to.to_string(), token.to_string()))
+            .or_default();
+
+        edge.transfers += 1;
+        if let Some(amount) = f64_field(transfer, "ui_amount") {
+            *edge.amount.get_or_insert(0.0) += amount;
+        }
+        if let Some(value) = usd_value(transfer) {
+            *edge.value_usd.get_or_insert(0.0) += value;
+        }
+        if let Some(time) = i64_field(transfer, "block_time") {
+            edge.first_time = Some(edge.first_time.map_or(time, |first| first.min(time)));
+            edge.last_time = Some(edge.last_time.map_or(time, |last| last.max(time)));
+        }
+        if let Some(trans_id) = str_field(transfer, "trans_id") {
+            if edge.trans_ids.len() < MAX_EDGE_TRANSACTIONS
+                && !edge.trans_ids.iter().any(|known| known == trans_id)
+            {
+                edge.trans_ids.push(trans_id.to_string());
+            }
+        }
+    }
+
+    /// Edges as `(from, to, token)` with their aggregates, in a stable order
+    pub fn edges(&self) -> impl Iterator<Item = (&str, &str, &str, &Edge)> {
+        self.edges
+            .iter()
+            .map(|((from, to, token), edge)| (from.as_str(), to.as_str(), token.as_str(), edge))
+    }
+
+    pub fn len(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn to_value(&self) -> Vec<Value> {
+        self.edges()
+            .map(|(from, to, token, edge)| {
+                json!({
+                    "from": from,
+                    "to": to,
+                    "token_address": token,
+                    "transfers": edge.transfers,
+                    "amount": edge.amount,
+                    "value_usd": edge.value_usd.map(|value| round(value, 2)),
+                    "first_time": edge.first_time,
+                    "last_time": edge.last_time,
+                    "trans_ids": edge.trans_ids,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Bounds on how far a trace goes and how many Solscan calls it makes
+#[derive(Debug, Clone, Copy)]
+pub struct TraceLimits {
+    pub hops: usize,
+    pub max_nodes: usize,
+    pub max_requests: usize,
+}
+
+#[derive(Debug)]
+struct Node {
+    address: String,
+    depth: usize,
+    explored: bool,
+}
+
+/// Breadth-first walk over transfers. The caller fetches the transfers of each address
+/// returned by `next_address` and hands them back with `add_transfers`.
+pub struct Trace {
+    direction: Direction,
+    limits: TraceLimits,
+    min_amount: Option<f64>,
+    nodes: Vec<Node>,
+    index: HashMap<String, usize>,
+    queue: VecDeque<usize>,
+    graph: FlowGraph,
+    requests: usize,
+    limits_reached: BTreeSet<&'static str>,
+    errors: Vec<Value>,
+}
+
+impl Trace {
+    pub fn new(direction: Direction, limits: TraceLimits, min_amount: Option<f64>) -> Self {
+        Self {
+            direction,
+            limits,
+            min_amount,
+            nodes: Vec::new(),
+            index: HashMap::new(),
+            queue: VecDeque::new(),
+            graph: FlowGraph::default(),
+            requests: 0,
+            limits_reached: BTreeSet::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Start from an address
+    pub fn add_seed(&mut self, address: &str) {
+        if let Some(node) = self.add_node(address, 0) {
+            self.queue.push_back(node);
+        }
+    }
+
+    /// Start from the transfers of a transaction: every participant is at depth 0 and the
+    /// side the trace follows (receivers for outflows, senders for inflows) is explored
+    pub fn add_seed_transfers(&mut self, transfers: &[Value]) {
+        for transfer in transfers {
+            if !self.passes_filter(transfer) {
+                continue;
+            }
+
+            let (Some(from), Some(to)) = (
+                str_field(transfer, "from_address"),
+                str_field(transfer, "to_address"),
+            ) else {
+                continue;
+            };
+
+            let from = self.add_node(from, 0);
+            let to = self.add_node(to, 0);
+            if from.is_none() || to.is_none() {
+                continue;
+            }
+
+            self.graph.add_transfer(transfer);
+            let followed = match self.direction {
+                Direction::Out => vec![to],
+                Direction::In => vec![from],
+                Direction::Both => vec![from, to],
+            };
+
+            for node in followed.into_iter().flatten() {
+                if !self.queue.contains(&node) {
+                    self.queue.push_back(node);
+                }
+            }
+        }
+    }
+
+    /// The next address whose transfers should be fetched, `None` once the walk is over
+    pub fn next_address(&mut self) -> Option<(String, usize)> {
+        while let Some(node) = self.queue.pop_front() {
+            let node = &mut self.nodes[node];
+            if node.explored || node.depth >= self.limits.hops {
+                continue;
+            }
+
+            if self.requests >= self.limits.max_requests {
+                self.limits_reached.insert("max_requests");
+                return None;
+            }
+
+            self.requests += 1;
+            node.explored = true;
+            return Some((node.address.clone(), node.depth));
+        }
+
+        None
+    }
+
+    /// Record the enriched transfers of an explored address and queue its counterparties
+    pub fn add_transfers(&mut self, address: &str, depth: usize, transfers: &[Value]) {
+        for transfer in transfers {
+            let flow = str_field(transfer, "flow");
+            if self.direction.flow().is_some() && flow != self.direction.flow() {
+                continue;
+            }
+            if !self.passes_filter(transfer) {
+                continue;
+            }
+
+            let counterparty = match flow {
+                Some("out") => str_field(transfer, "to_address"),
+                _ => str_field(transfer, "from_address"),
+            };
+            let Some(counterparty) = counterparty.filter(|other| *other != address) else {
+                continue;
+            };
+
+            // Keep the graph closed: edges only connect nodes that are part of the result
+            let Some(node) = self.add_node(counterparty, depth + 1) else {
+                continue;
+            };
+
+            self.graph.add_transfer(transfer);
+            if !self.nodes[node].explored {
+                self.queue.push_back(node);
+            }
+        }
+    }
+
+    /// Note an address whose transfers could not be fetched; the walk goes on without it
+    pub fn add_error(&mut self, address: &str, error: &str) {
+        self.errors
+            .push(json!({ "address": address, "error": error }));
+    }
+
+    pub fn into_value(self, start: Value) -> Value {
+        // Addresses still waiting when a limit stopped the walk
+        let unexplored: BTreeSet<usize> = self
+            .queue
+            .iter()
+            .copied()
+            .filter(|node| {
+                !self.nodes[*node].explored && self.nodes[*node].depth < self.limits.hops
+            })
+            .collect();
+
+        let nodes: Vec<Value> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                json!({
+                    "address": node.address,
+                    "depth": node.depth,
+                    "explored": node.explored,
+                })
+            })
+            .collect();
+
+        json!({
+            "success": true,
+            "data": {
+                "start": start,
+                "direction": match self.direction {
+                    Direction::Out => "out",
+                    Direction::In => "in",
+                    Direction::Both => "both",
+                },
+                "stats": {
+                    "api_calls": self.requests,
+                    "nodes": self.nodes.len(),
+                    "edges": self.graph.len(),
+                    "unexplored_nodes": unexplored.len(),
+                },
+                "limits_reached": self.limits_reached,
+                "errors": self.errors,
+                "nodes": nodes,
+                "edges": self.graph.to_value(),
+            },
+        })
+    }
+
+    fn passes_filter(&self, transfer: &Value) -> bool {
+        match self.min_amount {
+            Some(min_amount) => {
+                f64_field(transfer, "ui_amount").is_some_and(|amount| amount >= min_amount)
+            }
+            None => true,
+        }
+    }
+
+    /// Index of the node for `address`, adding it when there is room
+    fn add_node(&mut self, address: &str, depth: usize) -> Option<usize> {
+        if let Some(index) = self.index.get(address) {
+            return Some(*index);
+        }
+
+        if self.nodes.len() >= self.limits.max_nodes {
+            self.limits_reached.insert("max_nodes");
+            return None;
+        }
+
+        self.nodes.push(Node {
+            address: address.to_string(),
+            depth,
+            explored: false,
+        });
+        self.index.insert(address.to_string(), self.nodes.len() - 1);
+        Some(self.nodes.len() - 1)
+    }
+}
+
+/// `/transaction/actions` transfers in the shape of `account_transfer` items, so they can be
+/// enriched and added to a graph
+pub fn transaction_transfers(actions: &Value) -> Vec<Value> {
+    let data = &actions["data"];
+    let block_time = data.get("block_time").cloned().unwrap_or(Value::Null);
+    let trans_id = data.get("tx_hash").cloned().unwrap_or(Value::Null);
+
+    data.get("transfers")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|transfer| {
+            Some(json!({
+                "from_address": transfer
+                    .get("source_owner")
+                    .or_else(|| transfer.get("source"))?,
+                "to_address": transfer
+                    .get("destination_owner")
+                    .or_else(|| transfer.get("destination"))?,
+                "token_address": transfer.get("token_address")?,
+                "amount": transfer.get("amount")?,
+                "token_decimals": transfer.get("decimals"),
+                "block_time": block_time,
+                "trans_id": trans_id,
+            }))
+        })
+        .collect()
+}
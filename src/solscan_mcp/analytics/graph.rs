@@ -0,0 +1,220 @@
+use std::collections::{BTreeSet, HashMap};
+
+use super::flow::{Edge, FlowGraph};
+use super::round;
+
+/// Text formats a transfer graph can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+    GraphMl,
+}
+
+impl std::str::FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_ascii_lowercase().as_str() {
+            "dot" | "graphviz" => Ok(Self::Dot),
+            "mermaid" => Ok(Self::Mermaid),
+            "graphml" => Ok(Self::GraphMl),
+            _ => Err(format!(
+                "unknown graph format {:?}, expected dot, mermaid or graphml",
+                format
+            )),
+        }
+    }
+}
+
+/// Names used to label the nodes and edges of an exported graph
+#[derive(Debug, Default)]
+pub struct GraphLabels {
+    /// Address labels, from the label store and Solscan account metadata
+    pub addresses: HashMap<String, String>,
+    /// Token symbols by token address
+    pub symbols: HashMap<String, String>,
+}
+
+impl GraphLabels {
+    fn node(&self, address: &str) -> String {
+        match self.addresses.get(address) {
+            Some(name) => format!("{}\n{}", name, short_address(address)),
+            None => short_address(address),
+        }
+    }
+
+    /// Token and aggregated amount, e.g. `12.5 USDC (3 transfers)`
+    fn edge(&self, token: &str, edge: &Edge) -> String {
+        let token = self
+            .symbols
+            .get(token)
+            .cloned()
+            .unwrap_or_else(|| short_address(token));
+
+        let amount = match edge.amount {
+            Some(amount) => format!("{} {}", format_amount(amount), token),
+            None => token,
+        };
+
+        match edge.transfers {
+            1 => amount,
+            transfers => format!("{} ({} transfers)", amount, transfers),
+        }
+    }
+}
+
+pub fn render(graph: &FlowGraph, labels: &GraphLabels, format: GraphFormat) -> String {
+    match format {
+        GraphFormat::Dot => to_dot(graph, labels),
+        GraphFormat::Mermaid => to_mermaid(graph, labels),
+        GraphFormat::GraphMl => to_graphml(graph, labels),
+    }
+}
+
+/// Addresses of the graph in a stable order
+fn nodes(graph: &FlowGraph) -> Vec<&str> {
+    let nodes: BTreeSet<&str> = graph
+        .edges()
+        .flat_map(|(from, to, _, _)| [from, to])
+        .collect();
+    nodes.into_iter().collect()
+}
+
+fn to_dot(graph: &FlowGraph, labels: &GraphLabels) -> String {
+    let mut output = String::from("digraph transfers {\n  rankdir=LR;\n  node [shape=box];\n");
+
+    for address in nodes(graph) {
+        output.push_str(&format!(
+            "  \"{}\" [label=\"{}\"];\n",
+            address,
+            escape_dot(&labels.node(address))
+        ));
+    }
+
+    for (from, to, token, edge) in graph.edges() {
+        output.push_str(&format!(
+            "  \"{}\" -> \"{}\" [label=\"{}\"];\n",
+            from,
+            to,
+            escape_dot(&labels.edge(token, edge))
+        ));
+    }
+
+    output.push_str("}\n");
+    output
+}
+
+fn to_mermaid(graph: &FlowGraph, labels: &GraphLabels) -> String {
+    let mut output = String::from("flowchart LR\n");
+
+    // Mermaid ids cannot be 44-character addresses with arbitrary labels, so number them
+    let ids: HashMap<&str, String> = nodes(graph)
+        .into_iter()
+        .enumerate()
+        .map(|(index, address)| (address, format!("n{}", index)))
+        .collect();
+
+    for address in nodes(graph) {
+        output.push_str(&format!(
+            "  {}[\"{}\"]\n",
+            ids[address],
+            escape_mermaid(&labels.node(address))
+        ));
+    }
+
+    for (from, to, token, edge) in graph.edges() {
+        output.push_str(&format!(
+            "  {} -->|\"{}\"| {}\n",
+            ids[from],
+            escape_mermaid(&labels.edge(token, edge)),
+            ids[to]
+        ));
+    }
+
+    output
+}
+
+fn to_graphml(graph: &FlowGraph, labels: &GraphLabels) -> String {
+    let mut output = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+        "  <key id=\"label\" for=\"all\" attr.name=\"label\" attr.type=\"string\"/>\n",
+        "  <key id=\"token\" for=\"edge\" attr.name=\"token_address\" attr.type=\"string\"/>\n",
+        "  <key id=\"amount\" for=\"edge\" attr.name=\"amount\" attr.type=\"double\"/>\n",
+        "  <key id=\"value_usd\" for=\"edge\" attr.name=\"value_usd\" attr.type=\"double\"/>\n",
+        "  <key id=\"transfers\" for=\"edge\" attr.name=\"transfers\" attr.type=\"int\"/>\n",
+        "  <graph id=\"transfers\" edgedefault=\"directed\">\n",
+    ));
+
+    for address in nodes(graph) {
+        output.push_str(&format!(
+            "    <node id=\"{}\"><data key=\"label\">{}</data></node>\n",
+            address,
+            escape_xml(&labels.node(address).replace('\n', " "))
+        ));
+    }
+
+    for (index, (from, to, token, edge)) in graph.edges().enumerate() {
+        output.push_str(&format!(
+            "    <edge id=\"e{}\" source=\"{}\" target=\"{}\">\n",
+            index, from, to
+        ));
+        output.push_str(&format!(
+            "      <data key=\"label\">{}</data>\n      <data key=\"token\">{}</data>\n      <data key=\"transfers\">{}</data>\n",
+            escape_xml(&labels.edge(token, edge)),
+            escape_xml(token),
+            edge.transfers
+        ));
+        if let Some(amount) = edge.amount {
+            output.push_str(&format!("      <data key=\"amount\">{}</data>\n", amount));
+        }
+        if let Some(value_usd) = edge.value_usd {
+            output.push_str(&format!(
+                "      <data key=\"value_usd\">{}</data>\n",
+                round(value_usd, 2)
+            ));
+        }
+        output.push_str("    </edge>\n");
+    }
+
+    output.push_str("  </graph>\n</graphml>\n");
+    output
+}
+
+/// `AbCd…WxYz` form of an address for labels
+fn short_address(address: &str) -> String {
+    match (
+        address.get(..4),
+        address.get(address.len().saturating_sub(4)..),
+    ) {
+        (Some(head), Some(tail)) if address.len() > 10 => format!("{}…{}", head, tail),
+        _ => address.to_string(),
+    }
+}
+
+fn format_amount(amount: f64) -> String {
+    let rounded = round(amount, 4);
+    if rounded.fract() == 0.0 {
+        format!("{:.0}", rounded)
+    } else {
+        rounded.to_string()
+    }
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn escape_mermaid(text: &str) -> String {
+    text.replace('"', "#quot;").replace('\n', "<br/>")
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,265 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde_json::{json, Value};
+
+use super::{f64_field, round, str_field};
+use crate::solscan_mcp::enrich::as_u32;
+use crate::solscan_mcp::labels::Label;
+
+/// Holders listed in the response with their share of supply
+const TOP_HOLDERS_LISTED: usize = 10;
+
+/// Balance of one owner, summed over its token accounts, in token units
+#[derive(Debug, Clone)]
+pub struct Holder {
+    pub owner: String,
+    pub accounts: Vec<String>,
+    pub amount: f64,
+}
+
+/// Holders kept and holders left out with the reason
+pub type Partition = (Vec<Holder>, Vec<(Holder, String)>);
+
+/// Holder count of one size range, from `/token/holders` filtered with `from_amount` and
+/// `to_amount`
+#[derive(Debug, Clone)]
+pub struct Bucket {
+    pub from: f64,
+    pub to: Option<f64>,
+    pub holders: u64,
+}
+
+impl Bucket {
+    fn contains(&self, amount: f64) -> bool {
+        amount >= self.from && self.to.is_none_or(|to| amount < to)
+    }
+}
+
+/// Merge the token accounts of `/token/holders` items by owner, largest balance first.
+/// Raw amounts are scaled by the item's `decimals`, or by `decimals` when it has none.
+pub fn holders(items: &[Value], decimals: u32) -> Vec<Holder> {
+    let mut owners: BTreeMap<&str, Holder> = BTreeMap::new();
+
+    for item in items {
+        let Some(owner) = str_field(item, "owner").or_else(|| str_field(item, "address")) else {
+            continue;
+        };
+        let Some(amount) = f64_field(item, "amount") else {
+            continue;
+        };
+        let decimals = item.get("decimals").and_then(as_u32).unwrap_or(decimals);
+
+        let holder = owners.entry(owner).or_insert_with(|| Holder {
+            owner: owner.to_string(),
+            accounts: Vec::new(),
+            amount: 0.0,
+        });
+        holder.amount += amount / 10f64.powi(decimals as i32);
+        if let Some(account) = str_field(item, "address") {
+            holder.accounts.push(account.to_string());
+        }
+    }
+
+    let mut holders: Vec<Holder> = owners.into_values().collect();
+    holders.sort_by(|a, b| b.amount.total_cmp(&a.amount));
+    holders
+}
+
+/// Pool addresses of `/token/markets` items and the token accounts holding their reserves
+pub fn pool_addresses(markets: &[Value]) -> BTreeSet<String> {
+    markets
+        .iter()
+        .flat_map(|market| {
+            [
+                "pool_id",
+                "pool_address",
+                "token_account_1",
+                "token_account_2",
+            ]
+            .into_iter()
+            .filter_map(|key| str_field(market, key))
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// Split holders into the ones kept and the ones left out with the reason: owners or token
+/// accounts that are market pools or carry one of the `labeled` labels
+pub fn partition(
+    holders: Vec<Holder>,
+    pools: &BTreeSet<String>,
+    labeled: &HashMap<String, Label>,
+) -> Partition {
+    let (mut kept, mut excluded) = (Vec::new(), Vec::new());
+
+    for holder in holders {
+        let addresses = || std::iter::once(&holder.owner).chain(&holder.accounts);
+        let reason = if addresses().any(|address| pools.contains(address)) {
+            Some("Liquidity pool of a token market".to_string())
+        } else {
+            addresses()
+                .find_map(|address| labeled.get(address))
+                .map(|label| match &label.category {
+                    Some(category) => format!("Labeled {} ({})", label.name, category),
+                    None => format!("Labeled {}", label.name),
+                })
+        };
+        match reason {
+            Some(reason) => excluded.push((holder, reason)),
+            None => kept.push(holder),
+        }
+    }
+
+    (kept, excluded)
+}
+
+/// Size ranges between consecutive bounds, plus one below the first and one above the last
+pub fn bucket_ranges(bounds: &[f64]) -> Vec<(f64, Option<f64>)> {
+    let mut bounds: Vec<f64> = bounds
+        .iter()
+        .copied()
+        .filter(|bound| bound.is_finite() && *bound > 0.0)
+        .collect();
+    bounds.sort_by(f64::total_cmp);
+    bounds.dedup();
+
+    let mut ranges = Vec::with_capacity(bounds.len() + 1);
+    let mut from = 0.0;
+    for bound in bounds {
+        ranges.push((from, Some(bound)));
+        from = bound;
+    }
+    ranges.push((from, None));
+    ranges
+}
+
+/// Share of `supply` held by the largest `count` holders
+fn top_share(holders: &[Holder], count: usize, supply: f64) -> Option<f64> {
+    (supply > 0.0).then(|| {
+        let held: f64 = holders.iter().take(count).map(|holder| holder.amount).sum();
+        round(held / supply * 100.0, 4)
+    })
+}
+
+/// Gini coefficient of the balances: 0 when everyone holds the same, close to 1 when one
+/// holder has everything. Over a sample of the largest holders it understates inequality.
+fn gini(holders: &[Holder]) -> Option<f64> {
+    let mut amounts: Vec<f64> = holders.iter().map(|holder| holder.amount).collect();
+    amounts.sort_by(f64::total_cmp);
+
+    let count = amounts.len() as f64;
+    let total: f64 = amounts.iter().sum();
+    if amounts.is_empty() || total <= 0.0 {
+        return None;
+    }
+
+    let weighted: f64 = amounts
+        .iter()
+        .enumerate()
+        .map(|(index, amount)| (2.0 * (index as f64 + 1.0) - count - 1.0) * amount)
+        .sum();
+    Some(round(weighted / (count * total), 4))
+}
+
+/// Fewest holders that together hold more than half of `supply`; `None` when the holders
+/// read do not get there
+fn nakamoto(holders: &[Holder], supply: f64) -> Option<usize> {
+    let mut held = 0.0;
+    for (index, holder) in holders.iter().enumerate() {
+        held += holder.amount;
+        if held > supply / 2.0 {
+            return Some(index + 1);
+        }
+    }
+    None
+}
+
+/// Concentration figures of a token's holders.
+///
+/// `supply` is the total supply in token units, `None` when unknown; shares are then
+/// measured against the balances read. Excluded holders (pools, programs) are left out of
+/// every figure and their balances are taken off the supply. Bucket counts come from
+/// Solscan over all holders; excluded holders falling in a bucket are subtracted.
+pub fn stats(
+    holders: &[Holder],
+    excluded: &[(Holder, String)],
+    supply: Option<f64>,
+    buckets: &[Bucket],
+) -> Value {
+    let excluded_amount: f64 = excluded.iter().map(|(holder, _)| holder.amount).sum();
+    let read: f64 = holders.iter().map(|holder| holder.amount).sum();
+    let (basis, circulating) = match supply {
+        Some(supply) if supply > 0.0 => ("supply", (supply - excluded_amount).max(0.0)),
+        _ => ("holders_kept", read),
+    };
+
+    let share = |amount: f64| (circulating > 0.0).then(|| round(amount / circulating * 100.0, 4));
+
+    let top_holders: Vec<Value> = holders
+        .iter()
+        .take(TOP_HOLDERS_LISTED)
+        .map(|holder| {
+            json!({
+                "owner": holder.owner,
+                "token_accounts": holder.accounts,
+                "amount": holder.amount,
+                "share": share(holder.amount),
+            })
+        })
+        .collect();
+
+    let excluded_accounts: Vec<Value> = excluded
+        .iter()
+        .map(|(holder, reason)| {
+            json!({
+                "owner": holder.owner,
+                "token_accounts": holder.accounts,
+                "amount": holder.amount,
+                "supply_share": supply
+                    .filter(|supply| *supply > 0.0)
+                    .map(|supply| round(holder.amount / supply * 100.0, 4)),
+                "reason": reason,
+            })
+        })
+        .collect();
+
+    let mut excluded_per_bucket: HashMap<usize, u64> = HashMap::new();
+    for (holder, _) in excluded {
+        if let Some(index) = buckets
+            .iter()
+            .position(|bucket| bucket.contains(holder.amount))
+        {
+            *excluded_per_bucket.entry(index).or_default() += 1;
+        }
+    }
+    let buckets: Vec<Value> = buckets
+        .iter()
+        .enumerate()
+        .map(|(index, bucket)| {
+            let excluded = excluded_per_bucket.get(&index).copied().unwrap_or_default();
+            json!({
+                "from_amount": bucket.from,
+                "to_amount": bucket.to,
+                "holders": bucket.holders.saturating_sub(excluded),
+                "excluded": excluded,
+            })
+        })
+        .collect();
+
+    json!({
+        "share_basis": basis,
+        "circulating": circulating,
+        "excluded_amount": excluded_amount,
+        "top_10_share": top_share(holders, 10, circulating),
+        "top_100_share": top_share(holders, 100, circulating),
+        // Only the largest holders are read, so these describe them and not every holder;
+        // the figures are over the holders kept once pools and labeled accounts are excluded
+        "holders_read": holders.len() + excluded.len(),
+        "holders_kept": holders.len(),
+        "gini_holders_read": gini(holders),
+        "nakamoto_holders_read": nakamoto(holders, circulating),
+        "top_holders": top_holders,
+        "excluded": excluded_accounts,
+        "buckets": buckets,
+    })
+}
@@ -0,0 +1,309 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use serde_json::{json, Value};
+
+use super::{f64_field, round, str_field};
+
+/// Names of the DEX programs pools are grouped by
+const DEX_PROGRAMS: [(&str, &str); 12] = [
+    (
+        "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8",
+        "Raydium AMM",
+    ),
+    (
+        "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK",
+        "Raydium CLMM",
+    ),
+    (
+        "CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C",
+        "Raydium CPMM",
+    ),
+    (
+        "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc",
+        "Orca Whirlpool",
+    ),
+    ("9W959DqEETiGZocYWCQPaJ6sBmUzgfxXfqGeTEdp3aQP", "Orca"),
+    (
+        "LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo",
+        "Meteora DLMM",
+    ),
+    (
+        "Eo7WjKq67rjJQSZxS6z3YkapzY3eMj6Xy8X5EQVn5UaB",
+        "Meteora Pools",
+    ),
+    (
+        "pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA",
+        "Pump.fun AMM",
+    ),
+    ("2wT8Yq49kHgDzXuPxZSaeLaH1qbmGXtEyPy64bL7aD3c", "Lifinity"),
+    ("PhoeNiXZ8ByJGLkxNfZRnkUfjvmuYqLR89jjFHGqdXY", "Phoenix"),
+    ("opnb2LAfJYbRMAHHvqjCwQxanZn7ReEHp1k81EohpZb", "OpenBook"),
+    ("SSwpkEEcbUqx4vtoEByFjSkhKdCT862DNVb52nZg1UZ", "Saber"),
+];
+
+/// Order of the returned pools
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RankBy {
+    Tvl,
+    Volume,
+    Turnover,
+}
+
+impl FromStr for RankBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "tvl" => Ok(Self::Tvl),
+            "volume" | "volume_24h" => Ok(Self::Volume),
+            "turnover" | "volume_tvl" => Ok(Self::Turnover),
+            other => Err(format!(
+                "Unknown sort_by '{}', expected tvl, volume_24h or volume_tvl",
+                other
+            )),
+        }
+    }
+}
+
+/// One market of the token, merged from `/token/markets`, `/market/info` and `/market/volume`
+#[derive(Debug, Clone)]
+pub struct Pool {
+    pub address: String,
+    pub program: Option<String>,
+    pub other_token: Option<String>,
+    pub tvl: Option<f64>,
+    pub volume_24h: Option<f64>,
+    pub trades_24h: Option<f64>,
+    /// Reserve of the queried token, in token units
+    pub reserve: Option<f64>,
+    /// Reserve of the other token, in token units
+    pub other_reserve: Option<f64>,
+}
+
+fn first_str<'a>(item: &'a Value, keys: &[&str]) -> Option<&'a str> {
+    keys.iter().find_map(|key| str_field(item, key))
+}
+
+fn first_f64(item: &Value, keys: &[&str]) -> Option<f64> {
+    keys.iter().find_map(|key| f64_field(item, key))
+}
+
+impl Pool {
+    /// Merge a `/token/markets` item with the `data` of its `/market/info` and
+    /// `/market/volume` responses, when they were fetched
+    pub fn new(
+        token: &str,
+        market: &Value,
+        info: Option<&Value>,
+        volume: Option<&Value>,
+    ) -> Option<Self> {
+        let address = first_str(market, &["pool_id", "pool_address"])?.to_string();
+        let info = info.filter(|info| info.is_object());
+        let volume = volume.filter(|volume| volume.is_object());
+
+        let tokens = [
+            first_str(market, &["token_1", "token1"]),
+            first_str(market, &["token_2", "token2"]),
+        ];
+        let other_token = tokens.into_iter().flatten().find(|other| *other != token);
+
+        // Reserves from the market info, matched to the token by address
+        let (mut reserve, mut other_reserve) = (None, None);
+        if let Some(info) = info {
+            let sides = [
+                (["token1", "token_1"], ["token1_amount", "token_1_amount"]),
+                (["token2", "token_2"], ["token2_amount", "token_2_amount"]),
+            ];
+            for (token_keys, amount_keys) in sides {
+                let amount = first_f64(info, &amount_keys);
+                match first_str(info, &token_keys) {
+                    Some(side) if side == token => reserve = amount,
+                    Some(_) => other_reserve = amount,
+                    None => {}
+                }
+            }
+        }
+
+        Some(Self {
+            address,
+            program: first_str(market, &["program_id"])
+                .or_else(|| info.and_then(|info| first_str(info, &["program_id"])))
+                .map(str::to_string),
+            other_token: other_token.map(str::to_string),
+            tvl: first_f64(market, &["total_tvl", "tvl"]),
+            volume_24h: volume
+                .and_then(|volume| first_f64(volume, &["total_volume_24h", "volume_24h"]))
+                .or_else(|| first_f64(market, &["total_volume_24h", "volume_24h"])),
+            trades_24h: volume
+                .and_then(|volume| first_f64(volume, &["total_trades_24h", "trades_24h"]))
+                .or_else(|| first_f64(market, &["total_trades_24h", "trades_24h"])),
+            reserve,
+            other_reserve,
+        })
+    }
+
+    fn turnover(&self) -> Option<f64> {
+        match (self.volume_24h, self.tvl) {
+            (Some(volume), Some(tvl)) if tvl > 0.0 => Some(volume / tvl),
+            _ => None,
+        }
+    }
+
+    /// USD depth of the token side and of the other side. A constant-product pool holds
+    /// equal value on both sides, so half of the TVL stands in for a side whose reserve or
+    /// price is unknown.
+    fn depth(&self, price: Option<f64>) -> (Option<f64>, Option<f64>) {
+        let half = self.tvl.map(|tvl| tvl / 2.0);
+        let token_side = match (self.reserve, price) {
+            (Some(reserve), Some(price)) => Some(reserve * price),
+            _ => half,
+        };
+        let other_side = match (self.tvl, token_side) {
+            (Some(tvl), Some(side)) if tvl > side => Some(tvl - side),
+            _ => token_side,
+        };
+        (token_side, other_side)
+    }
+}
+
+/// Price impact in percent of a `trade` USD swap into a constant-product pool side holding
+/// `depth` USD: the trade moves the price by `trade / (depth + trade)`
+fn impact(trade: f64, depth: Option<f64>) -> Option<f64> {
+    depth
+        .filter(|depth| *depth > 0.0)
+        .map(|depth| round(trade / (depth + trade) * 100.0, 4))
+}
+
+pub fn program_name(program: &str) -> Option<&'static str> {
+    DEX_PROGRAMS
+        .iter()
+        .find(|(id, _)| *id == program)
+        .map(|(_, name)| *name)
+}
+
+/// Position of every pool when sorted by `key`, largest first; pools without a value rank last
+fn ranks(pools: &[Pool], key: impl Fn(&Pool) -> Option<f64>) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..pools.len()).collect();
+    order.sort_by(|a, b| {
+        let (a, b) = (key(&pools[*a]), key(&pools[*b]));
+        b.unwrap_or(f64::NEG_INFINITY)
+            .total_cmp(&a.unwrap_or(f64::NEG_INFINITY))
+    });
+
+    let mut ranks = vec![0; pools.len()];
+    for (rank, index) in order.into_iter().enumerate() {
+        ranks[index] = rank + 1;
+    }
+    ranks
+}
+
+#[derive(Default)]
+struct ProgramTotals<'a> {
+    pools: usize,
+    tvl: f64,
+    volume_24h: f64,
+    sell_depth: f64,
+    buy_depth: f64,
+    deepest: Option<&'a Pool>,
+}
+
+/// Compare a token's pools: rank them by TVL, 24h volume and volume/TVL, estimate the price
+/// impact of a `trade_usd` buy and sell in each pool, across all pools and per program.
+///
+/// `price` is the token's USD price, used to value its reserves. Impacts across several
+/// pools assume the trade is split in proportion to their depth.
+pub fn compare(pools: &[Pool], price: Option<f64>, trade_usd: f64, rank_by: RankBy) -> Value {
+    let tvl_ranks = ranks(pools, |pool| pool.tvl);
+    let volume_ranks = ranks(pools, |pool| pool.volume_24h);
+    let turnover_ranks = ranks(pools, Pool::turnover);
+
+    let mut programs: BTreeMap<&str, ProgramTotals> = BTreeMap::new();
+    let (mut sell_depth, mut buy_depth) = (0.0, 0.0);
+    let mut entries: Vec<(usize, Value)> = Vec::with_capacity(pools.len());
+
+    for (index, pool) in pools.iter().enumerate() {
+        let (token_side, other_side) = pool.depth(price);
+        sell_depth += token_side.unwrap_or_default();
+        buy_depth += other_side.unwrap_or_default();
+
+        let program = pool.program.as_deref().unwrap_or("unknown");
+        let totals = programs.entry(program).or_default();
+        totals.pools += 1;
+        totals.tvl += pool.tvl.unwrap_or_default();
+        totals.volume_24h += pool.volume_24h.unwrap_or_default();
+        totals.sell_depth += token_side.unwrap_or_default();
+        totals.buy_depth += other_side.unwrap_or_default();
+        if totals
+            .deepest
+            .is_none_or(|deepest| deepest.tvl.unwrap_or_default() < pool.tvl.unwrap_or_default())
+        {
+            totals.deepest = Some(pool);
+        }
+
+        let rank = match rank_by {
+            RankBy::Tvl => tvl_ranks[index],
+            RankBy::Volume => volume_ranks[index],
+            RankBy::Turnover => turnover_ranks[index],
+        };
+        entries.push((
+            rank,
+            json!({
+                "pool_address": pool.address,
+                "program_id": pool.program,
+                "program_name": pool.program.as_deref().and_then(program_name),
+                "paired_token": pool.other_token,
+                "tvl_usd": pool.tvl.map(|tvl| round(tvl, 2)),
+                "volume_24h_usd": pool.volume_24h.map(|volume| round(volume, 2)),
+                "trades_24h": pool.trades_24h,
+                "volume_tvl": pool.turnover().map(|turnover| round(turnover, 4)),
+                "reserve": pool.reserve,
+                "paired_reserve": pool.other_reserve,
+                "rank_tvl": tvl_ranks[index],
+                "rank_volume_24h": volume_ranks[index],
+                "rank_volume_tvl": turnover_ranks[index],
+                "sell_impact_pct": impact(trade_usd, token_side),
+                "buy_impact_pct": impact(trade_usd, other_side),
+            }),
+        ));
+    }
+    entries.sort_by_key(|(rank, _)| *rank);
+
+    let total_tvl: f64 = pools.iter().filter_map(|pool| pool.tvl).sum();
+    let total_volume: f64 = pools.iter().filter_map(|pool| pool.volume_24h).sum();
+    let share = |part: f64, total: f64| (total > 0.0).then(|| round(part / total * 100.0, 2));
+
+    let mut programs: Vec<Value> = programs
+        .into_iter()
+        .map(|(program, totals)| {
+            json!({
+                "program_id": program,
+                "name": program_name(program),
+                "pools": totals.pools,
+                "tvl_usd": round(totals.tvl, 2),
+                "tvl_share": share(totals.tvl, total_tvl),
+                "volume_24h_usd": round(totals.volume_24h, 2),
+                "volume_share": share(totals.volume_24h, total_volume),
+                "deepest_pool": totals.deepest.map(|pool| &pool.address),
+                "sell_impact_pct": impact(trade_usd, Some(totals.sell_depth)),
+                "buy_impact_pct": impact(trade_usd, Some(totals.buy_depth)),
+            })
+        })
+        .collect();
+    programs.sort_by(|a, b| {
+        let tvl = |program: &Value| program["tvl_usd"].as_f64().unwrap_or_default();
+        tvl(b).total_cmp(&tvl(a))
+    });
+
+    json!({
+        "price": price,
+        "trade_size_usd": trade_usd,
+        "total_tvl_usd": round(total_tvl, 2),
+        "total_volume_24h_usd": round(total_volume, 2),
+        "volume_tvl": (total_tvl > 0.0).then(|| round(total_volume / total_tvl, 4)),
+        "sell_impact_pct": impact(trade_usd, Some(sell_depth)),
+        "buy_impact_pct": impact(trade_usd, Some(buy_depth)),
+        "programs": programs,
+        "pools": entries.into_iter().map(|(_, entry)| entry).collect::<Vec<_>>(),
+    })
+}
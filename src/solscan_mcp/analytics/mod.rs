@@ -0,0 +1,76 @@
+// Aggregations behind the composite tools: the tools in `api.rs` fetch the data, the
+// functions here only look at the returned JSON
+
+pub mod cluster;
+pub mod counterparties;
+pub mod explain;
+pub mod exports;
+pub mod flow;
+pub mod graph;
+pub mod holders;
+pub mod liquidity;
+pub mod pnl;
+pub mod poisoning;
+pub mod portfolio;
+pub mod prices;
+pub mod risk;
+pub mod sandwich;
+pub mod series;
+pub mod staking;
+pub mod tax;
+pub mod wallet;
+
+use serde_json::Value;
+
+use crate::solscan_mcp::enrich::as_f64;
+
+/// Items gathered from consecutive pages of a list endpoint
+#[derive(Debug, Default)]
+pub struct Pages {
+    pub items: Vec<Value>,
+    /// Whether the last page was reached, as opposed to stopping at the page limit or an error
+    pub complete: bool,
+}
+
+/// Items of a Solscan list response: `data` itself or the list under `data.items` (or
+/// `data.transactions` for block transactions)
+pub fn items(response: &Value) -> &[Value] {
+    match response.get("data") {
+        Some(Value::Array(items)) => items,
+        Some(data) => data
+            .get("items")
+            .or_else(|| data.get("transactions"))
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default(),
+        None => &[],
+    }
+}
+
+pub fn str_field<'a>(item: &'a Value, key: &str) -> Option<&'a str> {
+    item.get(key).and_then(Value::as_str)
+}
+
+pub fn f64_field(item: &Value, key: &str) -> Option<f64> {
+    item.get(key).and_then(as_f64)
+}
+
+pub fn i64_field(item: &Value, key: &str) -> Option<i64> {
+    item.get(key).and_then(|value| match value {
+        Value::String(text) => text.parse().ok(),
+        value => value.as_i64(),
+    })
+}
+
+/// USD value of a transfer or activity at the time it happened, as reported by Solscan.
+///
+/// The enrichment step's `usd_value_now` is priced today and is deliberately not used here.
+pub fn usd_value(item: &Value) -> Option<f64> {
+    f64_field(item, "value")
+}
+
+/// Round to a fixed number of decimals so summaries stay readable
+pub fn round(value: f64, decimals: i32) -> f64 {
+    let factor = 10f64.powi(decimals);
+    (value * factor).round() / factor
+}
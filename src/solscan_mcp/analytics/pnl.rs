@@ -0,0 +1,561 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use chrono::{DateTime, Datelike};
+use serde_json::{json, Value};
+
+use super::prices::PriceHistory;
+use super::{f64_field, i64_field, round, str_field};
+use crate::solscan_mcp::enrich::{price_address, SOL_ADDRESS};
+
+/// How sold amounts are matched against earlier acquisitions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CostMethod {
+    Fifo,
+    Lifo,
+    Average,
+}
+
+impl std::str::FromStr for CostMethod {
+    type Err = String;
+
+    fn from_str(method: &str) -> Result<Self, Self::Err> {
+        match method.to_ascii_lowercase().as_str() {
+            "fifo" => Ok(Self::Fifo),
+            "lifo" => Ok(Self::Lifo),
+            "average" | "avg" | "average_cost" => Ok(Self::Average),
+            _ => Err(format!(
+                "unknown cost method {:?}, expected fifo, lifo or average",
+                method
+            )),
+        }
+    }
+}
+
+impl CostMethod {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Fifo => "fifo",
+            Self::Lifo => "lifo",
+            Self::Average => "average",
+        }
+    }
+}
+
+/// Length of the periods results are grouped by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Day,
+    Week,
+    Month,
+}
+
+impl std::str::FromStr for Period {
+    type Err = String;
+
+    fn from_str(period: &str) -> Result<Self, Self::Err> {
+        match period.to_ascii_lowercase().as_str() {
+            "day" | "daily" => Ok(Self::Day),
+            "week" | "weekly" => Ok(Self::Week),
+            "month" | "monthly" => Ok(Self::Month),
+            _ => Err(format!(
+                "unknown period {:?}, expected day, week or month",
+                period
+            )),
+        }
+    }
+}
+
+impl Period {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Month => "month",
+        }
+    }
+
+    /// `2024-03-05`, `2024-W10` or `2024-03` for a Unix timestamp
+    pub fn key(self, time: i64) -> String {
+        let Some(date) = DateTime::from_timestamp(time, 0) else {
+            return time.to_string();
+        };
+        match self {
+            Self::Day => date.format("%Y-%m-%d").to_string(),
+            Self::Week => {
+                let week = date.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            Self::Month => date.format("%Y-%m").to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Leg {
+    pub token: String,
+    pub amount: f64,
+}
+
+#[derive(Debug, Clone)]
+pub enum TradeKind {
+    Swap {
+        sold: Leg,
+        bought: Leg,
+    },
+    /// Tokens received outside of a swap, acquired at their market value
+    Deposit(Leg),
+    /// Tokens sent outside of a swap; they leave with their cost basis, without PnL
+    Withdrawal(Leg),
+}
+
+#[derive(Debug, Clone)]
+pub struct Trade {
+    pub time: i64,
+    pub kind: TradeKind,
+}
+
+impl Trade {
+    pub fn tokens(&self) -> Vec<&str> {
+        match &self.kind {
+            TradeKind::Swap { sold, bought } => vec![&sold.token, &bought.token],
+            TradeKind::Deposit(leg) | TradeKind::Withdrawal(leg) => vec![&leg.token],
+        }
+    }
+}
+
+/// Time, sold leg and bought leg of an enriched swap activity
+pub fn swap_legs(swap: &Value) -> Option<(i64, Leg, Leg)> {
+    let is_swap = str_field(swap, "activity_type")
+        .is_some_and(|activity_type| activity_type.contains("SWAP"));
+    if !is_swap {
+        return None;
+    }
+
+    let router = swap.get("routers").or_else(|| swap.get("amount_info"))?;
+    let leg = |token: &str, amount: &str| {
+        Some(Leg {
+            token: price_address(str_field(router, token)?).to_string(),
+            amount: f64_field(router, amount)?,
+        })
+    };
+
+    Some((
+        i64_field(swap, "block_time")?,
+        leg("token1", "ui_amount1")?,
+        leg("token2", "ui_amount2")?,
+    ))
+}
+
+/// Trades of a wallet, oldest first, from enriched swap activities and balance changes.
+/// Balance changes of swap transactions are left out since the swap already covers them.
+pub fn trades(swaps: &[Value], balance_changes: &[Value]) -> Vec<Trade> {
+    let mut trades = Vec::new();
+    let mut swap_transactions = HashSet::new();
+
+    for swap in swaps {
+        let Some((time, sold, bought)) = swap_legs(swap) else {
+            continue;
+        };
+
+        if let Some(trans_id) = str_field(swap, "trans_id") {
+            swap_transactions.insert(trans_id);
+        }
+        trades.push(Trade {
+            time,
+            kind: TradeKind::Swap { sold, bought },
+        });
+    }
+
+    for change in balance_changes {
+        let trans_id = str_field(change, "trans_id");
+        if trans_id.is_some_and(|trans_id| swap_transactions.contains(trans_id)) {
+            continue;
+        }
+
+        let (Some(time), Some(token), Some(amount)) = (
+            i64_field(change, "block_time"),
+            str_field(change, "token_address"),
+            f64_field(change, "ui_amount"),
+        ) else {
+            continue;
+        };
+
+        let increase = match str_field(change, "change_type") {
+            Some(change_type) => change_type == "inc",
+            None => amount > 0.0,
+        };
+        let leg = Leg {
+            token: price_address(token).to_string(),
+            amount: amount.abs(),
+        };
+        if leg.amount == 0.0 {
+            continue;
+        }
+
+        trades.push(Trade {
+            time,
+            kind: if increase {
+                TradeKind::Deposit(leg)
+            } else {
+                TradeKind::Withdrawal(leg)
+            },
+        });
+    }
+
+    trades.sort_by_key(|trade| trade.time);
+    trades
+}
+
+/// Current balance and price of a token held by the wallet
+#[derive(Debug, Clone, Copy)]
+pub struct Holding {
+    pub balance: f64,
+    pub price: Option<f64>,
+}
+
+/// Holdings of an `/account/portfolio` response by (price) token address; native SOL is
+/// merged into WSOL like the trades
+pub fn holdings(portfolio: &Value) -> HashMap<String, Holding> {
+    let data = &portfolio["data"];
+    let mut holdings: HashMap<String, Holding> = HashMap::new();
+
+    let tokens = data
+        .get("tokens")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    for token in tokens {
+        let (Some(address), Some(balance)) = (
+            str_field(token, "token_address"),
+            f64_field(token, "balance"),
+        ) else {
+            continue;
+        };
+        let holding = holdings
+            .entry(price_address(address).to_string())
+            .or_insert(Holding {
+                balance: 0.0,
+                price: None,
+            });
+        holding.balance += balance;
+        holding.price = holding.price.or(f64_field(token, "token_price"));
+    }
+
+    if let Some(native) = data.get("native_balance") {
+        if let Some(balance) = f64_field(native, "balance") {
+            let holding = holdings
+                .entry(price_address(SOL_ADDRESS).to_string())
+                .or_insert(Holding {
+                    balance: 0.0,
+                    price: None,
+                });
+            holding.balance += balance;
+            holding.price = holding.price.or(f64_field(native, "token_price"));
+        }
+    }
+
+    holdings
+}
+
+/// What the lots still held are valued at
+#[derive(Debug, Clone, Copy)]
+pub enum Valuation<'a> {
+    /// The current balances and prices of the wallet
+    Current(&'a HashMap<String, Holding>),
+    /// The tracked lots at the daily price of a past time, for a history that ends there
+    At(i64),
+}
+
+/// Acquired amount with its USD cost, `None` when the acquisition could not be priced
+#[derive(Debug, Clone, Copy)]
+struct Lot {
+    amount: f64,
+    cost: Option<f64>,
+}
+
+/// Open lots of one token
+#[derive(Debug, Default)]
+pub struct Lots {
+    lots: VecDeque<Lot>,
+}
+
+impl Lots {
+    pub fn acquire(&mut self, amount: f64, cost: Option<f64>, method: CostMethod) {
+        // Average cost keeps one priced and one unpriced lot, so an acquisition without a
+        // price does not lose the basis of the others
+        let pooled = self
+            .lots
+            .iter_mut()
+            .find(|lot| lot.cost.is_some() == cost.is_some());
+        match (method, pooled) {
+            (CostMethod::Average, Some(lot)) => {
+                lot.amount += amount;
+                lot.cost = lot.cost.zip(cost).map(|(a, b)| a + b);
+            }
+            _ => self.lots.push_back(Lot { amount, cost }),
+        }
+    }
+
+    /// Take `amount` out of the lots; returns the matched amount and its cost
+    pub fn consume(&mut self, amount: f64, method: CostMethod) -> (f64, Option<f64>) {
+        if method == CostMethod::Average {
+            return self.consume_average(amount);
+        }
+
+        let mut remaining = amount;
+        let mut cost = Some(0.0);
+
+        while remaining > 0.0 {
+            let lot = match method {
+                CostMethod::Lifo => self.lots.back_mut(),
+                CostMethod::Fifo | CostMethod::Average => self.lots.front_mut(),
+            };
+            let Some(lot) = lot else {
+                break;
+            };
+
+            let taken = remaining.min(lot.amount);
+            let share = if lot.amount > 0.0 {
+                taken / lot.amount
+            } else {
+                1.0
+            };
+            let taken_cost = lot.cost.map(|lot_cost| lot_cost * share);
+            cost = cost.zip(taken_cost).map(|(a, b)| a + b);
+            lot.amount -= taken;
+            lot.cost = lot
+                .cost
+                .map(|lot_cost| lot_cost - taken_cost.unwrap_or_default());
+            remaining -= taken;
+
+            // Drop exhausted lots, allowing for float dust
+            if lot.amount <= 1e-12 {
+                match method {
+                    CostMethod::Lifo => self.lots.pop_back(),
+                    CostMethod::Fifo | CostMethod::Average => self.lots.pop_front(),
+                };
+            }
+        }
+
+        (amount - remaining.max(0.0), cost)
+    }
+
+    /// Average cost takes the same share of the priced and the unpriced lot; the cost is
+    /// unknown when any unpriced units are taken
+    fn consume_average(&mut self, amount: f64) -> (f64, Option<f64>) {
+        let held = self.held();
+        let taken = amount.min(held);
+        if taken <= 0.0 {
+            return (0.0, Some(0.0));
+        }
+
+        let share = taken / held;
+        let mut cost = Some(0.0);
+        for lot in &mut self.lots {
+            let taken_cost = lot.cost.map(|lot_cost| lot_cost * share);
+            cost = cost.zip(taken_cost).map(|(a, b)| a + b);
+            lot.amount -= lot.amount * share;
+            lot.cost = lot.cost.zip(taken_cost).map(|(a, b)| a - b);
+        }
+        self.lots.retain(|lot| lot.amount > 1e-12);
+
+        (taken, cost)
+    }
+
+    fn held(&self) -> f64 {
+        self.lots.iter().map(|lot| lot.amount).sum()
+    }
+
+    /// Amount and cost of the priced lots, and the amount held without a known cost
+    fn basis(&self) -> (f64, f64, f64) {
+        self.lots
+            .iter()
+            .fold((0.0, 0.0, 0.0), |(amount, cost, unpriced), lot| {
+                match lot.cost {
+                    Some(lot_cost) => (amount + lot.amount, cost + lot_cost, unpriced),
+                    None => (amount, cost, unpriced + lot.amount),
+                }
+            })
+    }
+}
+
+#[derive(Debug, Default)]
+struct Position {
+    lots: Lots,
+    bought: f64,
+    sold: f64,
+    deposited: f64,
+    withdrawn: f64,
+    realized: f64,
+    /// Sold amounts with no acquisition in the scanned history to match
+    unmatched: f64,
+    /// Disposals whose PnL is unknown because a price or a cost basis is missing
+    unpriced: usize,
+}
+
+#[derive(Debug, Default)]
+struct PeriodTotals {
+    realized: f64,
+    disposals: usize,
+}
+
+/// Realized PnL of the trades with the given cost method and unrealized PnL of what is
+/// still held, per token and per period
+pub fn compute(
+    trades: &[Trade],
+    prices: &PriceHistory,
+    valuation: Valuation,
+    symbols: &HashMap<String, String>,
+    method: CostMethod,
+    period: Period,
+) -> Value {
+    let mut positions: BTreeMap<String, Position> = BTreeMap::new();
+    let mut periods: BTreeMap<String, PeriodTotals> = BTreeMap::new();
+    let mut unpriced_trades = 0;
+
+    for trade in trades {
+        let mut dispose =
+            |positions: &mut BTreeMap<String, Position>, leg: &Leg, proceeds: Option<f64>| {
+                let position = positions.entry(leg.token.clone()).or_default();
+                let (matched, cost) = position.lots.consume(leg.amount, method);
+                position.unmatched += leg.amount - matched;
+                if matched <= 0.0 {
+                    return;
+                }
+
+                // Only the part of the proceeds matched against known lots is realized
+                match (proceeds, cost) {
+                    (Some(proceeds), Some(cost)) => {
+                        let pnl = proceeds * matched / leg.amount - cost;
+                        position.realized += pnl;
+                        let totals = periods.entry(period.key(trade.time)).or_default();
+                        totals.realized += pnl;
+                        totals.disposals += 1;
+                    }
+                    _ => position.unpriced += 1,
+                }
+            };
+
+        match &trade.kind {
+            TradeKind::Swap { sold, bought } => {
+                // The trade is worth the sold leg, or the bought leg when only that one is priced
+                let value = prices
+                    .price_at(&sold.token, trade.time)
+                    .map(|price| price * sold.amount)
+                    .or_else(|| {
+                        prices
+                            .price_at(&bought.token, trade.time)
+                            .map(|price| price * bought.amount)
+                    });
+                if value.is_none() {
+                    unpriced_trades += 1;
+                }
+
+                dispose(&mut positions, sold, value);
+                positions.entry(sold.token.clone()).or_default().sold += sold.amount;
+
+                let position = positions.entry(bought.token.clone()).or_default();
+                position.bought += bought.amount;
+                position.lots.acquire(bought.amount, value, method);
+            }
+            TradeKind::Deposit(leg) => {
+                let value = prices
+                    .price_at(&leg.token, trade.time)
+                    .map(|price| price * leg.amount);
+                let position = positions.entry(leg.token.clone()).or_default();
+                position.deposited += leg.amount;
+                position.lots.acquire(leg.amount, value, method);
+            }
+            TradeKind::Withdrawal(leg) => {
+                let position = positions.entry(leg.token.clone()).or_default();
+                position.withdrawn += leg.amount;
+                position.lots.consume(leg.amount, method);
+            }
+        }
+    }
+
+    let mut realized_total = 0.0;
+    let mut unrealized_total = 0.0;
+    let mut tokens = Vec::new();
+
+    for (token, position) in &positions {
+        let held = position.lots.held();
+        let (balance, price) = match valuation {
+            Valuation::Current(holdings) => {
+                let holding = holdings.get(token);
+                (
+                    holding.map(|holding| holding.balance).unwrap_or_default(),
+                    holding.and_then(|holding| holding.price),
+                )
+            }
+            Valuation::At(time) => (held, prices.price_at(token, time)),
+        };
+
+        // Unrealized PnL covers the priced part of the tracked lots still in the wallet
+        let tracked = held.min(balance);
+        let share = if held > 0.0 { tracked / held } else { 0.0 };
+        let (priced_amount, priced_cost, unpriced_amount) = position.lots.basis();
+        let (priced_amount, unpriced_amount) = (priced_amount * share, unpriced_amount * share);
+        let cost = (priced_amount > 0.0 || unpriced_amount <= 0.0).then_some(priced_cost * share);
+        let value = price.map(|price| price * priced_amount);
+        let unrealized = value.zip(cost).map(|(value, cost)| value - cost);
+
+        realized_total += position.realized;
+        unrealized_total += unrealized.unwrap_or_default();
+
+        tokens.push(json!({
+            "token_address": token,
+            "symbol": symbols.get(token),
+            "bought": position.bought,
+            "sold": position.sold,
+            "deposited": position.deposited,
+            "withdrawn": position.withdrawn,
+            "realized_usd": round(position.realized, 2),
+            "unpriced_disposals": position.unpriced,
+            "unmatched_sold_amount": position.unmatched,
+            "held_amount": tracked,
+            "unpriced_held_amount": unpriced_amount,
+            "cost_basis_usd": cost.map(|cost| round(cost, 2)),
+            "current_value_usd": value.map(|value| round(value, 2)),
+            "unrealized_usd": unrealized.map(|unrealized| round(unrealized, 2)),
+            // Current balance not explained by the scanned history, unknown at a past time
+            "untracked_amount": match valuation {
+                Valuation::Current(_) => Some((balance - held).max(0.0)),
+                Valuation::At(_) => None,
+            },
+        }));
+    }
+
+    tokens.sort_by(|a, b| {
+        let pnl = |token: &Value| {
+            f64_field(token, "realized_usd").unwrap_or_default().abs()
+                + f64_field(token, "unrealized_usd").unwrap_or_default().abs()
+        };
+        pnl(b).total_cmp(&pnl(a))
+    });
+
+    json!({
+        "method": method.name(),
+        "period": period.name(),
+        "valued_at": match valuation {
+            Valuation::Current(_) => None,
+            Valuation::At(time) => Some(time),
+        },
+        "trades": trades.len(),
+        "unpriced_trades": unpriced_trades,
+        "totals": {
+            "realized_usd": round(realized_total, 2),
+            "unrealized_usd": round(unrealized_total, 2),
+            "total_usd": round(realized_total + unrealized_total, 2),
+        },
+        "tokens": tokens,
+        "periods": periods
+            .into_iter()
+            .map(|(period, totals)| json!({
+                "period": period,
+                "realized_usd": round(totals.realized, 2),
+                "disposals": totals.disposals,
+            }))
+            .collect::<Vec<_>>(),
+    })
+}
@@ -0,0 +1,189 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use serde_json::{json, Value};
+
+use super::{f64_field, i64_field, round, str_field, usd_value};
+
+// Transaction ids kept per flagged sender as evidence
+const MAX_EVIDENCE_TRANSACTIONS: usize = 3;
+
+/// What makes an inbound transfer suspicious
+#[derive(Debug, Clone, Copy)]
+pub struct PoisoningRules {
+    /// Transfers worth less than this many USD count as dust
+    pub dust_usd: f64,
+    /// Characters a sender must share with a known counterparty, counting the start and
+    /// the end of both addresses
+    pub min_match: usize,
+}
+
+/// Transfers from one sender that share a pattern
+#[derive(Default)]
+struct Sender {
+    transfers: usize,
+    zero_value: usize,
+    unpriced: usize,
+    value_usd: f64,
+    tokens: BTreeSet<String>,
+    first_time: Option<i64>,
+    last_time: Option<i64>,
+    trans_ids: Vec<String>,
+}
+
+impl Sender {
+    fn add(&mut self, transfer: &Value) {
+        self.transfers += 1;
+        if f64_field(transfer, "ui_amount").or_else(|| f64_field(transfer, "amount")) == Some(0.0) {
+            self.zero_value += 1;
+        }
+        match usd_value(transfer) {
+            Some(value) => self.value_usd += value,
+            None => self.unpriced += 1,
+        }
+        if let Some(token) = str_field(transfer, "token_address") {
+            self.tokens.insert(token.to_string());
+        }
+        if let Some(time) = i64_field(transfer, "block_time") {
+            self.first_time = Some(self.first_time.map_or(time, |first| first.min(time)));
+            self.last_time = Some(self.last_time.map_or(time, |last| last.max(time)));
+        }
+        if let Some(trans_id) = str_field(transfer, "trans_id") {
+            if self.trans_ids.len() < MAX_EVIDENCE_TRANSACTIONS
+                && !self.trans_ids.iter().any(|known| known == trans_id)
+            {
+                self.trans_ids.push(trans_id.to_string());
+            }
+        }
+    }
+
+    fn evidence(&self) -> Value {
+        json!({
+            "transfers": self.transfers,
+            "zero_value_transfers": self.zero_value,
+            "unpriced_transfers": self.unpriced,
+            "value_usd": round(self.value_usd, 4),
+            "tokens": self.tokens,
+            "first_time": self.first_time,
+            "last_time": self.last_time,
+            "trans_ids": self.trans_ids,
+        })
+    }
+}
+
+/// Scan an account's inbound transfers for address poisoning and dusting.
+///
+/// Known counterparties are the receivers of the account's outbound transfers and the
+/// senders of priced inbound transfers above the dust threshold. Inbound transfers below
+/// the threshold, or without a price, are suspicious. A poisoning sender sends them from an
+/// address that starts and ends like a known counterparty but is not; other senders of
+/// suspicious transfers are reported as dusting.
+pub fn detect(inbound: &[Value], outbound: &[Value], rules: PoisoningRules) -> Value {
+    let is_dust =
+        |transfer: &Value| usd_value(transfer).is_some_and(|value| value < rules.dust_usd);
+    // An unpriced token is worth whatever its sender claims, so only a price clears a sender
+    let is_suspicious =
+        |transfer: &Value| usd_value(transfer).is_none_or(|value| value < rules.dust_usd);
+
+    let known: HashSet<&str> = outbound
+        .iter()
+        .filter(|transfer| !is_dust(transfer))
+        .filter_map(|transfer| str_field(transfer, "to_address"))
+        .chain(
+            inbound
+                .iter()
+                .filter(|transfer| !is_suspicious(transfer))
+                .filter_map(|transfer| str_field(transfer, "from_address")),
+        )
+        .collect();
+
+    let mut dust_senders: BTreeMap<&str, Sender> = BTreeMap::new();
+    for transfer in inbound.iter().filter(|transfer| is_suspicious(transfer)) {
+        if let Some(sender) = str_field(transfer, "from_address") {
+            if !known.contains(sender) {
+                dust_senders.entry(sender).or_default().add(transfer);
+            }
+        }
+    }
+
+    let mut poisoning = Vec::new();
+    let mut dusting = Vec::new();
+    for (sender, activity) in &dust_senders {
+        let lookalike = known
+            .iter()
+            .filter_map(|known| {
+                let (prefix, suffix) = matching_ends(sender, known);
+                (prefix > 0 && suffix > 0 && prefix + suffix >= rules.min_match)
+                    .then_some((*known, prefix, suffix))
+            })
+            .max_by(|(a, a_prefix, a_suffix), (b, b_prefix, b_suffix)| {
+                (a_prefix + a_suffix)
+                    .cmp(&(b_prefix + b_suffix))
+                    .then(b.cmp(a))
+            });
+
+        match lookalike {
+            Some((imitates, prefix, suffix)) => {
+                let mut flag = json!({
+                    "sender": sender,
+                    "imitates": imitates,
+                    "prefix_match": prefix,
+                    "suffix_match": suffix,
+                    "reason": format!(
+                        "Sends dust from an address sharing the first {} and last {} characters \
+                         of {}, a counterparty of this account, so that it shows up in the \
+                         history next to it; copying the address from there would send funds \
+                         to the attacker",
+                        prefix, suffix, imitates
+                    ),
+                });
+                merge(&mut flag, activity.evidence());
+                poisoning.push(flag);
+            }
+            None => {
+                let mut flag = json!({
+                    "sender": sender,
+                    "reason": "Sends worthless or unpriced transfers to this account without \
+                               any other interaction, typically to advertise a scam token or \
+                               to link wallets by tracing where the dust moves",
+                });
+                merge(&mut flag, activity.evidence());
+                dusting.push(flag);
+            }
+        }
+    }
+
+    let transfers = |flag: &Value| flag["transfers"].as_u64().unwrap_or_default();
+    poisoning.sort_by_key(|flag| std::cmp::Reverse(transfers(flag)));
+    dusting.sort_by_key(|flag| std::cmp::Reverse(transfers(flag)));
+
+    json!({
+        "inbound_scanned": inbound.len(),
+        "outbound_scanned": outbound.len(),
+        "known_counterparties": known.len(),
+        "dust_transfers": dust_senders.values().map(|sender| sender.transfers).sum::<usize>(),
+        "poisoning": poisoning,
+        "dusting": dusting,
+    })
+}
+
+/// Number of characters two addresses share at their start and at their end
+fn matching_ends(a: &str, b: &str) -> (usize, usize) {
+    if a == b {
+        return (0, 0);
+    }
+
+    let prefix = a.chars().zip(b.chars()).take_while(|(a, b)| a == b).count();
+    let suffix = a
+        .chars()
+        .rev()
+        .zip(b.chars().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    (prefix, suffix)
+}
+
+fn merge(target: &mut Value, source: Value) {
+    if let (Some(target), Value::Object(source)) = (target.as_object_mut(), source) {
+        target.extend(source);
+    }
+}
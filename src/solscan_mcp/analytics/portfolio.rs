@@ -0,0 +1,208 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::DateTime;
+use serde_json::{json, Value};
+
+use super::prices::PriceHistory;
+use super::{f64_field, i64_field, round, str_field};
+use crate::solscan_mcp::enrich::price_address;
+
+/// A change of one token balance of the wallet, in token units: positive for an increase
+#[derive(Debug, Clone)]
+pub struct BalanceChange {
+    pub time: i64,
+    pub token: String,
+    pub amount: f64,
+}
+
+/// Signed balance changes of enriched `/account/balance_change` items, newest first. Native
+/// SOL is merged into WSOL like the portfolio holdings.
+pub fn balance_changes(items: &[Value]) -> Vec<BalanceChange> {
+    let mut changes: Vec<BalanceChange> = items
+        .iter()
+        .filter_map(|change| {
+            let amount = f64_field(change, "ui_amount")?;
+            let increase = match str_field(change, "change_type") {
+                Some(change_type) => change_type == "inc",
+                None => amount > 0.0,
+            };
+            Some(BalanceChange {
+                time: i64_field(change, "block_time")?,
+                token: price_address(str_field(change, "token_address")?).to_string(),
+                amount: if increase {
+                    amount.abs()
+                } else {
+                    -amount.abs()
+                },
+            })
+        })
+        .collect();
+    changes.sort_by_key(|change| std::cmp::Reverse(change.time));
+    changes
+}
+
+/// `(YYYY-MM-DD, end time)` of every day from `from_time` to `to_time`, oldest first; the
+/// last day ends at `to_time`
+pub fn days(from_time: i64, to_time: i64) -> Vec<(String, i64)> {
+    const DAY: i64 = 86_400;
+
+    let mut days = Vec::new();
+    let mut start = from_time - from_time.rem_euclid(DAY);
+    while start <= to_time {
+        if let Some(date) = DateTime::from_timestamp(start, 0) {
+            let end = (start + DAY - 1).min(to_time);
+            days.push((date.format("%Y-%m-%d").to_string(), end));
+        }
+        start += DAY;
+    }
+    days
+}
+
+/// End-of-day balance of every token on each day, oldest day first: the current balances
+/// with the changes made after the end of the day undone. `days` are `(label, end time)`
+/// pairs, oldest first.
+pub fn daily_balances(
+    current: &HashMap<String, f64>,
+    changes: &[BalanceChange],
+    days: &[(String, i64)],
+) -> BTreeMap<String, Vec<f64>> {
+    let mut balances: HashMap<&str, f64> = current
+        .iter()
+        .map(|(token, balance)| (token.as_str(), *balance))
+        .collect();
+    for change in changes {
+        balances.entry(&change.token).or_default();
+    }
+
+    let mut series: BTreeMap<String, Vec<f64>> = balances
+        .keys()
+        .map(|token| (token.to_string(), vec![0.0; days.len()]))
+        .collect();
+
+    let mut pending = changes.iter().peekable();
+    for (index, (_, end)) in days.iter().enumerate().rev() {
+        while let Some(change) = pending.next_if(|change| change.time > *end) {
+            if let Some(balance) = balances.get_mut(change.token.as_str()) {
+                *balance -= change.amount;
+            }
+        }
+        for (token, balance) in &balances {
+            if let Some(values) = series.get_mut(*token) {
+                // Rounding of the undone changes can leave dust below zero
+                values[index] = balance.max(0.0);
+            }
+        }
+    }
+
+    series.retain(|_, values| values.iter().any(|balance| *balance > 0.0));
+    series
+}
+
+/// Value the daily balances with the price of each day: chart-ready `dates` and `total_usd`
+/// arrays, and per token its `balance`, `price`, `value_usd` and `share_pct` arrays.
+/// Tokens without any price are listed under `unpriced` and left out of the totals;
+/// `unpriced_tokens` counts the tokens held but not valued on each day.
+pub fn valuation(
+    days: &[(String, i64)],
+    balances: &BTreeMap<String, Vec<f64>>,
+    prices: &PriceHistory,
+    symbols: &HashMap<String, String>,
+) -> Value {
+    let mut totals = vec![0.0; days.len()];
+    // Tokens held each day without a price that day, left out of its total
+    let mut missing = vec![0; days.len()];
+    let mut tokens = Vec::new();
+    let mut unpriced = Vec::new();
+
+    for (token, token_balances) in balances {
+        let token_prices: Vec<Option<f64>> = days
+            .iter()
+            .map(|(_, end)| prices.price_at(token, *end))
+            .collect();
+        for ((missing, balance), price) in missing.iter_mut().zip(token_balances).zip(&token_prices)
+        {
+            if *balance > 0.0 && price.is_none() {
+                *missing += 1;
+            }
+        }
+        if token_prices.iter().all(Option::is_none) {
+            unpriced.push(json!({
+                "token_address": token,
+                "symbol": symbols.get(token),
+                "balance": token_balances,
+            }));
+            continue;
+        }
+
+        let values: Vec<Option<f64>> = token_balances
+            .iter()
+            .zip(&token_prices)
+            .map(|(balance, price)| price.map(|price| balance * price))
+            .collect();
+        for (total, value) in totals.iter_mut().zip(&values) {
+            *total += value.unwrap_or_default();
+        }
+        tokens.push((token, token_balances, token_prices, values));
+    }
+
+    let share = |value: Option<f64>, total: f64| {
+        value
+            .filter(|_| total > 0.0)
+            .map(|value| round(value / total * 100.0, 2))
+    };
+    let mut tokens: Vec<Value> = tokens
+        .into_iter()
+        .map(|(token, token_balances, token_prices, values)| {
+            let rounded: Vec<Option<f64>> = values
+                .iter()
+                .map(|value| value.map(|value| round(value, 2)))
+                .collect();
+            json!({
+                "token_address": token,
+                "symbol": symbols.get(token),
+                "balance": token_balances,
+                "price": token_prices,
+                "value_usd": rounded,
+                "share_pct": values
+                    .iter()
+                    .zip(&totals)
+                    .map(|(value, total)| share(*value, *total))
+                    .collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+    // Largest holding on the last day first
+    let last_value = |token: &Value| {
+        token["value_usd"]
+            .as_array()
+            .and_then(|values| values.last())
+            .and_then(Value::as_f64)
+            .unwrap_or_default()
+    };
+    tokens.sort_by(|a, b| last_value(b).total_cmp(&last_value(a)));
+
+    let peak = totals
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(index, value)| json!({ "date": days[index].0, "value_usd": round(*value, 2) }));
+    let (first, last) = (totals.first().copied(), totals.last().copied());
+    let change = match (first, last) {
+        (Some(first), Some(last)) if first > 0.0 => Some(round((last / first - 1.0) * 100.0, 2)),
+        _ => None,
+    };
+
+    json!({
+        "dates": days.iter().map(|(date, _)| date).collect::<Vec<_>>(),
+        "total_usd": totals.iter().map(|total| round(*total, 2)).collect::<Vec<_>>(),
+        "unpriced_tokens": missing,
+        "summary": {
+            "start_value_usd": first.map(|value| round(value, 2)),
+            "end_value_usd": last.map(|value| round(value, 2)),
+            "change_pct": change,
+            "peak": peak,
+        },
+        "tokens": tokens,
+        "unpriced": unpriced,
+    })
+}
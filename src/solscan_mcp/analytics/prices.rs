@@ -0,0 +1,102 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, NaiveDate};
+use serde_json::Value;
+
+use super::{f64_field, i64_field, items};
+use crate::solscan_mcp::enrich::price_address;
+
+// Distance from the day asked for within which a daily price is still used
+const PRICE_TOLERANCE_SECS: i64 = 86_400;
+
+/// `YYYYMMDD` date of a Unix timestamp, the format of Solscan price history
+pub fn date_key(time: i64) -> Option<i64> {
+    DateTime::from_timestamp(time, 0)?
+        .format("%Y%m%d")
+        .to_string()
+        .parse()
+        .ok()
+}
+
+/// Unix timestamp of midnight UTC of a `YYYYMMDD` date
+pub fn date_time(date: i64) -> Option<i64> {
+    let date = NaiveDate::parse_from_str(&date.to_string(), "%Y%m%d").ok()?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc().timestamp())
+}
+
+/// Parse a `/token/price` response into a `YYYYMMDD` -> USD price map
+pub fn price_points(response: &Value) -> BTreeMap<i64, f64> {
+    items(response)
+        .iter()
+        .filter_map(|point| Some((i64_field(point, "date")?, f64_field(point, "price")?)))
+        .collect()
+}
+
+/// Daily USD prices per token, keyed by `YYYYMMDD`
+#[derive(Debug, Default)]
+pub struct PriceHistory {
+    prices: HashMap<String, BTreeMap<i64, f64>>,
+}
+
+impl PriceHistory {
+    pub fn insert(&mut self, token: &str, prices: BTreeMap<i64, f64>) {
+        self.prices
+            .entry(price_address(token).to_string())
+            .or_default()
+            .extend(prices);
+    }
+
+    /// Daily prices of a token, keyed by `YYYYMMDD`
+    pub fn prices(&self, token: &str) -> Option<&BTreeMap<i64, f64>> {
+        self.prices.get(price_address(token))
+    }
+
+    /// Price of the day of `time`, or of the day before or after when that day has none.
+    /// `None` past that: a stale price is not a price.
+    pub fn price_at(&self, token: &str, time: i64) -> Option<f64> {
+        let prices = self.prices.get(price_address(token))?;
+        let date = date_key(time)?;
+        if let Some(price) = prices.get(&date) {
+            return Some(*price);
+        }
+
+        let day = date_time(date)?;
+        let near = |(other, price): (&i64, &f64)| {
+            let gap = (date_time(*other)? - day).abs();
+            (gap <= PRICE_TOLERANCE_SECS).then_some(*price)
+        };
+        prices
+            .range(..date)
+            .next_back()
+            .and_then(near)
+            .or_else(|| prices.range(date..).next().and_then(near))
+    }
+}
+
+/// Time range of each token over `(token, time)` points, for the `limit` tokens with the
+/// most points. Also returns the number of distinct tokens seen.
+pub fn ranges<'a>(
+    points: impl IntoIterator<Item = (&'a str, i64)>,
+    limit: usize,
+) -> (BTreeMap<String, (i64, i64)>, usize) {
+    let mut ranges: HashMap<&str, (i64, i64, usize)> = HashMap::new();
+    for (token, time) in points {
+        let range = ranges.entry(token).or_insert((time, time, 0));
+        range.0 = range.0.min(time);
+        range.1 = range.1.max(time);
+        range.2 += 1;
+    }
+
+    let tokens = ranges.len();
+    let mut ranked: Vec<(&str, (i64, i64, usize))> = ranges.into_iter().collect();
+    ranked.sort_by(|(a, (_, _, a_points)), (b, (_, _, b_points))| {
+        b_points.cmp(a_points).then(a.cmp(b))
+    });
+
+    let ranges = ranked
+        .into_iter()
+        .take(limit)
+        .map(|(token, (from_time, to_time, _))| (token.to_string(), (from_time, to_time)))
+        .collect();
+    (ranges, tokens)
+}
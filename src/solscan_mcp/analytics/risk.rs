@@ -0,0 +1,443 @@
+use std::collections::BTreeSet;
+
+use rmcp::Error as McpError;
+use serde_json::{json, Value};
+
+use super::holders::{self, Partition};
+use super::{f64_field, i64_field, round, str_field};
+use crate::solscan_mcp::enrich::as_u32;
+
+// Markets and creator transfers listed per check as evidence
+const MAX_EVIDENCE_ITEMS: usize = 3;
+
+// Liquidity in USD under which a token fails or gets a warning
+const MIN_LIQUIDITY_USD: f64 = 10_000.0;
+const LOW_LIQUIDITY_USD: f64 = 50_000.0;
+
+// Percent of the circulating supply held by the top 10 holders
+const HIGH_TOP_10_SHARE: f64 = 50.0;
+const ELEVATED_TOP_10_SHARE: f64 = 30.0;
+
+// Percent of the supply the creator sent to pools
+const HIGH_CREATOR_SALES: f64 = 5.0;
+const ELEVATED_CREATOR_SALES: f64 = 1.0;
+
+// Token age in seconds
+const DAY: i64 = 86_400;
+const NEW_TOKEN_AGE: i64 = 7 * DAY;
+
+/// Responses gathered for a risk report; each source may have failed on its own
+pub struct RiskInputs {
+    /// `data` of `/token/meta`
+    pub meta: Result<Value, McpError>,
+    pub markets: Result<Vec<Value>, McpError>,
+    /// Largest holders, with pools and labeled program accounts already split off
+    pub holders: Result<Partition, McpError>,
+    /// Transfers sent by the creator wallet, enriched; `None` when the creator is unknown
+    pub creator_transfers: Option<Result<Vec<Value>, McpError>>,
+    /// Current Unix time, for the token age
+    pub now: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+    Unknown,
+}
+
+impl Status {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Pass => "pass",
+            Self::Warn => "warn",
+            Self::Fail => "fail",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// One item of the checklist: the points it adds to the risk score when it warns or fails,
+/// out of `weight`
+struct Check {
+    name: &'static str,
+    weight: u32,
+    status: Status,
+    points: u32,
+    summary: String,
+    evidence: Value,
+}
+
+impl Check {
+    fn new(name: &'static str, weight: u32) -> Self {
+        Self {
+            name,
+            weight,
+            status: Status::Unknown,
+            points: 0,
+            summary: String::new(),
+            evidence: Value::Null,
+        }
+    }
+
+    fn pass(self, summary: String, evidence: Value) -> Self {
+        self.rate(Status::Pass, 0, summary, evidence)
+    }
+
+    /// A warning counts for half of the check's weight
+    fn warn(self, summary: String, evidence: Value) -> Self {
+        let points = self.weight / 2;
+        self.rate(Status::Warn, points, summary, evidence)
+    }
+
+    fn fail(self, summary: String, evidence: Value) -> Self {
+        let points = self.weight;
+        self.rate(Status::Fail, points, summary, evidence)
+    }
+
+    fn unknown(self, summary: String) -> Self {
+        self.rate(Status::Unknown, 0, summary, Value::Null)
+    }
+
+    fn rate(mut self, status: Status, points: u32, summary: String, evidence: Value) -> Self {
+        self.status = status;
+        self.points = points;
+        self.summary = summary;
+        self.evidence = evidence;
+        self
+    }
+
+    fn to_value(&self) -> Value {
+        json!({
+            "check": self.name,
+            "status": self.status.as_str(),
+            "points": self.points,
+            "weight": self.weight,
+            "summary": self.summary,
+            "evidence": self.evidence,
+        })
+    }
+}
+
+/// Score a token against a fixed checklist: mint and freeze authority, liquidity depth,
+/// number of pools, holder concentration, creator sales and token age.
+///
+/// The risk score is the sum of the points of all checks, out of 100; checks whose source
+/// failed are `unknown`, count no points and are reported under `errors`. While any check
+/// is unknown the level is `incomplete` (or `unknown` when none could run), unless the
+/// points already scored make it `high`.
+pub fn report(token: &str, inputs: RiskInputs) -> Value {
+    let mut errors = serde_json::Map::new();
+    let mut source = |name: &str, error: &McpError| {
+        errors.insert(name.to_string(), json!(error.message));
+    };
+
+    let meta = match &inputs.meta {
+        Ok(meta) => Some(meta),
+        Err(e) => {
+            source("token_meta", e);
+            None
+        }
+    };
+    let markets = match &inputs.markets {
+        Ok(markets) => Some(markets.as_slice()),
+        Err(e) => {
+            source("token_markets", e);
+            None
+        }
+    };
+    let holders = match &inputs.holders {
+        Ok(holders) => Some(holders),
+        Err(e) => {
+            source("token_holders", e);
+            None
+        }
+    };
+    let creator_transfers = match &inputs.creator_transfers {
+        Some(Ok(transfers)) => Some(transfers.as_slice()),
+        Some(Err(e)) => {
+            source("token_transfer", e);
+            None
+        }
+        None => None,
+    };
+
+    let decimals = meta
+        .and_then(|meta| meta.get("decimals"))
+        .and_then(as_u32)
+        .unwrap_or_default();
+    let supply = meta
+        .and_then(|meta| f64_field(meta, "supply"))
+        .map(|supply| supply / 10f64.powi(decimals as i32));
+    let creator = meta.and_then(|meta| str_field(meta, "creator"));
+
+    let checks = [
+        authority_check(meta, "mint_authority", 25),
+        authority_check(meta, "freeze_authority", 15),
+        liquidity_check(markets),
+        pool_check(markets),
+        concentration_check(holders, supply),
+        creator_check(creator, creator_transfers, markets, supply),
+        age_check(meta, inputs.now),
+    ];
+
+    let score: u32 = checks.iter().map(|check| check.points).sum();
+    let unassessed: u32 = checks
+        .iter()
+        .filter(|check| check.status == Status::Unknown)
+        .map(|check| check.weight)
+        .sum();
+    let total: u32 = checks.iter().map(|check| check.weight).sum();
+    // With unknown checks the score is only a lower bound, which is enough to call it high
+    let level = match score {
+        _ if unassessed == total => "unknown",
+        50.. => "high",
+        _ if unassessed > 0 => "incomplete",
+        0..20 => "low",
+        _ => "medium",
+    };
+    let count = |status: Status| checks.iter().filter(|check| check.status == status).count();
+
+    json!({
+        "token_address": token,
+        "name": meta.and_then(|meta| meta.get("name")),
+        "symbol": meta.and_then(|meta| meta.get("symbol")),
+        "risk_score": score,
+        "risk_level": level,
+        // Weight of the unknown checks: the score could be this much higher
+        "unassessed_points": unassessed,
+        "failed": count(Status::Fail),
+        "warnings": count(Status::Warn),
+        "unknown": count(Status::Unknown),
+        "checks": checks.iter().map(Check::to_value).collect::<Vec<_>>(),
+        "errors": errors,
+    })
+}
+
+/// Whether an authority that can mint new tokens or freeze holder accounts is still set
+fn authority_check(meta: Option<&Value>, field: &'static str, weight: u32) -> Check {
+    let check = Check::new(field, weight);
+    let Some(meta) = meta else {
+        return check.unknown("Token metadata unavailable".to_string());
+    };
+
+    // Only an explicit null means revoked; a missing field says nothing
+    match meta.get(field) {
+        Some(Value::Null) => check.pass("Authority revoked".to_string(), json!({ field: null })),
+        Some(Value::String(authority)) if !authority.is_empty() => check.fail(
+            match field {
+                "mint_authority" => "The mint authority can still create new tokens",
+                _ => "The freeze authority can still freeze holder accounts",
+            }
+            .to_string(),
+            json!({ field: authority }),
+        ),
+        _ => check.unknown(format!("Token metadata does not report the {}", field)),
+    }
+}
+
+fn market_tvl(market: &Value) -> f64 {
+    f64_field(market, "total_tvl")
+        .or_else(|| f64_field(market, "tvl"))
+        .unwrap_or_default()
+}
+
+/// Total USD liquidity over the token's markets
+fn liquidity_check(markets: Option<&[Value]>) -> Check {
+    let check = Check::new("liquidity", 20);
+    let Some(markets) = markets else {
+        return check.unknown("Token markets unavailable".to_string());
+    };
+
+    let tvl: f64 = markets.iter().map(market_tvl).sum();
+    let mut deepest: Vec<&Value> = markets.iter().collect();
+    deepest.sort_by(|a, b| market_tvl(b).total_cmp(&market_tvl(a)));
+    let evidence = json!({
+        "total_tvl_usd": round(tvl, 2),
+        "deepest_pools": deepest
+            .iter()
+            .take(MAX_EVIDENCE_ITEMS)
+            .map(|market| json!({
+                "pool_id": market.get("pool_id").or_else(|| market.get("pool_address")),
+                "program_id": market.get("program_id"),
+                "tvl_usd": round(market_tvl(market), 2),
+            }))
+            .collect::<Vec<_>>(),
+    });
+
+    let summary = format!("${:.0} of liquidity across all pools", tvl);
+    if tvl < MIN_LIQUIDITY_USD {
+        check.fail(summary, evidence)
+    } else if tvl < LOW_LIQUIDITY_USD {
+        check.warn(summary, evidence)
+    } else {
+        check.pass(summary, evidence)
+    }
+}
+
+/// Number of pools the token trades in
+fn pool_check(markets: Option<&[Value]>) -> Check {
+    let check = Check::new("pools", 10);
+    let Some(markets) = markets else {
+        return check.unknown("Token markets unavailable".to_string());
+    };
+
+    let programs: BTreeSet<&str> = markets
+        .iter()
+        .filter_map(|market| str_field(market, "program_id"))
+        .collect();
+    let evidence = json!({ "pools": markets.len(), "programs": programs });
+
+    match markets.len() {
+        0 => check.fail("No pool trades the token".to_string(), evidence),
+        1 => check.warn("A single pool trades the token".to_string(), evidence),
+        pools => check.pass(format!("{} pools trade the token", pools), evidence),
+    }
+}
+
+/// Share of the circulating supply held by the largest holders
+fn concentration_check(holders: Option<&Partition>, supply: Option<f64>) -> Check {
+    let check = Check::new("holder_concentration", 15);
+    let Some((kept, excluded)) = holders else {
+        return check.unknown("Token holders unavailable".to_string());
+    };
+    if kept.is_empty() {
+        return check.unknown("No holders outside of pools".to_string());
+    }
+
+    let stats = holders::stats(kept, excluded, supply, &[]);
+    let Some(top_10) = stats["top_10_share"].as_f64() else {
+        return check.unknown("Supply unknown".to_string());
+    };
+    let evidence = json!({
+        "top_10_share": stats["top_10_share"],
+        "top_100_share": stats["top_100_share"],
+        "holders_read": stats["holders_read"],
+        "holders_kept": stats["holders_kept"],
+        "nakamoto_holders_read": stats["nakamoto_holders_read"],
+        "share_basis": stats["share_basis"],
+        "largest_holders": stats["top_holders"]
+            .as_array()
+            .map(|holders| &holders[..holders.len().min(MAX_EVIDENCE_ITEMS)]),
+        "excluded": stats["excluded"],
+    });
+
+    let summary = format!(
+        "The 10 largest holders own {:.2}% of the circulating supply",
+        top_10
+    );
+    if top_10 > HIGH_TOP_10_SHARE {
+        check.fail(summary, evidence)
+    } else if top_10 > ELEVATED_TOP_10_SHARE {
+        check.warn(summary, evidence)
+    } else {
+        check.pass(summary, evidence)
+    }
+}
+
+/// Tokens the creator wallet sent to the token's pools (sales) and to other wallets
+fn creator_check(
+    creator: Option<&str>,
+    transfers: Option<&[Value]>,
+    markets: Option<&[Value]>,
+    supply: Option<f64>,
+) -> Check {
+    let check = Check::new("creator_sales", 10);
+    let Some(creator) = creator else {
+        return check.unknown("Creator unknown".to_string());
+    };
+    let Some(transfers) = transfers else {
+        return check.unknown("Creator transfers unavailable".to_string());
+    };
+    let Some(supply) = supply.filter(|supply| *supply > 0.0) else {
+        return check.unknown("Supply unknown".to_string());
+    };
+
+    let pools = holders::pool_addresses(markets.unwrap_or_default());
+    let amount = |transfer: &Value| {
+        f64_field(transfer, "ui_amount").or_else(|| {
+            let decimals = transfer.get("token_decimals").and_then(as_u32)?;
+            Some(f64_field(transfer, "amount")? / 10f64.powi(decimals as i32))
+        })
+    };
+
+    let (mut sold, mut sent) = (0.0, 0.0);
+    let mut sales: Vec<&Value> = Vec::new();
+    for transfer in transfers
+        .iter()
+        .filter(|transfer| str_field(transfer, "from_address") == Some(creator))
+    {
+        let to_pool = ["to_address", "to_token_account"]
+            .iter()
+            .filter_map(|key| str_field(transfer, key))
+            .any(|address| pools.contains(address));
+        let amount = amount(transfer).unwrap_or_default();
+        if to_pool {
+            sold += amount;
+            sales.push(transfer);
+        } else {
+            sent += amount;
+        }
+    }
+    sales.sort_by(|a, b| {
+        amount(b)
+            .unwrap_or_default()
+            .total_cmp(&amount(a).unwrap_or_default())
+    });
+
+    let sold_share = sold / supply * 100.0;
+    let evidence = json!({
+        "creator": creator,
+        "transfers_scanned": transfers.len(),
+        "sold_to_pools": sold,
+        "sold_share": round(sold_share, 4),
+        "sent_to_wallets": sent,
+        "sent_share": round(sent / supply * 100.0, 4),
+        "largest_sales": sales
+            .iter()
+            .take(MAX_EVIDENCE_ITEMS)
+            .map(|transfer| json!({
+                "trans_id": transfer.get("trans_id"),
+                "block_time": transfer.get("block_time"),
+                "amount": amount(transfer),
+                "to_address": transfer.get("to_address"),
+            }))
+            .collect::<Vec<_>>(),
+    });
+
+    let summary = format!(
+        "The creator sold {:.2}% of the supply into the token's pools",
+        sold_share
+    );
+    if sold_share >= HIGH_CREATOR_SALES {
+        check.fail(summary, evidence)
+    } else if sold_share >= ELEVATED_CREATOR_SALES {
+        check.warn(summary, evidence)
+    } else {
+        check.pass(summary, evidence)
+    }
+}
+
+/// How long ago the token was created
+fn age_check(meta: Option<&Value>, now: i64) -> Check {
+    let check = Check::new("token_age", 5);
+    let Some(created) = meta.and_then(|meta| i64_field(meta, "created_time")) else {
+        return check.unknown("Creation time unknown".to_string());
+    };
+
+    let age = (now - created).max(0);
+    let evidence = json!({
+        "created_time": created,
+        "create_tx": meta.and_then(|meta| meta.get("create_tx")),
+        "age_days": round(age as f64 / DAY as f64, 1),
+    });
+    let summary = format!("Created {} days ago", age / DAY);
+    if age < DAY {
+        check.fail("Created less than a day ago".to_string(), evidence)
+    } else if age < NEW_TOKEN_AGE {
+        check.warn(summary, evidence)
+    } else {
+        check.pass(summary, evidence)
+    }
+}
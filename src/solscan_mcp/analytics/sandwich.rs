@@ -0,0 +1,191 @@
+use serde_json::{json, Value};
+
+use super::{f64_field, round, str_field};
+use crate::solscan_mcp::enrich::as_u32;
+
+/// One swap instruction of a transaction: `amount_in` of `token_in` for `amount_out` of
+/// `token_out` in `pool`, in token units
+#[derive(Debug, Clone)]
+pub struct SwapLeg {
+    pub pool: String,
+    pub program: Option<String>,
+    pub token_in: String,
+    pub token_out: String,
+    pub amount_in: f64,
+    pub amount_out: f64,
+}
+
+/// A front-run and a back-run around a victim swap
+#[derive(Debug)]
+pub struct Sandwich {
+    pub attacker: String,
+    pub front_tx: String,
+    pub back_tx: String,
+    pub front: SwapLeg,
+    pub back: SwapLeg,
+    /// The victim's input token, in which the attacker's profit is measured
+    pub token: String,
+    /// What the attacker got back minus what it paid, in `token`; `None` when the two runs
+    /// do not settle in that token
+    pub extracted: Option<f64>,
+}
+
+impl Sandwich {
+    pub fn to_value(&self) -> Value {
+        json!({
+            "attacker": self.attacker,
+            "front_tx": self.front_tx,
+            "back_tx": self.back_tx,
+            "front_run": leg_value(&self.front),
+            "back_run": leg_value(&self.back),
+            "extracted": {
+                "token_address": self.token,
+                "amount": self.extracted.map(|amount| round(amount, 9)),
+            },
+        })
+    }
+}
+
+/// Swap instructions listed by `/transaction/actions`, with the number of swaps left out
+/// because the decimals of one of their tokens are missing
+pub fn swap_legs(actions: &Value) -> (Vec<SwapLeg>, usize) {
+    let mut unscaled = 0;
+    let legs = actions["data"]
+        .get("activities")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter(|activity| {
+            str_field(activity, "activity_type")
+                .or_else(|| str_field(activity, "name"))
+                .is_some_and(|name| name.to_ascii_lowercase().contains("swap"))
+        })
+        .filter_map(|activity| {
+            let data = activity.get("data")?;
+            let decimals = |key: &str| data.get(key).and_then(as_u32);
+            let (Some(decimals_in), Some(decimals_out)) =
+                (decimals("token_decimal_1"), decimals("token_decimal_2"))
+            else {
+                unscaled += 1;
+                return None;
+            };
+            let scaled = |amount: &str, decimals: u32| {
+                Some(f64_field(data, amount)? / 10f64.powi(decimals as i32))
+            };
+
+            Some(SwapLeg {
+                pool: str_field(data, "amm_id")
+                    .or_else(|| str_field(data, "pool_address"))?
+                    .to_string(),
+                program: str_field(activity, "program_id").map(str::to_string),
+                token_in: str_field(data, "token_1")?.to_string(),
+                token_out: str_field(data, "token_2")?.to_string(),
+                amount_in: scaled("amount_1", decimals_in)?,
+                amount_out: scaled("amount_2", decimals_out)?,
+            })
+        })
+        .collect();
+
+    (legs, unscaled)
+}
+
+/// Signer of a `/block/transactions` item, listed either as a string or as an array
+pub fn signer(transaction: &Value) -> Option<&str> {
+    match transaction.get("signer")? {
+        Value::Array(signers) => signers.first()?.as_str(),
+        signer => signer.as_str(),
+    }
+}
+
+/// Transactions of the block around `trans_id`: up to `window` before it, nearest first,
+/// and up to `window` after it. `None` when the transaction is not in the list.
+pub fn neighbors<'a>(
+    block_transactions: &'a [Value],
+    trans_id: &str,
+    window: usize,
+) -> Option<(Vec<&'a Value>, Vec<&'a Value>)> {
+    let position = block_transactions
+        .iter()
+        .position(|transaction| str_field(transaction, "tx_hash") == Some(trans_id))?;
+
+    let before = block_transactions[..position]
+        .iter()
+        .rev()
+        .take(window)
+        .collect();
+    let after = block_transactions[position + 1..]
+        .iter()
+        .take(window)
+        .collect();
+    Some((before, after))
+}
+
+/// Signers with a transaction on both sides of the victim, with the nearest transaction on
+/// each side. The victim's own signer is left out.
+pub fn candidates<'a>(
+    before: &[&'a Value],
+    after: &[&'a Value],
+    victim: &str,
+) -> Vec<(&'a str, &'a str, &'a str)> {
+    let mut found: Vec<(&str, &str, &str)> = Vec::new();
+
+    for front in before {
+        let (Some(attacker), Some(front_tx)) = (signer(front), str_field(front, "tx_hash")) else {
+            continue;
+        };
+        if attacker == victim || found.iter().any(|(known, _, _)| *known == attacker) {
+            continue;
+        }
+
+        let back = after
+            .iter()
+            .find(|back| signer(back) == Some(attacker))
+            .and_then(|back| str_field(back, "tx_hash"));
+        if let Some(back_tx) = back {
+            found.push((attacker, front_tx, back_tx));
+        }
+    }
+
+    found
+}
+
+/// Check whether a front-run and a back-run transaction sandwich the victim's swap: the
+/// front run buys the token the victim buys in the same pool, the back run sells it there
+pub fn match_sandwich(
+    victim: &SwapLeg,
+    (attacker, front_tx, back_tx): (&str, &str, &str),
+    front_legs: &[SwapLeg],
+    back_legs: &[SwapLeg],
+) -> Option<Sandwich> {
+    let front = front_legs
+        .iter()
+        .find(|leg| leg.pool == victim.pool && leg.token_out == victim.token_out)?;
+    let back = back_legs
+        .iter()
+        .find(|leg| leg.pool == victim.pool && leg.token_in == victim.token_out)?;
+
+    let extracted = (front.token_in == victim.token_in && back.token_out == victim.token_in)
+        .then_some(back.amount_out - front.amount_in);
+
+    Some(Sandwich {
+        attacker: attacker.to_string(),
+        front_tx: front_tx.to_string(),
+        back_tx: back_tx.to_string(),
+        front: front.clone(),
+        back: back.clone(),
+        token: victim.token_in.clone(),
+        extracted,
+    })
+}
+
+pub fn leg_value(leg: &SwapLeg) -> Value {
+    json!({
+        "pool": leg.pool,
+        "program": leg.program,
+        "token_in": leg.token_in,
+        "amount_in": round(leg.amount_in, 9),
+        "token_out": leg.token_out,
+        "amount_out": round(leg.amount_out, 9),
+    })
+}
@@ -0,0 +1,215 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde_json::{json, Value};
+
+use super::pnl::Period;
+use super::prices::date_time;
+use super::round;
+
+const DAY: i64 = 86_400;
+
+/// Consecutive `[from, to]` day ranges of at most `days` days covering `from_time` to
+/// `to_time`, for endpoints that limit the range of one request
+pub fn chunks(from_time: i64, to_time: i64, days: i64) -> Vec<(i64, i64)> {
+    let mut chunks = Vec::new();
+    let mut start = from_time;
+    while start <= to_time {
+        let end = (start + (days - 1) * DAY).min(to_time);
+        chunks.push((start, end));
+        start = end + DAY;
+    }
+    chunks
+}
+
+/// OHLC of the daily prices falling in one period
+#[derive(Debug, Clone)]
+struct Candle {
+    time: i64,
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+}
+
+/// Resample `YYYYMMDD` daily prices into candles keyed by `period`
+fn candles(points: &BTreeMap<i64, f64>, period: Period) -> BTreeMap<String, Candle> {
+    let mut candles: BTreeMap<String, Candle> = BTreeMap::new();
+    for (date, price) in points {
+        let Some(time) = date_time(*date) else {
+            continue;
+        };
+        candles
+            .entry(period.key(time))
+            .and_modify(|candle| {
+                candle.high = candle.high.max(*price);
+                candle.low = candle.low.min(*price);
+                candle.close = *price;
+            })
+            .or_insert(Candle {
+                time,
+                open: *price,
+                high: *price,
+                low: *price,
+                close: *price,
+            });
+    }
+    candles
+}
+
+/// Periods in a year, to annualize volatility
+fn periods_per_year(period: Period) -> f64 {
+    match period {
+        Period::Day => 365.0,
+        Period::Week => 52.0,
+        Period::Month => 12.0,
+    }
+}
+
+/// Return, volatility and max drawdown of the closes of consecutive periods
+fn statistics(keys: &[&String], closes: &[f64], period: Period) -> Value {
+    let returns: Vec<f64> = closes
+        .windows(2)
+        .filter(|pair| pair[0] > 0.0)
+        .map(|pair| pair[1] / pair[0] - 1.0)
+        .collect();
+
+    let volatility = (returns.len() > 1).then(|| {
+        let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+        let variance = returns
+            .iter()
+            .map(|value| (value - mean).powi(2))
+            .sum::<f64>()
+            / (returns.len() - 1) as f64;
+        variance.sqrt()
+    });
+
+    // Largest fall from a running peak, with the periods of the peak and the trough
+    let mut drawdown: Option<(f64, usize, usize)> = None;
+    let mut peak = 0;
+    for (index, close) in closes.iter().enumerate() {
+        if *close > closes[peak] {
+            peak = index;
+        }
+        if closes[peak] > 0.0 {
+            let fall = 1.0 - close / closes[peak];
+            if fall > drawdown.map_or(0.0, |(largest, _, _)| largest) {
+                drawdown = Some((fall, peak, index));
+            }
+        }
+    }
+
+    let first = closes.first().copied();
+    let last = closes.last().copied();
+    let total_return = match (first, last) {
+        (Some(first), Some(last)) if first > 0.0 => Some(last / first - 1.0),
+        _ => None,
+    };
+    json!({
+        "first": first,
+        "last": last,
+        "high": closes.iter().copied().reduce(f64::max),
+        "low": closes.iter().copied().reduce(f64::min),
+        "total_return_pct": total_return.map(|total| round(total * 100.0, 4)),
+        "volatility_pct": volatility.map(|volatility| round(volatility * 100.0, 4)),
+        "annualized_volatility_pct": volatility
+            .map(|volatility| round(volatility * periods_per_year(period).sqrt() * 100.0, 4)),
+        "max_drawdown_pct": (!closes.is_empty())
+            .then(|| round(drawdown.map_or(0.0, |(fall, _, _)| fall) * 100.0, 4)),
+        "drawdown_peak": drawdown.map(|(_, peak, _)| keys[peak]),
+        "drawdown_trough": drawdown.map(|(_, _, trough)| keys[trough]),
+        "periods": closes.len(),
+    })
+}
+
+/// Resample the daily prices of each token into `period` candles on one timeline, with
+/// chart-ready arrays: `timeline` and `timestamps` give the periods, and every token has
+/// `open`, `high`, `low`, `close`, `returns_pct` and `normalized` arrays of the same length,
+/// `null` where the token has no price. `normalized` rebases the closes to 100 at the
+/// token's first period so several tokens can share one chart.
+pub fn align(
+    tokens: &[(String, BTreeMap<i64, f64>)],
+    symbols: &HashMap<String, String>,
+    period: Period,
+) -> Value {
+    let candles: Vec<BTreeMap<String, Candle>> = tokens
+        .iter()
+        .map(|(_, points)| candles(points, period))
+        .collect();
+
+    let keys: BTreeSet<&String> = candles.iter().flat_map(|candles| candles.keys()).collect();
+    let keys: Vec<&String> = keys.into_iter().collect();
+    let timestamps: Vec<Option<i64>> = keys
+        .iter()
+        .map(|key| {
+            candles
+                .iter()
+                .filter_map(|candles| candles.get(*key).map(|candle| candle.time))
+                .min()
+        })
+        .collect();
+
+    let series: Vec<Value> = tokens
+        .iter()
+        .zip(&candles)
+        .map(|((token, _), candles)| {
+            let column = |field: fn(&Candle) -> f64| -> Vec<Option<f64>> {
+                keys.iter()
+                    .map(|key| candles.get(*key).map(field))
+                    .collect()
+            };
+            let closes = column(|candle| candle.close);
+
+            let base = closes.iter().flatten().next().copied();
+            let normalized: Vec<Option<f64>> = closes
+                .iter()
+                .map(|close| match (close, base) {
+                    (Some(close), Some(base)) if base > 0.0 => Some(round(close / base * 100.0, 4)),
+                    _ => None,
+                })
+                .collect();
+
+            let mut previous: Option<f64> = None;
+            let returns: Vec<Option<f64>> = closes
+                .iter()
+                .map(|close| {
+                    let change = match (previous, close) {
+                        (Some(previous), Some(close)) if previous > 0.0 => {
+                            Some(round((close / previous - 1.0) * 100.0, 4))
+                        }
+                        _ => None,
+                    };
+                    if close.is_some() {
+                        previous = *close;
+                    }
+                    change
+                })
+                .collect();
+
+            let priced: Vec<(&String, f64)> = keys
+                .iter()
+                .zip(&closes)
+                .filter_map(|(key, close)| Some((*key, (*close)?)))
+                .collect();
+            let (priced_keys, priced_closes): (Vec<&String>, Vec<f64>) = priced.into_iter().unzip();
+
+            json!({
+                "token_address": token,
+                "symbol": symbols.get(token),
+                "open": column(|candle| candle.open),
+                "high": column(|candle| candle.high),
+                "low": column(|candle| candle.low),
+                "close": closes,
+                "returns_pct": returns,
+                "normalized": normalized,
+                "stats": statistics(&priced_keys, &priced_closes, period),
+            })
+        })
+        .collect();
+
+    json!({
+        "interval": period.name(),
+        "timeline": keys,
+        "timestamps": timestamps,
+        "tokens": series,
+    })
+}
@@ -0,0 +1,183 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde_json::{json, Value};
+
+use super::exports::RewardRow;
+use super::{f64_field, round, str_field};
+use crate::solscan_mcp::enrich::SOL_DECIMALS;
+
+// Epoch length assumed when the rewards do not span enough epochs to measure it
+const DEFAULT_EPOCH_SECONDS: f64 = 2.2 * 86_400.0;
+const YEAR_SECONDS: f64 = 365.0 * 86_400.0;
+
+fn sol(lamports: f64) -> f64 {
+    lamports / 10f64.powi(SOL_DECIMALS as i32)
+}
+
+/// Reward rate of one epoch: the reward over the balance it was earned on
+fn epoch_rate(row: &RewardRow, fallback_balance: f64) -> Option<f64> {
+    let balance = row
+        .post_balance
+        .map(|balance| balance - row.amount)
+        .unwrap_or(fallback_balance);
+    (balance > 0.0).then(|| row.amount / balance)
+}
+
+/// Average seconds per epoch over the rewards, `None` when they span less than two epochs
+fn epoch_seconds(rows: &[RewardRow]) -> Option<f64> {
+    let epochs: BTreeMap<u64, i64> = rows
+        .iter()
+        .filter_map(|row| Some((row.epoch?, row.time)))
+        .collect();
+    let ((first_epoch, first_time), (last_epoch, last_time)) =
+        (epochs.first_key_value()?, epochs.last_key_value()?);
+    (last_epoch > first_epoch && last_time > first_time)
+        .then(|| (last_time - first_time) as f64 / (last_epoch - first_epoch) as f64)
+}
+
+/// Stake and reward figures of one validator
+#[derive(Default)]
+struct Validator {
+    accounts: usize,
+    active: f64,
+    activating: f64,
+    deactivating: f64,
+    inactive: f64,
+    rewards: f64,
+    /// Sum of `apy * stake` and of `stake` over the accounts with a yield
+    weighted_apy: f64,
+    yield_stake: f64,
+}
+
+/// Tie stake accounts to their rewards: stake per validator and status, rewards per epoch,
+/// the effective APY of each stake account over its last `epochs` rewarded epochs and
+/// validators compared by realized yield.
+///
+/// `rewards` holds the parsed reward export of each stake account. The APY compounds the
+/// mean reward rate of those epochs over the epochs in a year, measured from the reward
+/// times.
+pub fn summary(
+    accounts: &[Value],
+    rewards: &HashMap<String, Vec<RewardRow>>,
+    epochs: usize,
+) -> Value {
+    let mut validators: BTreeMap<&str, Validator> = BTreeMap::new();
+    let mut per_epoch: BTreeMap<u64, (f64, usize, i64)> = BTreeMap::new();
+    let mut totals: BTreeMap<&str, f64> = BTreeMap::new();
+    let mut account_rows = Vec::new();
+    let mut total_rewards = 0.0;
+
+    let all_rows: Vec<RewardRow> = rewards.values().flatten().cloned().collect();
+    let epochs_per_year = YEAR_SECONDS / epoch_seconds(&all_rows).unwrap_or(DEFAULT_EPOCH_SECONDS);
+
+    for account in accounts {
+        let Some(address) = str_field(account, "stake_account") else {
+            continue;
+        };
+        let voter = str_field(account, "voter").unwrap_or("undelegated");
+        let status = str_field(account, "status").unwrap_or("unknown");
+        let amount = sol(f64_field(account, "amount").unwrap_or_default());
+        let active = sol(f64_field(account, "active_stake_amount").unwrap_or_default());
+
+        let validator = validators.entry(voter).or_default();
+        validator.accounts += 1;
+        match status {
+            "active" => validator.active += amount,
+            "activating" => validator.activating += amount,
+            "deactivating" => validator.deactivating += amount,
+            _ => validator.inactive += amount,
+        }
+        *totals.entry(status).or_default() += amount;
+
+        let mut rows: Vec<&RewardRow> = rewards
+            .get(address)
+            .map(|rows| rows.iter().filter(|row| row.amount > 0.0).collect())
+            .unwrap_or_default();
+        rows.sort_by_key(|row| std::cmp::Reverse((row.epoch, row.time)));
+
+        let earned: f64 = rows.iter().map(|row| row.amount).sum();
+        validator.rewards += earned;
+        total_rewards += earned;
+        for row in &rows {
+            if let Some(epoch) = row.epoch {
+                let entry = per_epoch.entry(epoch).or_insert((0.0, 0, row.time));
+                entry.0 += row.amount;
+                entry.1 += 1;
+                entry.2 = entry.2.min(row.time);
+            }
+        }
+
+        let rates: Vec<f64> = rows
+            .iter()
+            .take(epochs)
+            .filter_map(|row| epoch_rate(row, amount))
+            .collect();
+        let apy = (!rates.is_empty()).then(|| {
+            let mean = rates.iter().sum::<f64>() / rates.len() as f64;
+            (1.0 + mean).powf(epochs_per_year) - 1.0
+        });
+        if let Some(apy) = apy {
+            validator.weighted_apy += apy * amount;
+            validator.yield_stake += amount;
+        }
+
+        account_rows.push(json!({
+            "stake_account": address,
+            "voter": voter,
+            "status": status,
+            "stake_sol": amount,
+            "active_sol": active,
+            "rewards_sol": round(earned, 9),
+            "epochs_rewarded": rows.len(),
+            "last_epoch": rows.first().and_then(|row| row.epoch),
+            "apy_pct": apy.map(|apy| round(apy * 100.0, 4)),
+        }));
+    }
+
+    let mut validators: Vec<Value> = validators
+        .into_iter()
+        .map(|(voter, validator)| {
+            let apy = (validator.yield_stake > 0.0)
+                .then(|| validator.weighted_apy / validator.yield_stake);
+            json!({
+                "voter": voter,
+                "stake_accounts": validator.accounts,
+                "active_sol": validator.active,
+                "activating_sol": validator.activating,
+                "deactivating_sol": validator.deactivating,
+                "inactive_sol": validator.inactive,
+                "rewards_sol": round(validator.rewards, 9),
+                "realized_apy_pct": apy.map(|apy| round(apy * 100.0, 4)),
+            })
+        })
+        .collect();
+    // Best realized yield first, validators without rewards last
+    validators.sort_by(|a, b| {
+        let apy = |validator: &Value| validator["realized_apy_pct"].as_f64().unwrap_or(f64::MIN);
+        apy(b).total_cmp(&apy(a))
+    });
+
+    let per_epoch: Vec<Value> = per_epoch
+        .into_iter()
+        .rev()
+        .map(|(epoch, (amount, accounts, time))| {
+            json!({
+                "epoch": epoch,
+                "time": time,
+                "rewards_sol": round(amount, 9),
+                "stake_accounts": accounts,
+            })
+        })
+        .collect();
+
+    json!({
+        "stake_accounts": account_rows.len(),
+        "stake_sol": totals.values().sum::<f64>(),
+        "stake_by_status": totals,
+        "rewards_sol": round(total_rewards, 9),
+        "epochs_per_year": round(epochs_per_year, 2),
+        "validators": validators,
+        "epochs": per_epoch,
+        "accounts": account_rows,
+    })
+}
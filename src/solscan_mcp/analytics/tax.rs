@@ -0,0 +1,406 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Read;
+
+use chrono::DateTime;
+use serde_json::Value;
+
+use super::exports::{self, ExportError};
+use super::pnl::{self, CostMethod, Leg, Lots};
+use super::prices::PriceHistory;
+use super::{f64_field, i64_field, round, str_field};
+use crate::solscan_mcp::enrich::{price_address, SOL_ADDRESS};
+
+/// CSV layouts of the export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaxFormat {
+    /// One acquisition or disposal per row, with cost basis and gain
+    Generic,
+    /// Koinly universal import
+    Koinly,
+    /// CoinTracker CSV import
+    CoinTracker,
+}
+
+impl std::str::FromStr for TaxFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_ascii_lowercase().as_str() {
+            "generic" | "csv" => Ok(Self::Generic),
+            "koinly" => Ok(Self::Koinly),
+            "cointracker" => Ok(Self::CoinTracker),
+            _ => Err(format!(
+                "unknown export format {:?}, expected generic, koinly or cointracker",
+                format
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Category {
+    Trade,
+    TransferIn,
+    TransferOut,
+    StakingReward,
+}
+
+impl Category {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Trade => "trade",
+            Self::TransferIn => "transfer_in",
+            Self::TransferOut => "transfer_out",
+            Self::StakingReward => "staking_reward",
+        }
+    }
+}
+
+/// A taxable event: what left the wallet and what came in
+#[derive(Debug, Clone)]
+pub struct TaxEvent {
+    pub time: i64,
+    pub category: Category,
+    pub sent: Option<Leg>,
+    pub received: Option<Leg>,
+    pub trans_id: Option<String>,
+    pub counterparty: Option<String>,
+}
+
+impl TaxEvent {
+    pub fn tokens(&self) -> impl Iterator<Item = &str> {
+        self.sent
+            .iter()
+            .chain(&self.received)
+            .map(|leg| leg.token.as_str())
+    }
+}
+
+/// Events from enriched transfers and swap activities, oldest first. Transfers of swap
+/// transactions are left out since the swap already covers them.
+pub fn events(transfers: &[Value], swaps: &[Value]) -> Vec<TaxEvent> {
+    let mut events = Vec::new();
+    let mut swap_transactions = HashSet::new();
+
+    for swap in swaps {
+        let Some((time, sold, bought)) = pnl::swap_legs(swap) else {
+            continue;
+        };
+        let trans_id = str_field(swap, "trans_id");
+        if let Some(trans_id) = trans_id {
+            swap_transactions.insert(trans_id);
+        }
+        events.push(TaxEvent {
+            time,
+            category: Category::Trade,
+            sent: Some(sold),
+            received: Some(bought),
+            trans_id: trans_id.map(str::to_string),
+            counterparty: None,
+        });
+    }
+
+    for transfer in transfers {
+        let trans_id = str_field(transfer, "trans_id");
+        if trans_id.is_some_and(|trans_id| swap_transactions.contains(trans_id)) {
+            continue;
+        }
+
+        let (Some(time), Some(token), Some(amount)) = (
+            i64_field(transfer, "block_time"),
+            str_field(transfer, "token_address"),
+            f64_field(transfer, "ui_amount"),
+        ) else {
+            continue;
+        };
+        let leg = Leg {
+            token: price_address(token).to_string(),
+            amount,
+        };
+
+        let (category, sent, received, counterparty) = match str_field(transfer, "flow") {
+            Some("in") => (
+                Category::TransferIn,
+                None,
+                Some(leg),
+                str_field(transfer, "from_address"),
+            ),
+            Some("out") => (
+                Category::TransferOut,
+                Some(leg),
+                None,
+                str_field(transfer, "to_address"),
+            ),
+            _ => continue,
+        };
+
+        events.push(TaxEvent {
+            time,
+            category,
+            sent,
+            received,
+            trans_id: trans_id.map(str::to_string),
+            counterparty: counterparty.map(str::to_string),
+        });
+    }
+
+    events.sort_by_key(|event| event.time);
+    events
+}
+
+/// Staking rewards of an `/account/reward/export` CSV as acquisitions of SOL
+pub fn reward_events(export: impl Read) -> Result<Vec<TaxEvent>, ExportError> {
+    Ok(exports::reward_rows(export)?
+        .into_iter()
+        .filter(|row| row.amount != 0.0)
+        .map(|row| TaxEvent {
+            time: row.time,
+            category: Category::StakingReward,
+            sent: None,
+            received: Some(Leg {
+                token: price_address(SOL_ADDRESS).to_string(),
+                amount: row.amount,
+            }),
+            trans_id: row.epoch.map(|epoch| format!("epoch {}", epoch)),
+            counterparty: None,
+        })
+        .collect())
+}
+
+/// Name of a token in the export: the symbol when known, otherwise the address
+fn currency(symbols: &HashMap<String, String>, token: &str) -> String {
+    if token == price_address(SOL_ADDRESS) {
+        return "SOL".to_string();
+    }
+    symbols
+        .get(token)
+        .cloned()
+        .unwrap_or_else(|| token.to_string())
+}
+
+/// Counts of the export, for the caller to check the flagged rows
+#[derive(Debug, Default)]
+pub struct TaxSummary {
+    pub events: usize,
+    pub missing_prices: usize,
+}
+
+/// Write the events as CSV. The USD value of an event comes from the daily price of the
+/// sent leg, or of the received leg when only that one is known; events without either are
+/// written without a value and flagged, never estimated.
+pub fn render(
+    events: &[TaxEvent],
+    prices: &PriceHistory,
+    symbols: &HashMap<String, String>,
+    method: CostMethod,
+    format: TaxFormat,
+) -> Result<(String, TaxSummary), csv::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let mut summary = TaxSummary::default();
+    let mut lots: BTreeMap<String, Lots> = BTreeMap::new();
+
+    match format {
+        TaxFormat::Generic => writer.write_record([
+            "date_utc",
+            "timestamp",
+            "event",
+            "category",
+            "token_address",
+            "currency",
+            "amount",
+            "price_usd",
+            "value_usd",
+            "cost_basis_usd",
+            "gain_usd",
+            "price_missing",
+            "counter_currency",
+            "counter_amount",
+            "counterparty",
+            "trans_id",
+        ])?,
+        TaxFormat::Koinly => writer.write_record([
+            "Date",
+            "Sent Amount",
+            "Sent Currency",
+            "Received Amount",
+            "Received Currency",
+            "Fee Amount",
+            "Fee Currency",
+            "Net Worth Amount",
+            "Net Worth Currency",
+            "Label",
+            "Description",
+            "TxHash",
+        ])?,
+        TaxFormat::CoinTracker => writer.write_record([
+            "Date",
+            "Received Quantity",
+            "Received Currency",
+            "Sent Quantity",
+            "Sent Currency",
+            "Fee Amount",
+            "Fee Currency",
+            "Tag",
+        ])?,
+    }
+
+    for event in events {
+        let leg_value = |leg: &Leg| {
+            prices
+                .price_at(&leg.token, event.time)
+                .map(|price| price * leg.amount)
+        };
+        let value = event
+            .sent
+            .as_ref()
+            .and_then(leg_value)
+            .or_else(|| event.received.as_ref().and_then(leg_value));
+
+        summary.events += 1;
+        if value.is_none() {
+            summary.missing_prices += 1;
+        }
+
+        let date = DateTime::from_timestamp(event.time, 0)
+            .map(|date| date.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        let number = |value: Option<f64>| value.map(|value| value.to_string()).unwrap_or_default();
+        let usd = |value: Option<f64>| number(value.map(|value| round(value, 2)));
+
+        match format {
+            TaxFormat::Generic => {
+                let legs = [
+                    ("disposal", &event.sent, &event.received),
+                    ("acquisition", &event.received, &event.sent),
+                ];
+                for (kind, leg, counter) in legs {
+                    let Some(leg) = leg else {
+                        continue;
+                    };
+
+                    let token_lots = lots.entry(leg.token.clone()).or_default();
+                    let (cost_basis, gain) = if kind == "acquisition" {
+                        token_lots.acquire(leg.amount, value, method);
+                        (value, None)
+                    } else {
+                        let (matched, cost) = token_lots.consume(leg.amount, method);
+                        // A gain is only realized by trades, and only when every unit is matched
+                        let gain = match (event.category, value, cost) {
+                            (Category::Trade, Some(value), Some(cost))
+                                if matched >= leg.amount * (1.0 - 1e-9) =>
+                            {
+                                Some(value - cost)
+                            }
+                            _ => None,
+                        };
+                        (cost.filter(|_| matched > 0.0), gain)
+                    };
+
+                    writer.write_record([
+                        date.clone(),
+                        event.time.to_string(),
+                        kind.to_string(),
+                        event.category.name().to_string(),
+                        leg.token.clone(),
+                        currency(symbols, &leg.token),
+                        leg.amount.to_string(),
+                        number(
+                            value
+                                .filter(|_| leg.amount > 0.0)
+                                .map(|value| value / leg.amount),
+                        ),
+                        usd(value),
+                        usd(cost_basis),
+                        usd(gain),
+                        value.is_none().to_string(),
+                        counter
+                            .as_ref()
+                            .map(|counter| currency(symbols, &counter.token))
+                            .unwrap_or_default(),
+                        number(counter.as_ref().map(|counter| counter.amount)),
+                        event.counterparty.clone().unwrap_or_default(),
+                        event.trans_id.clone().unwrap_or_default(),
+                    ])?;
+                }
+            }
+            TaxFormat::Koinly => {
+                let label = match event.category {
+                    Category::StakingReward => "reward",
+                    _ => "",
+                };
+                let mut description = event.category.name().to_string();
+                if event.category == Category::StakingReward {
+                    if let Some(epoch) = &event.trans_id {
+                        description = format!("{} {}", description, epoch);
+                    }
+                }
+                if value.is_none() {
+                    description.push_str(" (price unknown)");
+                }
+                writer.write_record([
+                    date,
+                    number(event.sent.as_ref().map(|leg| leg.amount)),
+                    event
+                        .sent
+                        .as_ref()
+                        .map(|leg| currency(symbols, &leg.token))
+                        .unwrap_or_default(),
+                    number(event.received.as_ref().map(|leg| leg.amount)),
+                    event
+                        .received
+                        .as_ref()
+                        .map(|leg| currency(symbols, &leg.token))
+                        .unwrap_or_default(),
+                    String::new(),
+                    String::new(),
+                    usd(value),
+                    if value.is_some() {
+                        "USD".to_string()
+                    } else {
+                        String::new()
+                    },
+                    label.to_string(),
+                    description,
+                    event
+                        .trans_id
+                        .clone()
+                        .filter(|_| event.category != Category::StakingReward)
+                        .unwrap_or_default(),
+                ])?;
+            }
+            TaxFormat::CoinTracker => {
+                let tag = match event.category {
+                    Category::StakingReward => "staked",
+                    _ => "",
+                };
+                // CoinTracker expects MM/DD/YYYY HH:MM:SS
+                let date = DateTime::from_timestamp(event.time, 0)
+                    .map(|date| date.format("%m/%d/%Y %H:%M:%S").to_string())
+                    .unwrap_or_default();
+                writer.write_record([
+                    date,
+                    number(event.received.as_ref().map(|leg| leg.amount)),
+                    event
+                        .received
+                        .as_ref()
+                        .map(|leg| currency(symbols, &leg.token))
+                        .unwrap_or_default(),
+                    number(event.sent.as_ref().map(|leg| leg.amount)),
+                    event
+                        .sent
+                        .as_ref()
+                        .map(|leg| currency(symbols, &leg.token))
+                        .unwrap_or_default(),
+                    String::new(),
+                    String::new(),
+                    tag.to_string(),
+                ])?;
+            }
+        }
+    }
+
+    let csv = writer
+        .into_inner()
+        .map_err(|e| csv::Error::from(e.into_error()))?;
+    Ok((String::from_utf8_lossy(&csv).into_owned(), summary))
+}
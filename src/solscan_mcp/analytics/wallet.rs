@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+
+use rmcp::Error as McpError;
+use serde_json::{json, Map, Value};
+
+use super::{f64_field, i64_field, items, round, str_field, usd_value};
+use crate::solscan_mcp::enrich::SOL_DECIMALS;
+
+/// Responses gathered for a wallet profile; each section may have failed on its own
+pub struct ProfileInputs {
+    pub detail: Result<Value, McpError>,
+    pub portfolio: Result<Value, McpError>,
+    /// Latest transfers, newest first
+    pub transfers: Result<Value, McpError>,
+    /// Earliest transfers, oldest first
+    pub first_transfers: Result<Value, McpError>,
+    pub defi_activities: Result<Value, McpError>,
+    pub stake: Result<Value, McpError>,
+}
+
+/// Summarize a wallet from its detail, portfolio, transfers, DeFi activities and stake accounts.
+///
+/// Sections whose request failed are reported under `errors` instead of failing the profile.
+pub fn profile(address: &str, inputs: ProfileInputs, top_n: usize) -> Value {
+    let mut errors = Map::new();
+    let mut section = |name: &str, response: Result<Value, McpError>| match response {
+        Ok(response) => Some(response),
+        Err(e) => {
+            errors.insert(name.to_string(), json!(e.message));
+            None
+        }
+    };
+
+    let detail = section("account_detail", inputs.detail);
+    let portfolio = section("account_portfolio", inputs.portfolio);
+    let transfers = section("account_transfer", inputs.transfers);
+    let first_transfers = section("account_transfer_earliest", inputs.first_transfers);
+    let defi_activities = section("account_defi_activities", inputs.defi_activities);
+    let stake = section("account_stake", inputs.stake);
+
+    let mut summary = json!({ "address": address });
+
+    if let Some(detail) = &detail {
+        let data = &detail["data"];
+        summary["account"] = json!({
+            "type": data.get("type"),
+            "owner_program": data.get("owner_program"),
+            "executable": data.get("executable"),
+        });
+        if let Some(lamports) = f64_field(data, "lamports") {
+            summary["sol_balance"] = json!(lamports / 10f64.powi(SOL_DECIMALS as i32));
+        }
+    }
+
+    if let Some(portfolio) = &portfolio {
+        summary["portfolio"] = holdings(&portfolio["data"], top_n);
+    }
+
+    let transfers = transfers.as_ref().map(items).unwrap_or_default();
+    let first_transfers = first_transfers.as_ref().map(items).unwrap_or_default();
+    let defi_activities = defi_activities.as_ref().map(items).unwrap_or_default();
+
+    summary["first_activity"] = activity_bound(
+        first_transfers
+            .iter()
+            .chain(transfers)
+            .chain(defi_activities),
+        Bound::First,
+    );
+    summary["last_activity"] = activity_bound(
+        transfers
+            .iter()
+            .chain(defi_activities)
+            .chain(first_transfers),
+        Bound::Last,
+    );
+    summary["transfers"] = transfer_summary(transfers, top_n);
+    summary["defi"] = defi_summary(defi_activities);
+
+    if let Some(stake) = &stake {
+        summary["stake"] = stake_summary(items(stake));
+    }
+
+    if !errors.is_empty() {
+        summary["errors"] = Value::Object(errors);
+    }
+
+    json!({
+        "success": true,
+        "data": summary,
+    })
+}
+
+/// Total value, SOL value and the largest token holdings by USD value
+fn holdings(portfolio: &Value, top_n: usize) -> Value {
+    let tokens = portfolio
+        .get("tokens")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+
+    let mut top: Vec<&Value> = tokens.iter().collect();
+    top.sort_by(|a, b| {
+        let a = f64_field(a, "value").unwrap_or_default();
+        let b = f64_field(b, "value").unwrap_or_default();
+        b.total_cmp(&a)
+    });
+
+    let top: Vec<Value> = top
+        .into_iter()
+        .take(top_n)
+        .map(|token| {
+            json!({
+                "token_address": token.get("token_address"),
+                "symbol": token.get("token_symbol"),
+                "balance": token.get("balance"),
+                "price": token.get("token_price"),
+                "value_usd": token.get("value"),
+            })
+        })
+        .collect();
+
+    json!({
+        "total_value_usd": portfolio.get("total_value"),
+        "sol_value_usd": portfolio.pointer("/native_balance/value"),
+        "token_count": tokens.len(),
+        "top_holdings": top,
+    })
+}
+
+enum Bound {
+    First,
+    Last,
+}
+
+/// Earliest or latest item by `block_time`, with the transaction it comes from
+fn activity_bound<'a>(items: impl Iterator<Item = &'a Value>, bound: Bound) -> Value {
+    let timed = items.filter_map(|item| Some((i64_field(item, "block_time")?, item)));
+    let found = match bound {
+        Bound::First => timed.min_by_key(|(time, _)| *time),
+        Bound::Last => timed.max_by_key(|(time, _)| *time),
+    };
+
+    match found {
+        Some((time, item)) => json!({
+            "block_time": time,
+            "trans_id": item.get("trans_id"),
+            "activity_type": item.get("activity_type"),
+        }),
+        None => Value::Null,
+    }
+}
+
+#[derive(Default)]
+struct Counterparty {
+    transfers: usize,
+    inbound: usize,
+    outbound: usize,
+    volume_usd: f64,
+}
+
+/// Inbound/outbound counts and USD totals, and the most frequent counterparties
+fn transfer_summary(transfers: &[Value], top_n: usize) -> Value {
+    let mut inbound = 0;
+    let mut outbound = 0;
+    let mut inbound_usd = 0.0;
+    let mut outbound_usd = 0.0;
+    let mut counterparties: HashMap<&str, Counterparty> = HashMap::new();
+
+    for transfer in transfers {
+        let usd = usd_value(transfer).unwrap_or_default();
+        let (counterparty, is_inbound) = match str_field(transfer, "flow") {
+            Some("in") => (str_field(transfer, "from_address"), true),
+            Some("out") => (str_field(transfer, "to_address"), false),
+            _ => continue,
+        };
+
+        if is_inbound {
+            inbound += 1;
+            inbound_usd += usd;
+        } else {
+            outbound += 1;
+            outbound_usd += usd;
+        }
+
+        if let Some(counterparty) = counterparty {
+            let entry = counterparties.entry(counterparty).or_default();
+            entry.transfers += 1;
+            entry.volume_usd += usd;
+            if is_inbound {
+                entry.inbound += 1;
+            } else {
+                entry.outbound += 1;
+            }
+        }
+    }
+
+    let mut top: Vec<(&str, Counterparty)> = counterparties.into_iter().collect();
+    top.sort_by(|(a_address, a), (b_address, b)| {
+        b.transfers
+            .cmp(&a.transfers)
+            .then(b.volume_usd.total_cmp(&a.volume_usd))
+            .then(a_address.cmp(b_address))
+    });
+
+    let top: Vec<Value> = top
+        .into_iter()
+        .take(top_n)
+        .map(|(address, counterparty)| {
+            json!({
+                "address": address,
+                "transfers": counterparty.transfers,
+                "inbound": counterparty.inbound,
+                "outbound": counterparty.outbound,
+                "volume_usd": round(counterparty.volume_usd, 2),
+            })
+        })
+        .collect();
+
+    json!({
+        "sampled": transfers.len(),
+        "inbound": inbound,
+        "outbound": outbound,
+        "inbound_usd": round(inbound_usd, 2),
+        "outbound_usd": round(outbound_usd, 2),
+        "top_counterparties": top,
+    })
+}
+
+/// DeFi platforms and activity types used, by number of activities
+fn defi_summary(activities: &[Value]) -> Value {
+    let mut platforms: HashMap<&str, usize> = HashMap::new();
+    let mut activity_types: HashMap<&str, usize> = HashMap::new();
+
+    for activity in activities {
+        // `platform` is a list of program ids on most endpoints and a single id on some
+        match activity.get("platform") {
+            Some(Value::Array(ids)) => ids
+                .iter()
+                .filter_map(Value::as_str)
+                .for_each(|id| *platforms.entry(id).or_default() += 1),
+            Some(Value::String(id)) => *platforms.entry(id).or_default() += 1,
+            _ => {}
+        }
+
+        if let Some(activity_type) = str_field(activity, "activity_type") {
+            *activity_types.entry(activity_type).or_default() += 1;
+        }
+    }
+
+    json!({
+        "sampled": activities.len(),
+        "platforms": ranked(platforms, "platform"),
+        "activity_types": ranked(activity_types, "activity_type"),
+    })
+}
+
+fn ranked(counts: HashMap<&str, usize>, key: &str) -> Vec<Value> {
+    let mut counts: Vec<(&str, usize)> = counts.into_iter().collect();
+    counts.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then(a_name.cmp(b_name)));
+
+    counts
+        .into_iter()
+        .map(|(name, count)| json!({ key: name, "activities": count }))
+        .collect()
+}
+
+/// Stake totals in SOL, by status and by validator
+fn stake_summary(accounts: &[Value]) -> Value {
+    let sol = |lamports: f64| lamports / 10f64.powi(SOL_DECIMALS as i32);
+
+    let mut total = 0.0;
+    let mut active = 0.0;
+    let mut rewards = 0.0;
+    let mut validators: HashMap<&str, f64> = HashMap::new();
+
+    for account in accounts {
+        let amount = f64_field(account, "amount").unwrap_or_default();
+        total += amount;
+        active += f64_field(account, "active_stake_amount").unwrap_or_default();
+        rewards += f64_field(account, "total_reward").unwrap_or_default();
+
+        if let Some(voter) = str_field(account, "voter") {
+            *validators.entry(voter).or_default() += amount;
+        }
+    }
+
+    let mut validators: Vec<(&str, f64)> = validators.into_iter().collect();
+    validators.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+    json!({
+        "stake_accounts": accounts.len(),
+        "total_sol": sol(total),
+        "active_sol": sol(active),
+        "total_rewards_sol": sol(rewards),
+        "validators": validators
+            .into_iter()
+            .map(|(voter, amount)| json!({ "voter": voter, "stake_sol": sol(amount) }))
+            .collect::<Vec<_>>(),
+    })
+}
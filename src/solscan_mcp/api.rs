@@ -1,55 +1,307 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
-use rmcp::{model::*, schemars, tool, Error as McpError, ServerHandler};
+use rmcp::{
+    handler::server::tool::ToolCallContext, model::*, schemars, service::RequestContext, tool,
+    Error as McpError, RoleServer, ServerHandler,
+};
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
+use serde_json::{json, Map, Value};
+use tokio::io::AsyncWriteExt;
 use tokio::sync::Mutex;
 
+use crate::solscan_mcp::analytics::cluster::{self, ClusterRules, WalletTransfers};
+use crate::solscan_mcp::analytics::counterparties::{self, SortBy};
+use crate::solscan_mcp::analytics::explain::Transaction;
+use crate::solscan_mcp::analytics::exports::{
+    self, ExportError, GroupBy, RewardGroups, TransferFilter, TransferGroups,
+};
+use crate::solscan_mcp::analytics::flow::{self, Direction, FlowGraph, Trace, TraceLimits};
+use crate::solscan_mcp::analytics::graph::{self, GraphFormat, GraphLabels};
+use crate::solscan_mcp::analytics::holders::{self, Bucket};
+use crate::solscan_mcp::analytics::liquidity::{self, Pool, RankBy};
+use crate::solscan_mcp::analytics::pnl::{self, CostMethod, Period, Valuation};
+use crate::solscan_mcp::analytics::poisoning::{self, PoisoningRules};
+use crate::solscan_mcp::analytics::portfolio;
+use crate::solscan_mcp::analytics::prices::{self, PriceHistory};
+use crate::solscan_mcp::analytics::risk::{self, RiskInputs};
+use crate::solscan_mcp::analytics::sandwich::{self, Sandwich};
+use crate::solscan_mcp::analytics::series;
+use crate::solscan_mcp::analytics::staking;
+use crate::solscan_mcp::analytics::tax::{self, TaxFormat};
+use crate::solscan_mcp::analytics::wallet::{self, ProfileInputs};
+use crate::solscan_mcp::analytics::{i64_field, items, round, str_field, Pages};
+use crate::solscan_mcp::cache::ResponseCache;
+use crate::solscan_mcp::config::Config;
+use crate::solscan_mcp::enrich::{self, TokenMeta, TokenMetaCache, SOL_ADDRESS};
+use crate::solscan_mcp::labels::{self, Label, LabelStore, SolscanLabel};
+use crate::solscan_mcp::output::OutputOptions;
+use crate::solscan_mcp::policy::{
+    ToolGroup, ToolPolicy, DEFAULT_STAKE_ACCOUNTS, MAX_STAKE_ACCOUNTS,
+};
+use crate::solscan_mcp::rate_limit::RateLimiter;
 use crate::solscan_mcp::requests::account::*;
 use crate::solscan_mcp::requests::block::*;
+use crate::solscan_mcp::requests::label::*;
 use crate::solscan_mcp::requests::market::*;
 use crate::solscan_mcp::requests::nft::*;
 use crate::solscan_mcp::requests::token::*;
 use crate::solscan_mcp::requests::transaction::*;
 
-// Base URLs for Solscan API
-const SOLSCAN_API_BASE_URL: &str = "https://pro-api.solscan.io/v2.0";
-const SOLSCAN_PUBLIC_API_BASE_URL: &str = "https://public-api.solscan.io";
-// const WSOL_ADDRESS: &str = "So11111111111111111111111111111111111111112";
+// Endpoints that reject a `page_size` above 40; the configured default is capped to fit
+const MAX_PAGE_SIZES: &[(&str, i32)] = &[
+    ("/token/holders", 40),
+    ("/account/token-accounts", 40),
+    ("/account/stake", 40),
+];
+
+// Number of entries in the ranked lists of composite tools when the caller does not say
+const DEFAULT_TOP_N: usize = 5;
+
+// Default and maximum bounds of `trace_funds`
+const DEFAULT_TRACE_HOPS: usize = 2;
+const MAX_TRACE_HOPS: usize = 5;
+const DEFAULT_TRACE_NODES: usize = 50;
+const MAX_TRACE_NODES: usize = 500;
+const DEFAULT_TRACE_REQUESTS: usize = 20;
+const MAX_TRACE_REQUESTS: usize = 100;
+
+// Bounds of `transfer_graph`: addresses per graph and pages of 100 transfers per address
+const MAX_GRAPH_ADDRESSES: usize = 20;
+const DEFAULT_GRAPH_PAGES: usize = 1;
+const MAX_GRAPH_PAGES: usize = 10;
+// Busiest nodes of a `transfer_graph` whose Solscan account metadata is looked up
+const DEFAULT_GRAPH_LABEL_LOOKUPS: usize = 10;
+const MAX_GRAPH_LABEL_LOOKUPS: usize = 50;
+
+// Bounds of `account_counterparties`: pages of 100 transfers and counterparties returned
+const DEFAULT_COUNTERPARTY_PAGES: usize = 5;
+const MAX_COUNTERPARTY_PAGES: usize = 20;
+const DEFAULT_COUNTERPARTY_LIMIT: usize = 20;
+
+// Bounds of `cluster_wallets`; each wallet costs two requests
+const MAX_CLUSTER_ADDRESSES: usize = 30;
+const DEFAULT_CLUSTER_TIME_WINDOW: i64 = 600;
+const DEFAULT_SHARED_COUNTERPARTIES: usize = 2;
+const DEFAULT_HUB_DEGREE: usize = 10;
+
+// Defaults of `detect_address_poisoning`: pages of 100 transfers per direction, the USD
+// value below which a transfer is dust and the characters a lookalike must share
+const DEFAULT_POISONING_PAGES: usize = 3;
+const MAX_POISONING_PAGES: usize = 10;
+const DEFAULT_DUST_USD: f64 = 0.01;
+const DEFAULT_LOOKALIKE_MATCH: usize = 6;
+
+// Bounds of `detect_sandwiches`: swaps checked, block transactions looked at on each side
+// of a swap, pages of 100 block transactions, signers checked per swap and Solscan calls
+const DEFAULT_SANDWICH_SWAPS: usize = 10;
+const MAX_SANDWICH_SWAPS: usize = 50;
+const DEFAULT_SANDWICH_WINDOW: usize = 5;
+const MAX_SANDWICH_WINDOW: usize = 20;
+const DEFAULT_BLOCK_PAGES: usize = 5;
+const MAX_BLOCK_PAGES: usize = 20;
+const MAX_SANDWICH_CANDIDATES: usize = 3;
+const DEFAULT_SANDWICH_REQUESTS: usize = 60;
+const MAX_SANDWICH_REQUESTS: usize = 300;
+
+// Bounds of `wallet_pnl` and `tax_lot_export`: pages of 100 swaps, transfers or balance
+// changes, tokens priced
+const DEFAULT_PNL_PAGES: usize = 5;
+const MAX_PNL_PAGES: usize = 20;
+const MAX_PRICED_TOKENS: usize = 25;
+
+// Bounds of `account_portfolio_history`: days valued and pages of 100 balance changes
+const DEFAULT_HISTORY_DAYS: i64 = 30;
+const MAX_HISTORY_DAYS: i64 = 366;
+const DEFAULT_HISTORY_PAGES: usize = 5;
+const MAX_HISTORY_PAGES: usize = 20;
+
+// Bounds of `staking_summary`: pages of 40 stake accounts and rewarded epochs averaged into
+// the APY; the accounts whose rewards are read are bounded in `policy`, which prices them
+const MAX_STAKE_PAGES: usize = 5;
+const DEFAULT_APY_EPOCHS: usize = 10;
+const MAX_APY_EPOCHS: usize = 100;
+
+// Bounds of `token_holder_stats`: holders read in pages of 40 and pages of 100 markets whose
+// pools are left out
+const DEFAULT_HOLDER_LIMIT: usize = 400;
+const MAX_HOLDER_LIMIT: usize = 2000;
+const HOLDER_PAGE_SIZE: usize = 40;
+const MAX_MARKET_PAGES: usize = 3;
+
+// Local label categories of pool and program accounts left out of holder statistics
+const EXCLUDED_HOLDER_CATEGORIES: [&str; 5] = ["lp", "pool", "amm", "dex", "program"];
+
+// Default holder size buckets of `token_holder_stats`, as shares of the supply
+const DEFAULT_BUCKET_SHARES: [f64; 4] = [0.00001, 0.0001, 0.001, 0.01];
+
+// Bounds of `token_risk_report`: largest holders read and pages of 100 creator transfers
+const RISK_HOLDER_LIMIT: usize = 100;
+const DEFAULT_CREATOR_PAGES: usize = 3;
+const MAX_CREATOR_PAGES: usize = 10;
+
+// Bounds of `token_liquidity`: pages of 100 markets, pools looked up with `market_info` and
+// `market_volume`, and the trade size priced when the caller does not say
+const MAX_LIQUIDITY_MARKET_PAGES: usize = 10;
+const DEFAULT_LIQUIDITY_POOLS: usize = 20;
+const MAX_LIQUIDITY_POOLS: usize = 50;
+const DEFAULT_TRADE_SIZE_USD: f64 = 1_000.0;
+
+// Bounds of `token_price_series`: tokens per call, days per price request, price requests
+// per token group and the days read when the caller gives no start
+const MAX_SERIES_TOKENS: usize = 20;
+const PRICE_CHUNK_DAYS: i64 = 30;
+const MAX_PRICE_CHUNKS: usize = 40;
+const DEFAULT_SERIES_DAYS: i64 = 90;
 
 #[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
 pub struct ChainInfoRequest {
     // No parameters
 }
 
+/// Body of an export endpoint, in memory or downloaded to a file when too large. The
+/// download is only kept while the export is read and removed when this is dropped.
+enum Export {
+    Text(String),
+    File { path: PathBuf },
+}
+
+impl Export {
+    /// Read the export back, from the downloaded file when it was streamed to disk
+    fn reader(&self) -> Result<Box<dyn std::io::Read + '_>, McpError> {
+        match self {
+            Self::Text(text) => Ok(Box::new(text.as_bytes())),
+            Self::File { path } => std::fs::File::open(path)
+                .map(|file| Box::new(std::io::BufReader::new(file)) as Box<dyn std::io::Read>)
+                .map_err(|e| McpError::internal_error(format!("Export file error: {}", e), None)),
+        }
+    }
+}
+
+impl Drop for Export {
+    fn drop(&mut self) {
+        if let Self::File { path } = self {
+            if let Err(e) = std::fs::remove_file(&*path) {
+                tracing::warn!("Failed to remove export download {}: {}", path.display(), e);
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct SolscanApi {
     api_key: Arc<Mutex<String>>,
     client: reqwest::Client,
+    config: Arc<Config>,
+    cache: Arc<ResponseCache>,
+    rate_limiter: Arc<RateLimiter>,
+    policy: Arc<ToolPolicy>,
+    token_metas: Arc<TokenMetaCache>,
+    labels: Arc<LabelStore>,
+    /// Whether the tool call being served may use credit-expensive endpoints
+    confirmed: bool,
 }
 
 #[tool(tool_box)]
 impl SolscanApi {
-    pub fn new(api_key: String) -> Self {
-        Self {
-            api_key: Arc::new(Mutex::new(api_key)),
-            client: reqwest::Client::new(),
-        }
+    pub fn new(config: Config, labels: LabelStore) -> Result<Self, reqwest::Error> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.api.timeout_secs))
+            .build()?;
+
+        Ok(Self {
+            api_key: Arc::new(Mutex::new(config.api_key().to_string())),
+            client,
+            cache: Arc::new(ResponseCache::new(&config.cache)),
+            rate_limiter: Arc::new(RateLimiter::new(config.rate_limit.requests_per_second)),
+            policy: Arc::new(ToolPolicy::new(&config)),
+            token_metas: Arc::new(TokenMetaCache::new(Duration::from_secs(
+                config.cache.token_meta_ttl_secs,
+            ))),
+            labels: Arc::new(labels),
+            config: Arc::new(config),
+            confirmed: false,
+        })
+    }
+
+    /// The `page_size` sent to `endpoint`: the caller's value, or the configured default
+    /// capped at the largest size the endpoint accepts
+    fn page_size(&self, endpoint: &str, requested: Option<i32>) -> Option<i32> {
+        requested.or_else(|| {
+            let default = self.config.defaults.page_size?;
+            let max = MAX_PAGE_SIZES
+                .iter()
+                .find(|(name, _)| *name == endpoint)
+                .map_or(default, |(_, max)| *max);
+            Some(default.min(max))
+        })
     }
 
     async fn make_request(&self, endpoint: &str, params: Option<Value>) -> Result<Value, McpError> {
-        let url = format!("{}{}", SOLSCAN_API_BASE_URL, endpoint);
+        let url = format!("{}{}", self.config.api.base_url, endpoint);
+        self.send_request(&url, params).await
+    }
+
+    // Make a request to the Solscan Public API
+    async fn make_public_request(&self, endpoint: &str) -> Result<Value, McpError> {
+        let url = format!("{}{}", self.config.api.public_base_url, endpoint);
+        self.send_request(&url, None).await
+    }
+
+    // Shared HTTP path: cache lookup and JSON parsing on top of `get`
+    async fn send_request(&self, url: &str, params: Option<Value>) -> Result<Value, McpError> {
+        let cache_key = ResponseCache::key(url, params.as_ref());
+        if let Some(cached) = self.cache.get(&cache_key).await {
+            return Ok(cached);
+        }
+
+        let response = self.get(url, params).await?;
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| McpError::internal_error(format!("HTTP response error: {}", e), None))?;
+
+        // Files like the CSV exports are not JSON; say what came back instead of failing on
+        // its first character
+        let value = serde_json::from_slice::<Value>(&body).map_err(|e| {
+            if content_type.is_empty() || content_type.contains("json") {
+                McpError::internal_error(format!("JSON parsing error: {}", e), None)
+            } else {
+                McpError::internal_error(
+                    format!("Expected a JSON response, got {}", content_type),
+                    Some(json!({ "body": String::from_utf8_lossy(&body[..body.len().min(200)]) })),
+                )
+            }
+        })?;
+
+        self.cache.insert(cache_key, value.clone()).await;
+
+        Ok(value)
+    }
+
+    // GET with the API key, rate limiting and error mapping; the body is left to the caller
+    async fn get(&self, url: &str, params: Option<Value>) -> Result<reqwest::Response, McpError> {
         let api_key = self.api_key.lock().await.clone();
 
-        let request = self.client.get(&url).header("token", api_key);
+        let request = self.client.get(url).header("token", api_key);
 
         // Add query parameters if provided
         let request = if let Some(params) = params {
-            request.query(&params)
+            request.query(&query_pairs(&params))
         } else {
             request
         };
 
+        self.rate_limiter.acquire().await;
+
         let response = request
             .send()
             .await
@@ -67,40 +319,379 @@ impl SolscanApi {
             ));
         }
 
-        response
-            .json::<Value>()
-            .await
-            .map_err(|e| McpError::internal_error(format!("JSON parsing error: {}", e), None))
+        Ok(response)
     }
 
-    // Make a request to the Solscan Public API
-    async fn make_public_request(&self, endpoint: &str) -> Result<Value, McpError> {
-        let url = format!("{}{}", SOLSCAN_PUBLIC_API_BASE_URL, endpoint);
-        let api_key = self.api_key.lock().await.clone();
+    // Token metadata (decimals, price) for a set of tokens, served from the local cache when
+    // possible. Lookup failures are logged and leave the affected tokens out.
+    async fn token_meta_map(&self, addresses: &BTreeSet<String>) -> HashMap<String, TokenMeta> {
+        let (mut metas, missing) = self.token_metas.lookup(addresses).await;
+
+        let mut fetched = HashMap::new();
+        for chunk in missing.chunks(20) {
+            let params = json!({ "address": chunk });
+            match self.make_request("/token/meta/multi", Some(params)).await {
+                Ok(response) => {
+                    let items = response["data"].as_array().cloned().unwrap_or_default();
+                    for item in items {
+                        if let Some(address) = item.get("address").and_then(Value::as_str) {
+                            fetched.insert(address.to_string(), TokenMeta::from_value(&item));
+                        }
+                    }
+                }
+                Err(e) => tracing::warn!("Failed to fetch token metadata: {}", e.message),
+            }
+        }
+
+        self.token_metas.insert(&fetched).await;
+        metas.extend(fetched);
+        metas
+    }
+
+    // Symbols of a set of tokens, keyed by the given addresses; native SOL is `SOL`
+    async fn token_symbols(&self, tokens: &BTreeSet<String>) -> HashMap<String, String> {
+        let lookup = tokens
+            .iter()
+            .map(|token| enrich::price_address(token).to_string())
+            .collect();
+        let metas = self.token_meta_map(&lookup).await;
+
+        tokens
+            .iter()
+            .filter_map(|token| {
+                let symbol = match token.as_str() {
+                    SOL_ADDRESS => "SOL".to_string(),
+                    token => metas.get(token)?.symbol.clone()?,
+                };
+                Some((token.clone(), symbol))
+            })
+            .collect()
+    }
 
-        let request = self.client.get(&url).header("token", api_key);
+    // GET an export endpoint. Every call of a credit-expensive endpoint goes through here and
+    // is refused unless the tool call was confirmed, whichever tool makes it.
+    async fn get_export(
+        &self,
+        endpoint: &str,
+        params: Value,
+    ) -> Result<reqwest::Response, McpError> {
+        if let Some(credits) = self.policy.endpoint_cost(endpoint) {
+            if !self.confirmed {
+                return Err(McpError::invalid_params(
+                    format!(
+                        "{} is credit-expensive (about {} credits per call); call the tool \
+                         again with \"confirm\": true to use it",
+                        endpoint, credits
+                    ),
+                    None,
+                ));
+            }
+        }
+
+        let url = format!("{}{}", self.config.api.base_url, endpoint);
+        self.get(&url, Some(params)).await
+    }
 
-        let response = request
-            .send()
+    // Download an export: kept in memory up to `[exports] max_inline_bytes`, streamed to a
+    // temporary file named after `name` in the exports directory past that or when `to_file`
+    // is set
+    async fn download_export(
+        &self,
+        endpoint: &str,
+        params: Value,
+        name: &str,
+        to_file: bool,
+    ) -> Result<Export, McpError> {
+        let mut response = self.get_export(endpoint, params).await?;
+        let max_inline = self.config.exports.max_inline_bytes;
+        let read_error = |e: reqwest::Error| {
+            McpError::internal_error(format!("HTTP response error: {}", e), None)
+        };
+        let write_error =
+            |e: std::io::Error| McpError::internal_error(format!("Export file error: {}", e), None);
+
+        let mut buffer = Vec::new();
+        let mut file: Option<(PathBuf, tokio::fs::File)> = None;
+        loop {
+            let chunk = response.chunk().await.map_err(read_error)?;
+            let overflow = chunk
+                .as_ref()
+                .is_some_and(|chunk| buffer.len() + chunk.len() > max_inline);
+            if file.is_none() && (to_file || overflow) {
+                let dir = self.config.exports.dir();
+                tokio::fs::create_dir_all(&dir).await.map_err(write_error)?;
+                let path = dir.join(export_file_name(&format!("{}_download", name)));
+                let mut created = tokio::fs::File::create(&path).await.map_err(write_error)?;
+                created.write_all(&buffer).await.map_err(write_error)?;
+                buffer.clear();
+                file = Some((path, created));
+            }
+
+            let Some(chunk) = chunk else {
+                break;
+            };
+            match &mut file {
+                Some((_, file)) => file.write_all(&chunk).await.map_err(write_error)?,
+                None => buffer.extend_from_slice(&chunk),
+            }
+        }
+
+        match file {
+            Some((path, mut file)) => {
+                // Wrapped first so the download is removed even if the flush fails
+                let export = Export::File { path };
+                file.flush().await.map_err(write_error)?;
+                Ok(export)
+            }
+            None => Ok(Export::Text(String::from_utf8_lossy(&buffer).into_owned())),
+        }
+    }
+
+    // Path for the parsed rows of an export named after `name`, in the exports directory.
+    // Export files there older than `[exports] retention_hours` are removed first, so saved
+    // exports do not pile up.
+    async fn export_output(&self, name: &str) -> Result<PathBuf, McpError> {
+        let dir = self.config.exports.dir();
+        tokio::fs::create_dir_all(&dir)
             .await
-            .map_err(|e| McpError::internal_error(format!("HTTP request error: {}", e), None))?;
+            .map_err(|e| McpError::internal_error(format!("Export file error: {}", e), None))?;
+
+        let retention_hours = self.config.exports.retention_hours;
+        if retention_hours > 0 {
+            let retention = Duration::from_secs(retention_hours * 3600);
+            if let Ok(mut entries) = tokio::fs::read_dir(&dir).await {
+                while let Ok(Some(entry)) = entries.next_entry().await {
+                    if !is_export_file(&entry.file_name().to_string_lossy()) {
+                        continue;
+                    }
+                    let expired = entry
+                        .metadata()
+                        .await
+                        .ok()
+                        .and_then(|metadata| metadata.modified().ok())
+                        .and_then(|modified| modified.elapsed().ok())
+                        .is_some_and(|age| age > retention);
+                    if expired {
+                        if let Err(e) = tokio::fs::remove_file(entry.path()).await {
+                            tracing::warn!("Failed to remove old export: {}", e);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(dir.join(export_file_name(name)))
+    }
 
-        if response.status() != reqwest::StatusCode::OK {
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
+    // Daily USD prices of each token over its `(from_time, to_time)` range, read in
+    // `PRICE_CHUNK_DAYS` requests; at most the latest `MAX_PRICE_CHUNKS` of them per token.
+    // Ranges that could not be fetched are returned as errors per token.
+    async fn price_history(
+        &self,
+        ranges: &BTreeMap<String, (i64, i64)>,
+    ) -> (PriceHistory, Vec<(String, String)>) {
+        let mut errors = Vec::new();
+        let mut lookups = tokio::task::JoinSet::new();
+        for (token, (from_time, to_time)) in ranges {
+            let chunks = series::chunks(*from_time, *to_time, PRICE_CHUNK_DAYS);
+            let skipped = chunks.len().saturating_sub(MAX_PRICE_CHUNKS);
+            if let Some((_, before)) = chunks.get(skipped) {
+                if skipped > 0 {
+                    errors.push((
+                        token.clone(),
+                        format!("range too long, no prices before {}", before),
+                    ));
+                }
+            }
+
+            for (from, to) in chunks.into_iter().skip(skipped) {
+                let api = self.clone();
+                let token = token.clone();
+                let range = [prices::date_key(from), prices::date_key(to)];
+                let params = json!({
+                    "address": enrich::price_address(&token),
+                    "time": range,
+                });
+                lookups.spawn(async move {
+                    let response = api.make_request("/token/price", Some(params)).await;
+                    (token, range, response)
+                });
+            }
+        }
+
+        let mut history = PriceHistory::default();
+        while let Some(lookup) = lookups.join_next().await {
+            match lookup {
+                Ok((token, _, Ok(response))) => {
+                    history.insert(&token, prices::price_points(&response))
+                }
+                Ok((token, [from, to], Err(e))) => errors.push((
+                    token,
+                    format!(
+                        "no prices from {} to {}: {}",
+                        from.unwrap_or_default(),
+                        to.unwrap_or_default(),
+                        e.message
+                    ),
+                )),
+                Err(e) => tracing::warn!("Token price lookup failed: {}", e),
+            }
+        }
+
+        (history, errors)
+    }
 
-            return Err(McpError::internal_error(
-                format!("HTTP {}", error_text),
-                None,
-            ));
+    // Add `ui_amount` / `usd_value_now` next to the raw amounts of a transfer or activity response
+    async fn enrich_amounts(&self, value: &mut Value) {
+        let metas = self.token_meta_map(&enrich::token_addresses(value)).await;
+        enrich::apply(value, &metas);
+    }
+
+    // Labels of a set of addresses, keyed by address, from the local address book and, up to
+    // the configured number of lookups per call, Solscan's account metadata
+    async fn address_labels(&self, addresses: &[String]) -> Map<String, Value> {
+        if self.config.labels.solscan_metadata {
+            self.lookup_metadata(addresses, self.config.labels.max_metadata_lookups)
+                .await;
         }
 
-        response
-            .json::<Value>()
-            .await
-            .map_err(|e| McpError::internal_error(format!("JSON parsing error: {}", e), None))
+        self.labels.annotations(addresses).await
+    }
+
+    // Fetch the Solscan account metadata of at most `limit` addresses not looked up yet, in
+    // the order given, into the label store
+    async fn lookup_metadata(&self, addresses: &[String], limit: usize) {
+        let unknown = self.labels.unknown_metadata(addresses).await;
+
+        let mut lookups = tokio::task::JoinSet::new();
+        for address in unknown.into_iter().take(limit) {
+            let api = self.clone();
+            lookups.spawn(async move {
+                let params = json!({ "address": address });
+                let response = api.make_request("/account/metadata", Some(params)).await;
+                (address, response)
+            });
+        }
+
+        while let Some(lookup) = lookups.join_next().await {
+            match lookup {
+                Ok((address, Ok(response))) => {
+                    let label = SolscanLabel::from_value(&response["data"]);
+                    self.labels.insert_metadata(&address, label).await;
+                }
+                // Failed lookups are not remembered, so the next result retries them
+                Ok((address, Err(e))) => {
+                    tracing::warn!("Failed to fetch metadata of {}: {}", address, e.message);
+                }
+                Err(e) => tracing::warn!("Account metadata lookup failed: {}", e),
+            }
+        }
+    }
+
+    // Add a `labels` object naming the addresses of a result
+    async fn annotate_labels(&self, value: &mut Value) {
+        let addresses = labels::addresses(value);
+        if addresses.is_empty() {
+            return;
+        }
+
+        let annotations = self.address_labels(&addresses).await;
+        if let (Value::Object(map), false) = (value, annotations.is_empty()) {
+            map.insert("labels".to_string(), Value::Object(annotations));
+        }
+    }
+
+    // Items of consecutive pages of a list endpoint, stopping at the first short page. Only a
+    // failure on the first page is an error; later failures keep the items fetched so far.
+    async fn fetch_pages(
+        &self,
+        endpoint: &str,
+        params: Value,
+        page_size: usize,
+        max_pages: usize,
+    ) -> Result<Pages, McpError> {
+        let mut pages = Pages::default();
+
+        for page in 1..=max_pages {
+            let mut params = params.clone();
+            params["page"] = json!(page);
+            params["page_size"] = json!(page_size);
+
+            let response = match self.make_request(endpoint, Some(params)).await {
+                Ok(response) => response,
+                Err(e) if page == 1 => return Err(e),
+                Err(e) => {
+                    tracing::warn!(
+                        "Stopped paging {} at page {}: {}",
+                        endpoint,
+                        page,
+                        e.message
+                    );
+                    return Ok(pages);
+                }
+            };
+
+            let page_items = items(&response);
+            pages.items.extend(page_items.iter().cloned());
+            if page_items.len() < page_size {
+                pages.complete = true;
+                break;
+            }
+        }
+
+        Ok(pages)
+    }
+
+    // Local labels in any of `categories`, by address
+    async fn labeled_accounts(&self, categories: &[String]) -> HashMap<String, Label> {
+        let mut labeled = HashMap::new();
+        for category in categories {
+            labeled.extend(self.labels.list(None, Some(category)).await);
+        }
+        labeled
+    }
+
+    // Apply the output options to every JSON content of a successful tool result and render it
+    async fn process_output(
+        &self,
+        tool: &str,
+        options: &OutputOptions,
+        mut result: CallToolResult,
+    ) -> Result<CallToolResult, McpError> {
+        if result.is_error == Some(true) {
+            return Ok(result);
+        }
+
+        let mut contents = Vec::with_capacity(result.content.len());
+        for content in result.content {
+            let value = content
+                .as_text()
+                .and_then(|text| serde_json::from_str::<Value>(&text.text).ok());
+
+            match value {
+                Some(mut value) => {
+                    if enrich::is_enriched(tool) {
+                        self.enrich_amounts(&mut value).await;
+                    }
+
+                    options.select(&mut value);
+
+                    if ToolGroup::of(tool) != Some(ToolGroup::Labels) {
+                        self.annotate_labels(&mut value).await;
+                    }
+
+                    // Trim last so the labels count towards the response size
+                    options.trim(tool, &mut value);
+                    labels::prune_labels(&mut value);
+
+                    contents.extend(options.render(value)?);
+                }
+                None => contents.push(content),
+            }
+        }
+
+        result.content = contents;
+        Ok(result)
     }
 
     // Chain Info endpoint
@@ -196,7 +787,7 @@ impl SolscanApi {
             params["page"] = json!(page);
         }
 
-        if let Some(page_size) = request.page_size {
+        if let Some(page_size) = self.page_size("/token/markets", request.page_size) {
             params["page_size"] = json!(page_size);
         }
 
@@ -227,7 +818,7 @@ impl SolscanApi {
             params["page"] = json!(page);
         }
 
-        if let Some(page_size) = request.page_size {
+        if let Some(page_size) = self.page_size("/token/holders", request.page_size) {
             params["page_size"] = json!(page_size);
         }
 
@@ -272,7 +863,7 @@ impl SolscanApi {
             params["page"] = json!(page);
         }
 
-        if let Some(page_size) = request.page_size {
+        if let Some(page_size) = self.page_size("/token/list", request.page_size) {
             params["page_size"] = json!(page_size);
         }
 
@@ -443,7 +1034,7 @@ impl SolscanApi {
             params["page"] = json!(page);
         }
 
-        if let Some(page_size) = request.page_size {
+        if let Some(page_size) = self.page_size("/token/transfer", request.page_size) {
             params["page_size"] = json!(page_size);
         }
 
@@ -514,7 +1105,7 @@ impl SolscanApi {
             params["page"] = json!(page);
         }
 
-        if let Some(page_size) = request.page_size {
+        if let Some(page_size) = self.page_size("/token/defi/activities", request.page_size) {
             params["page_size"] = json!(page_size);
         }
 
@@ -595,7 +1186,7 @@ impl SolscanApi {
             params["page"] = json!(page);
         }
 
-        if let Some(page_size) = request.page_size {
+        if let Some(page_size) = self.page_size("/account/transfer", request.page_size) {
             params["page_size"] = json!(page_size);
         }
 
@@ -676,7 +1267,7 @@ impl SolscanApi {
             params["page"] = json!(page);
         }
 
-        if let Some(page_size) = request.page_size {
+        if let Some(page_size) = self.page_size("/account/balance_change", request.page_size) {
             params["page_size"] = json!(page_size);
         }
 
@@ -835,7 +1426,7 @@ impl SolscanApi {
             params["page"] = json!(page);
         }
 
-        if let Some(page_size) = request.page_size {
+        if let Some(page_size) = self.page_size("/block/transactions", request.page_size) {
             params["page_size"] = json!(page_size);
         }
 
@@ -896,7 +1487,7 @@ impl SolscanApi {
             params["page"] = json!(page);
         }
 
-        if let Some(page_size) = request.page_size {
+        if let Some(page_size) = self.page_size("/market/list", request.page_size) {
             params["page_size"] = json!(page_size);
         }
 
@@ -1008,7 +1599,7 @@ impl SolscanApi {
             params["page"] = json!(page);
         }
 
-        if let Some(page_size) = request.page_size {
+        if let Some(page_size) = self.page_size("/account/defi/activities", request.page_size) {
             params["page_size"] = json!(page_size);
         }
 
@@ -1107,7 +1698,7 @@ impl SolscanApi {
             params["page"] = json!(page);
         }
 
-        if let Some(page_size) = request.page_size {
+        if let Some(page_size) = self.page_size("/account/token-accounts", request.page_size) {
             params["page_size"] = json!(page_size);
         }
 
@@ -1144,7 +1735,7 @@ impl SolscanApi {
             params["page"] = json!(page);
         }
 
-        if let Some(page_size) = request.page_size {
+        if let Some(page_size) = self.page_size("/account/stake", request.page_size) {
             params["page_size"] = json!(page_size);
         }
 
@@ -1161,11 +1752,25 @@ impl SolscanApi {
     }
 
     // Stake Rewards Export endpoint
-    #[tool(description = "Export the rewards for an account")]
+    #[tool(
+        description = "Export the rewards for an account as rows with the epoch, time, reward and post balance in SOL, optionally summed per day, week or month. Large exports are saved to a local CSV file whose path is returned"
+    )]
     async fn account_reward_export(
         &self,
         #[tool(aggr)] request: AccountRewardExportRequest,
     ) -> Result<CallToolResult, McpError> {
+        let period = match request.group_by.as_deref().map(str::parse::<GroupBy>) {
+            None => None,
+            Some(Ok(GroupBy::Period(period))) => Some(period),
+            Some(Ok(_)) => {
+                return Err(McpError::invalid_params(
+                    "rewards can only be grouped by day, week or month",
+                    None,
+                ))
+            }
+            Some(Err(e)) => return Err(McpError::invalid_params(e, None)),
+        };
+
         let mut params = json!({
             "address": request.address,
         });
@@ -1179,9 +1784,81 @@ impl SolscanApi {
             params["time_to"] = json!(time_to);
         }
 
-        let response = self
-            .make_request("/account/reward/export", Some(params))
+        let name = format!("rewards_{}", request.address);
+        let export = self
+            .download_export(
+                "/account/reward/export",
+                params,
+                &name,
+                request.save_to_file.unwrap_or(false),
+            )
             .await?;
+        // A download too large to return inline is parsed from disk, its rows saved as CSV
+        let output = match &export {
+            Export::File { .. } => Some(self.export_output(&name).await?),
+            Export::Text(_) => None,
+        };
+
+        let keep_rows = output.is_none() && period.is_none();
+        let saved = output.clone();
+        let parse = move || {
+            let failed = |e: ExportError| McpError::internal_error(e.to_string(), None);
+            let mut writer = saved
+                .map(csv::Writer::from_path)
+                .transpose()
+                .map_err(|e| failed(e.into()))?;
+            let mut groups = period.map(RewardGroups::new);
+            let (mut total_rows, mut reward_sol, mut rows) = (0, 0.0, Vec::new());
+            exports::read_reward_rows(export.reader()?, |row| {
+                total_rows += 1;
+                reward_sol += row.amount;
+                if let Some(groups) = &mut groups {
+                    groups.add(&row);
+                }
+                if let Some(writer) = &mut writer {
+                    writer.serialize(&row)?;
+                } else if keep_rows {
+                    rows.push(row);
+                }
+                Ok(())
+            })
+            .map_err(failed)?;
+            if let Some(writer) = &mut writer {
+                writer
+                    .flush()
+                    .map_err(|e| failed(csv::Error::from(e).into()))?;
+            }
+            Ok::<_, McpError>((total_rows, reward_sol, rows, groups))
+        };
+        let (total_rows, reward_sol, rows, groups) =
+            tokio::task::spawn_blocking(parse).await.map_err(|e| {
+                McpError::internal_error(format!("Export parsing failed: {}", e), None)
+            })??;
+
+        let mut data = json!({
+            "address": request.address,
+            "total_rows": total_rows,
+            "reward_sol": round(reward_sol, 9),
+        });
+        if let Some(groups) = groups {
+            data["groups"] = json!(groups.into_values());
+        }
+        match output {
+            Some(path) => {
+                let bytes = tokio::fs::metadata(&path)
+                    .await
+                    .map(|metadata| metadata.len());
+                data["path"] = json!(path);
+                data["bytes"] = json!(bytes.ok());
+            }
+            None if keep_rows => data["rows"] = json!(rows),
+            None => {}
+        }
+
+        let response = json!({
+            "success": true,
+            "data": data,
+        });
 
         let content = Content::json(response).map_err(|e| {
             McpError::internal_error(
@@ -1194,11 +1871,24 @@ impl SolscanApi {
     }
 
     // Account Transfer Export endpoint
-    #[tool(description = "Export transfer data of an account")]
+    #[tool(
+        description = "Export transfer data of an account as rows with the signature, time, activity type, sender, recipient, token, amount in token units, flow and USD value. Rows can be filtered by counterparty and minimum amount and grouped by token, counterparty, action, flow, day, week or month. Large exports are saved to a local CSV file whose path is returned"
+    )]
     async fn account_transfer_export(
         &self,
         #[tool(aggr)] request: AccountTransferExportRequest,
     ) -> Result<CallToolResult, McpError> {
+        let group_by = request
+            .group_by
+            .as_deref()
+            .map(str::parse::<GroupBy>)
+            .transpose()
+            .map_err(|e| McpError::invalid_params(e, None))?;
+        let filter = TransferFilter {
+            counterparty: request.counterparty.clone(),
+            min_amount: request.min_amount,
+        };
+
         let mut params = json!({
             "address": request.address,
         });
@@ -1244,9 +1934,86 @@ impl SolscanApi {
             params["flow"] = json!(flow);
         }
 
-        let response = self
-            .make_request("/account/transfer/export", Some(params))
+        let name = format!("transfers_{}", request.address);
+        let export = self
+            .download_export(
+                "/account/transfer/export",
+                params,
+                &name,
+                request.save_to_file.unwrap_or(false),
+            )
             .await?;
+        // A download too large to return inline is parsed from disk and the rows left by the
+        // filters are saved as CSV, so the file holds what the counts describe
+        let output = match &export {
+            Export::File { .. } => Some(self.export_output(&name).await?),
+            Export::Text(_) => None,
+        };
+
+        let keep_rows = output.is_none() && group_by.is_none();
+        let saved = output.clone();
+        let address = request.address.clone();
+        let parse = move || {
+            let failed = |e: ExportError| McpError::internal_error(e.to_string(), None);
+            let mut writer = saved
+                .map(csv::Writer::from_path)
+                .transpose()
+                .map_err(|e| failed(e.into()))?;
+            let mut groups = group_by.map(|group_by| TransferGroups::new(&address, group_by));
+            let (mut total_rows, mut matched_rows, mut rows) = (0, 0, Vec::new());
+            exports::read_transfer_rows(export.reader()?, |row| {
+                total_rows += 1;
+                if !filter.matches(&row) {
+                    return Ok(());
+                }
+                matched_rows += 1;
+                if let Some(groups) = &mut groups {
+                    groups.add(&row);
+                }
+                if let Some(writer) = &mut writer {
+                    writer.serialize(&row)?;
+                } else if keep_rows {
+                    rows.push(row);
+                }
+                Ok(())
+            })
+            .map_err(failed)?;
+            if let Some(writer) = &mut writer {
+                writer
+                    .flush()
+                    .map_err(|e| failed(csv::Error::from(e).into()))?;
+            }
+            Ok::<_, McpError>((total_rows, matched_rows, rows, groups))
+        };
+        let (total_rows, matched_rows, rows, groups) =
+            tokio::task::spawn_blocking(parse).await.map_err(|e| {
+                McpError::internal_error(format!("Export parsing failed: {}", e), None)
+            })??;
+
+        let mut data = json!({
+            "address": request.address,
+            "total_rows": total_rows,
+            "matched_rows": matched_rows,
+        });
+        if let Some(groups) = groups {
+            data["groups"] = json!(groups.into_values());
+        }
+        match output {
+            Some(path) => {
+                let bytes = tokio::fs::metadata(&path)
+                    .await
+                    .map(|metadata| metadata.len());
+                data["path"] = json!(path);
+                data["bytes"] = json!(bytes.ok());
+            }
+            None if keep_rows => data["rows"] = json!(rows),
+            None => {}
+        }
+
+        let response = json!({
+            "success": true,
+            "data": data,
+        });
 
         let content = Content::json(response).map_err(|e| {
             McpError::internal_error(
@@ -1295,7 +2062,7 @@ impl SolscanApi {
             params["page"] = json!(page);
         }
 
-        if let Some(page_size) = request.page_size {
+        if let Some(page_size) = self.page_size("/nft/news", request.page_size) {
             params["page_size"] = json!(page_size);
         }
 
@@ -1364,7 +2131,7 @@ impl SolscanApi {
             params["page"] = json!(page);
         }
 
-        if let Some(page_size) = request.page_size {
+        if let Some(page_size) = self.page_size("/nft/activities", request.page_size) {
             params["page_size"] = json!(page_size);
         }
 
@@ -1405,7 +2172,7 @@ impl SolscanApi {
             params["page"] = json!(page);
         }
 
-        if let Some(page_size) = request.page_size {
+        if let Some(page_size) = self.page_size("/nft/collection/lists", request.page_size) {
             params["page_size"] = json!(page_size);
         }
 
@@ -1446,7 +2213,7 @@ impl SolscanApi {
             params["page"] = json!(page);
         }
 
-        if let Some(page_size) = request.page_size {
+        if let Some(page_size) = self.page_size("/nft/collection/items", request.page_size) {
             params["page_size"] = json!(page_size);
         }
 
@@ -1463,18 +2230,2027 @@ impl SolscanApi {
 
         Ok(CallToolResult::success(vec![content]))
     }
-}
 
-#[tool(tool_box)]
-impl ServerHandler for SolscanApi {
-    fn get_info(&self) -> ServerInfo {
-        ServerInfo {
-            protocol_version: ProtocolVersion::V_2024_11_05,
-            capabilities: ServerCapabilities::builder()
-                .enable_tools()
-                .build(),
-            server_info: Implementation::from_build_env(),
-            instructions: Some("This server provides tools to access the Solscan API for Solana blockchain data. Use these tools to fetch token information, account activities, and transaction details on the Solana blockchain.".to_string()),
+    // Wallet Profile tool
+    #[tool(
+        description = "Summarize a wallet in one call: SOL balance, top holdings by USD value, first and last activity, inbound/outbound transfer counts, top counterparties, DeFi platforms used and stake totals"
+    )]
+    async fn wallet_profile(
+        &self,
+        #[tool(aggr)] request: WalletProfileRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let address = json!({ "address": request.address });
+        let transfers = |sort_order: &str, page_size: i32| {
+            json!({
+                "address": request.address,
+                "sort_by": "block_time",
+                "sort_order": sort_order,
+                "page_size": page_size,
+            })
+        };
+        let defi_activities = json!({
+            "address": request.address,
+            "page_size": 100,
+        });
+
+        let (detail, portfolio, mut transfers, first_transfers, defi_activities, stake) = tokio::join!(
+            self.make_request("/account/detail", Some(address.clone())),
+            self.make_request("/account/portfolio", Some(address.clone())),
+            self.make_request("/account/transfer", Some(transfers("desc", 100))),
+            self.make_request("/account/transfer", Some(transfers("asc", 10))),
+            self.make_request("/account/defi/activities", Some(defi_activities)),
+            self.make_request("/account/stake", Some(address)),
+        );
+
+        // Transfer volumes need USD values
+        if let Ok(transfers) = &mut transfers {
+            self.enrich_amounts(transfers).await;
+        }
+
+        let response = wallet::profile(
+            &request.address,
+            ProfileInputs {
+                detail,
+                portfolio,
+                transfers,
+                first_transfers,
+                defi_activities,
+                stake,
+            },
+            request.top_n.unwrap_or(DEFAULT_TOP_N),
+        );
+
+        let content = Content::json(response).map_err(|e| {
+            McpError::internal_error(
+                "Failed to serialize JSON response",
+                Some(json!({"error": e.message})),
+            )
+        })?;
+
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    // Trace Funds tool
+    #[tool(
+        description = "Trace where funds went (or came from) over several hops, starting from an address or a transaction signature. Walks account transfers breadth-first and returns a directed graph of address-to-address edges with aggregated amounts per token. Node and API-call limits bound the credit use"
+    )]
+    async fn trace_funds(
+        &self,
+        #[tool(aggr)] request: TraceFundsRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let direction = match &request.direction {
+            Some(direction) => direction
+                .parse()
+                .map_err(|e: String| McpError::invalid_params(e, None))?,
+            None => Direction::Out,
+        };
+
+        let limits = TraceLimits {
+            hops: request
+                .hops
+                .unwrap_or(DEFAULT_TRACE_HOPS)
+                .clamp(1, MAX_TRACE_HOPS),
+            max_nodes: request
+                .max_nodes
+                .unwrap_or(DEFAULT_TRACE_NODES)
+                .clamp(1, MAX_TRACE_NODES),
+            max_requests: request
+                .max_requests
+                .unwrap_or(DEFAULT_TRACE_REQUESTS)
+                .clamp(1, MAX_TRACE_REQUESTS),
+        };
+
+        let mut trace = Trace::new(direction, limits, request.min_amount);
+
+        let start = match (&request.address, &request.tx) {
+            (Some(address), None) => {
+                trace.add_seed(address);
+                json!({ "address": address })
+            }
+            (None, Some(tx)) => {
+                let params = json!({ "tx": tx });
+                let actions = self
+                    .make_request("/transaction/actions", Some(params))
+                    .await?;
+
+                let mut transfers = Value::Array(flow::transaction_transfers(&actions));
+                self.enrich_amounts(&mut transfers).await;
+
+                if let (Some(token), Some(transfers)) = (&request.token, transfers.as_array_mut()) {
+                    transfers.retain(|transfer| transfer["token_address"] == token.as_str());
+                }
+
+                trace.add_seed_transfers(
+                    transfers.as_array().map(Vec::as_slice).unwrap_or_default(),
+                );
+                json!({ "tx": tx })
+            }
+            _ => {
+                return Err(McpError::invalid_params(
+                    "pass either address or tx to start the trace from",
+                    None,
+                ))
+            }
+        };
+
+        while let Some((address, depth)) = trace.next_address() {
+            let mut params = json!({
+                "address": address,
+                "exclude_amount_zero": true,
+                "sort_by": "block_time",
+                "sort_order": "desc",
+                "page_size": 100,
+            });
+
+            if let Some(flow) = direction.flow() {
+                params["flow"] = json!(flow);
+            }
+
+            if let Some(token) = &request.token {
+                params["token"] = json!(token);
+            }
+
+            if let Some(from_time) = request.from_time {
+                params["from_time"] = json!(from_time);
+            }
+
+            if let Some(to_time) = request.to_time {
+                params["to_time"] = json!(to_time);
+            }
+
+            match self.make_request("/account/transfer", Some(params)).await {
+                Ok(mut response) => {
+                    self.enrich_amounts(&mut response).await;
+                    trace.add_transfers(&address, depth, items(&response));
+                }
+                Err(e) => trace.add_error(&address, &e.message),
+            }
+        }
+
+        let content = Content::json(trace.into_value(start)).map_err(|e| {
+            McpError::internal_error(
+                "Failed to serialize JSON response",
+                Some(json!({"error": e.message})),
+            )
+        })?;
+
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    // Transfer Graph tool
+    #[tool(
+        description = "Export the transfer graph of a set of addresses over a time window as Graphviz DOT, Mermaid or GraphML. Edges are labeled with the token and aggregated amount, nodes with known address labels"
+    )]
+    async fn transfer_graph(
+        &self,
+        #[tool(aggr)] request: TransferGraphRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let format = match &request.format {
+            Some(format) => format
+                .parse()
+                .map_err(|e: String| McpError::invalid_params(e, None))?,
+            None => GraphFormat::Dot,
+        };
+
+        let addresses: BTreeSet<&String> = request.addresses.iter().collect();
+        if addresses.is_empty() || addresses.len() > MAX_GRAPH_ADDRESSES {
+            return Err(McpError::invalid_params(
+                format!("pass between 1 and {} addresses", MAX_GRAPH_ADDRESSES),
+                None,
+            ));
+        }
+
+        let max_pages = request
+            .max_pages
+            .unwrap_or(DEFAULT_GRAPH_PAGES)
+            .clamp(1, MAX_GRAPH_PAGES);
+
+        let mut transfer_graph = FlowGraph::default();
+        for address in addresses {
+            let mut params = json!({
+                "address": address,
+                "exclude_amount_zero": true,
+                "sort_by": "block_time",
+                "sort_order": "desc",
+            });
+
+            if let Some(token) = &request.token {
+                params["token"] = json!(token);
+            }
+
+            if let Some(from_time) = request.from_time {
+                params["from_time"] = json!(from_time);
+            }
+
+            if let Some(to_time) = request.to_time {
+                params["to_time"] = json!(to_time);
+            }
+
+            let pages = self
+                .fetch_pages("/account/transfer", params, 100, max_pages)
+                .await?;
+
+            let mut transfers = Value::Array(pages.items);
+            self.enrich_amounts(&mut transfers).await;
+            for transfer in transfers.as_array().map(Vec::as_slice).unwrap_or_default() {
+                transfer_graph.add_transfer(transfer);
+            }
+        }
+
+        // Node labels come from the label store, edge labels use token symbols
+        let nodes: BTreeSet<String> = transfer_graph
+            .edges()
+            .flat_map(|(from, to, _, _)| [from.to_string(), to.to_string()])
+            .collect();
+        let tokens: BTreeSet<String> = transfer_graph
+            .edges()
+            .map(|(_, _, token, _)| token.to_string())
+            .collect();
+
+        let nodes: Vec<String> = nodes.into_iter().collect();
+
+        // The busiest nodes are looked up on Solscan whether or not `[labels] solscan_metadata`
+        // annotates other results, so the graph names its main actors at a bounded cost
+        let mut transfers: HashMap<&str, usize> = HashMap::new();
+        for (from, to, _, edge) in transfer_graph.edges() {
+            *transfers.entry(from).or_default() += edge.transfers;
+            *transfers.entry(to).or_default() += edge.transfers;
+        }
+        let mut busiest = nodes.clone();
+        busiest.sort_by_key(|node| std::cmp::Reverse(transfers[node.as_str()]));
+        let label_lookups = request
+            .label_lookups
+            .unwrap_or(DEFAULT_GRAPH_LABEL_LOOKUPS)
+            .min(MAX_GRAPH_LABEL_LOOKUPS);
+        self.lookup_metadata(&busiest, label_lookups).await;
+
+        let mut names = GraphLabels::default();
+        for (address, label) in self.address_labels(&nodes).await {
+            if let Some(name) = label.get("name").and_then(Value::as_str) {
+                names.addresses.insert(address, name.to_string());
+            }
+        }
+        names.symbols = self.token_symbols(&tokens).await;
+
+        let rendered = graph::render(&transfer_graph, &names, format);
+        Ok(CallToolResult::success(vec![Content::text(rendered)]))
+    }
+
+    // Account Counterparties tool
+    #[tool(
+        description = "Aggregate an account's transfers by counterparty: inbound/outbound amounts per token, USD value, number of transactions and first/last interaction. Sorted by value_usd (default), transactions, last_time or first_time"
+    )]
+    async fn account_counterparties(
+        &self,
+        #[tool(aggr)] request: AccountCounterpartiesRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let sort_by = match &request.sort_by {
+            Some(sort_by) => sort_by
+                .parse()
+                .map_err(|e: String| McpError::invalid_params(e, None))?,
+            None => SortBy::ValueUsd,
+        };
+
+        let mut params = json!({
+            "address": request.address,
+            "sort_by": "block_time",
+            "sort_order": "desc",
+        });
+
+        if let Some(flow) = &request.flow {
+            params["flow"] = json!(flow);
+        }
+
+        if let Some(token) = &request.token {
+            params["token"] = json!(token);
+        }
+
+        if let Some(from_time) = request.from_time {
+            params["from_time"] = json!(from_time);
+        }
+
+        if let Some(to_time) = request.to_time {
+            params["to_time"] = json!(to_time);
+        }
+
+        let max_pages = request
+            .max_pages
+            .unwrap_or(DEFAULT_COUNTERPARTY_PAGES)
+            .clamp(1, MAX_COUNTERPARTY_PAGES);
+
+        let pages = self
+            .fetch_pages("/account/transfer", params, 100, max_pages)
+            .await?;
+
+        let mut transfers = Value::Array(pages.items);
+        self.enrich_amounts(&mut transfers).await;
+        let transfers = transfers.as_array().map(Vec::as_slice).unwrap_or_default();
+
+        let tokens = transfers
+            .iter()
+            .filter_map(|transfer| transfer["token_address"].as_str())
+            .map(str::to_string)
+            .collect();
+        let symbols = self.token_symbols(&tokens).await;
+
+        let counterparties = counterparties::aggregate(
+            transfers,
+            &symbols,
+            sort_by,
+            request.limit.unwrap_or(DEFAULT_COUNTERPARTY_LIMIT),
+        );
+
+        let response = json!({
+            "success": true,
+            "data": {
+                "address": request.address,
+                "transfers_scanned": transfers.len(),
+                // False when older transfers were left out by `max_pages`
+                "complete": pages.complete,
+                "counterparties": counterparties,
+            },
+        });
+
+        let content = Content::json(response).map_err(|e| {
+            McpError::internal_error(
+                "Failed to serialize JSON response",
+                Some(json!({"error": e.message})),
+            )
+        })?;
+
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    // Cluster Wallets tool
+    #[tool(
+        description = "Group wallets that look operated together: same funder of their first inbound SOL transfer, direct transfers, or both close first-funding times and shared counterparties. Labeled accounts and hubs seen with many of the wallets are ignored. Returns the clusters with the evidence linking them"
+    )]
+    async fn cluster_wallets(
+        &self,
+        #[tool(aggr)] request: ClusterWalletsRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let addresses: BTreeSet<&String> = request.addresses.iter().collect();
+        if addresses.len() < 2 || addresses.len() > MAX_CLUSTER_ADDRESSES {
+            return Err(McpError::invalid_params(
+                format!("pass between 2 and {} addresses", MAX_CLUSTER_ADDRESSES),
+                None,
+            ));
+        }
+
+        let rules = ClusterRules {
+            time_window: request
+                .time_window
+                .unwrap_or(DEFAULT_CLUSTER_TIME_WINDOW)
+                .max(0),
+            min_shared_counterparties: request
+                .min_shared_counterparties
+                .unwrap_or(DEFAULT_SHARED_COUNTERPARTIES)
+                .max(1),
+            max_hub_degree: request.max_hub_degree.unwrap_or(DEFAULT_HUB_DEGREE).max(2),
+            max_funder_degree: request.max_funder_degree.map(|degree| degree.max(2)),
+        };
+
+        let mut wallets = Vec::new();
+        let mut errors = Map::new();
+        for address in addresses {
+            // Earliest inbound SOL transfers: the first one is the wallet's funding
+            let funding_params = json!({
+                "address": address,
+                "flow": "in",
+                "token": SOL_ADDRESS,
+                "exclude_amount_zero": true,
+                "page": 1,
+                "page_size": 10,
+                "sort_by": "block_time",
+                "sort_order": "asc",
+            });
+            let recent_params = json!({
+                "address": address,
+                "exclude_amount_zero": true,
+                "page": 1,
+                "page_size": 100,
+                "sort_by": "block_time",
+                "sort_order": "desc",
+            });
+
+            let (funding, recent) = tokio::join!(
+                self.make_request("/account/transfer", Some(funding_params)),
+                self.make_request("/account/transfer", Some(recent_params)),
+            );
+
+            let (mut funding, recent) = match (funding, recent) {
+                (Ok(funding), Ok(recent)) => (funding, recent),
+                (Err(e), _) | (_, Err(e)) => {
+                    errors.insert(address.clone(), json!(e.message));
+                    continue;
+                }
+            };
+
+            self.enrich_amounts(&mut funding).await;
+            wallets.push(WalletTransfers {
+                address: address.clone(),
+                funding: items(&funding).to_vec(),
+                recent: items(&recent).to_vec(),
+            });
+        }
+
+        // Exchanges, programs and other labeled accounts say nothing about common ownership
+        let candidates = cluster::addresses(&wallets);
+        let labeled: HashSet<String> = self
+            .address_labels(&candidates)
+            .await
+            .into_iter()
+            .map(|(address, _)| address)
+            .collect();
+
+        let mut data = cluster::cluster(&wallets, rules, &labeled);
+        if !errors.is_empty() {
+            data["errors"] = Value::Object(errors);
+        }
+
+        let response = json!({
+            "success": true,
+            "data": data,
+        });
+
+        let content = Content::json(response).map_err(|e| {
+            McpError::internal_error(
+                "Failed to serialize JSON response",
+                Some(json!({"error": e.message})),
+            )
+        })?;
+
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    // Detect Address Poisoning tool
+    #[tool(
+        description = "Scan an account's inbound transfers for address poisoning (dust sent from lookalike addresses sharing the start and end of a real counterparty) and dusting spam. Flags each suspicious sender with the address it imitates and the evidence"
+    )]
+    async fn detect_address_poisoning(
+        &self,
+        #[tool(aggr)] request: DetectAddressPoisoningRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let rules = PoisoningRules {
+            dust_usd: request.dust_usd.unwrap_or(DEFAULT_DUST_USD),
+            min_match: request.min_match.unwrap_or(DEFAULT_LOOKALIKE_MATCH).max(2),
+        };
+        let max_pages = request
+            .max_pages
+            .unwrap_or(DEFAULT_POISONING_PAGES)
+            .clamp(1, MAX_POISONING_PAGES);
+
+        let mut inbound_params = json!({
+            "address": request.address,
+            "flow": "in",
+            "sort_by": "block_time",
+            "sort_order": "desc",
+        });
+
+        if let Some(from_time) = request.from_time {
+            inbound_params["from_time"] = json!(from_time);
         }
+
+        if let Some(to_time) = request.to_time {
+            inbound_params["to_time"] = json!(to_time);
+        }
+
+        // The addresses the account really pays are the ones worth imitating
+        let mut outbound_params = inbound_params.clone();
+        outbound_params["flow"] = json!("out");
+        outbound_params["exclude_amount_zero"] = json!(true);
+
+        let (inbound, outbound) = tokio::join!(
+            self.fetch_pages("/account/transfer", inbound_params, 100, max_pages),
+            self.fetch_pages("/account/transfer", outbound_params, 100, max_pages),
+        );
+        let (inbound, outbound) = (inbound?, outbound?);
+        let complete = inbound.complete && outbound.complete;
+
+        let mut inbound = Value::Array(inbound.items);
+        let mut outbound = Value::Array(outbound.items);
+        self.enrich_amounts(&mut inbound).await;
+        self.enrich_amounts(&mut outbound).await;
+
+        let mut data = poisoning::detect(
+            inbound.as_array().map(Vec::as_slice).unwrap_or_default(),
+            outbound.as_array().map(Vec::as_slice).unwrap_or_default(),
+            rules,
+        );
+        data["address"] = json!(request.address);
+        data["complete"] = json!(complete);
+
+        let response = json!({
+            "success": true,
+            "data": data,
+        });
+
+        let content = Content::json(response).map_err(|e| {
+            McpError::internal_error(
+                "Failed to serialize JSON response",
+                Some(json!({"error": e.message})),
+            )
+        })?;
+
+        Ok(CallToolResult::success(vec![content]))
     }
+
+    // Detect Sandwiches tool
+    #[tool(
+        description = "Check whether an account's swaps were sandwiched: looks for a buy right before and a sell right after the swap, in the same pool and block, by the same signer, and estimates the value extracted. Checks the latest swaps or a single transaction"
+    )]
+    async fn detect_sandwiches(
+        &self,
+        #[tool(aggr)] request: DetectSandwichesRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let limit = request
+            .limit
+            .unwrap_or(DEFAULT_SANDWICH_SWAPS)
+            .clamp(1, MAX_SANDWICH_SWAPS);
+        let window = request
+            .window
+            .unwrap_or(DEFAULT_SANDWICH_WINDOW)
+            .clamp(1, MAX_SANDWICH_WINDOW);
+        let max_block_pages = request
+            .max_block_pages
+            .unwrap_or(DEFAULT_BLOCK_PAGES)
+            .clamp(1, MAX_BLOCK_PAGES);
+        let max_requests = request
+            .max_requests
+            .unwrap_or(DEFAULT_SANDWICH_REQUESTS)
+            .clamp(1, MAX_SANDWICH_REQUESTS);
+        let mut requests = 0;
+
+        let swaps = match &request.tx {
+            Some(tx) => vec![json!({ "trans_id": tx })],
+            None => {
+                let mut params = json!({
+                    "address": request.address,
+                    "activity_type": ["ACTIVITY_TOKEN_SWAP", "ACTIVITY_AGG_TOKEN_SWAP"],
+                    "page": 1,
+                    "page_size": 100,
+                    "sort_by": "block_time",
+                    "sort_order": "desc",
+                });
+
+                if let Some(from_time) = request.from_time {
+                    params["from_time"] = json!(from_time);
+                }
+
+                if let Some(to_time) = request.to_time {
+                    params["to_time"] = json!(to_time);
+                }
+
+                requests += 1;
+                let response = self
+                    .make_request("/account/defi/activities", Some(params))
+                    .await?;
+                items(&response).iter().take(limit).cloned().collect()
+            }
+        };
+
+        let mut results = Vec::new();
+        let mut sandwiches: Vec<(usize, Sandwich)> = Vec::new();
+        let mut limit_reached = false;
+
+        for swap in &swaps {
+            let Some(trans_id) = str_field(swap, "trans_id") else {
+                continue;
+            };
+            let mut result = json!({
+                "trans_id": trans_id,
+                "block_id": swap.get("block_id"),
+                "block_time": swap.get("block_time"),
+            });
+
+            // The swap itself, one page of its block and the two runs of one candidate
+            if requests + 4 > max_requests {
+                limit_reached = true;
+                result["status"] = json!("not_checked");
+                results.push(result);
+                continue;
+            }
+
+            requests += 1;
+            let actions = match self
+                .make_request("/transaction/actions", Some(json!({ "tx": trans_id })))
+                .await
+            {
+                Ok(actions) => actions,
+                Err(e) => {
+                    result["status"] = json!("error");
+                    result["error"] = json!(e.message);
+                    results.push(result);
+                    continue;
+                }
+            };
+
+            let (legs, unscaled) = sandwich::swap_legs(&actions);
+            let block =
+                i64_field(swap, "block_id").or_else(|| i64_field(&actions["data"], "block_id"));
+            result["block_id"] = json!(block);
+            result["swaps"] = json!(legs.iter().map(sandwich::leg_value).collect::<Vec<_>>());
+            if unscaled > 0 {
+                result["swaps_without_decimals"] = json!(unscaled);
+            }
+
+            let Some(block) = block.filter(|_| !legs.is_empty()) else {
+                result["status"] = json!("no_swap_found");
+                results.push(result);
+                continue;
+            };
+
+            // Page through the block until the swap and the transactions after it are in
+            let mut block_transactions: Vec<Value> = Vec::new();
+            for page in 1..=max_block_pages {
+                if requests >= max_requests {
+                    limit_reached = true;
+                    break;
+                }
+
+                let params = json!({
+                    "block": block,
+                    "exclude_vote": true,
+                    "page": page,
+                    "page_size": 100,
+                });
+                requests += 1;
+                let Ok(response) = self.make_request("/block/transactions", Some(params)).await
+                else {
+                    break;
+                };
+
+                let page_items = items(&response);
+                block_transactions.extend(page_items.iter().cloned());
+                let covered = sandwich::neighbors(&block_transactions, trans_id, window)
+                    .is_some_and(|(_, after)| after.len() >= window);
+                if covered || page_items.len() < 100 {
+                    break;
+                }
+            }
+
+            let Some((before, after)) = sandwich::neighbors(&block_transactions, trans_id, window)
+            else {
+                result["status"] = json!("not_found_in_block");
+                results.push(result);
+                continue;
+            };
+
+            let victim = block_transactions
+                .iter()
+                .find(|transaction| str_field(transaction, "tx_hash") == Some(trans_id))
+                .and_then(sandwich::signer)
+                .unwrap_or(&request.address);
+
+            let mut found = None;
+            for candidate in sandwich::candidates(&before, &after, victim)
+                .into_iter()
+                .take(MAX_SANDWICH_CANDIDATES)
+            {
+                if requests + 2 > max_requests {
+                    limit_reached = true;
+                    break;
+                }
+
+                let (_, front_tx, back_tx) = candidate;
+                requests += 2;
+                let (front, back) = tokio::join!(
+                    self.make_request("/transaction/actions", Some(json!({ "tx": front_tx }))),
+                    self.make_request("/transaction/actions", Some(json!({ "tx": back_tx }))),
+                );
+                let (Ok(front), Ok(back)) = (front, back) else {
+                    continue;
+                };
+
+                // Runs whose amounts cannot be scaled are not matched
+                let (front, back) = (sandwich::swap_legs(&front).0, sandwich::swap_legs(&back).0);
+                found = legs
+                    .iter()
+                    .find_map(|leg| sandwich::match_sandwich(leg, candidate, &front, &back));
+                if found.is_some() {
+                    break;
+                }
+            }
+
+            result["status"] = json!(if found.is_some() {
+                "sandwiched"
+            } else {
+                "clean"
+            });
+            if let Some(found) = found {
+                sandwiches.push((results.len(), found));
+            }
+            results.push(result);
+        }
+
+        // Value what the attackers took in USD where the token has a price
+        let tokens = sandwiches
+            .iter()
+            .map(|(_, found)| enrich::price_address(&found.token).to_string())
+            .collect();
+        let metas = self.token_meta_map(&tokens).await;
+        let mut extracted_usd = 0.0;
+        for (index, found) in &sandwiches {
+            let mut value = found.to_value();
+            let price = metas
+                .get(enrich::price_address(&found.token))
+                .and_then(|meta| meta.price);
+            if let (Some(amount), Some(price)) = (found.extracted, price) {
+                value["extracted"]["usd_value"] = json!(round(amount * price, 2));
+                extracted_usd += amount * price;
+            }
+            results[*index]["sandwich"] = value;
+        }
+
+        let response = json!({
+            "success": true,
+            "data": {
+                "address": request.address,
+                "swaps_checked": results
+                    .iter()
+                    .filter(|result| result["status"] != "not_checked")
+                    .count(),
+                "sandwiched": sandwiches.len(),
+                "extracted_usd": round(extracted_usd, 2),
+                "api_calls": requests,
+                "limit_reached": limit_reached,
+                "swaps": results,
+            },
+        });
+
+        let content = Content::json(response).map_err(|e| {
+            McpError::internal_error(
+                "Failed to serialize JSON response",
+                Some(json!({"error": e.message})),
+            )
+        })?;
+
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    // Wallet PnL tool
+    #[tool(
+        description = "Compute a wallet's realized PnL (FIFO, LIFO or average cost) from its swaps and balance changes valued at historical prices, plus unrealized PnL on current holdings (or on the lots held at `to_time`), per token and per day, week or month"
+    )]
+    async fn wallet_pnl(
+        &self,
+        #[tool(aggr)] request: WalletPnlRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let method = match &request.method {
+            Some(method) => method
+                .parse()
+                .map_err(|e: String| McpError::invalid_params(e, None))?,
+            None => CostMethod::Fifo,
+        };
+        let period = match &request.period {
+            Some(period) => period
+                .parse()
+                .map_err(|e: String| McpError::invalid_params(e, None))?,
+            None => Period::Month,
+        };
+        let max_pages = request
+            .max_pages
+            .unwrap_or(DEFAULT_PNL_PAGES)
+            .clamp(1, MAX_PNL_PAGES);
+
+        let mut params = json!({
+            "address": request.address,
+            "sort_by": "block_time",
+            "sort_order": "desc",
+        });
+
+        if let Some(from_time) = request.from_time {
+            params["from_time"] = json!(from_time);
+        }
+
+        if let Some(to_time) = request.to_time {
+            params["to_time"] = json!(to_time);
+        }
+
+        let mut swap_params = params.clone();
+        swap_params["activity_type"] = json!(["ACTIVITY_TOKEN_SWAP", "ACTIVITY_AGG_TOKEN_SWAP"]);
+        let mut change_params = params;
+        change_params["remove_spam"] = json!(true);
+
+        // A history ending at `to_time` is valued then, not with today's portfolio
+        let portfolio = async {
+            match request.to_time {
+                Some(_) => None,
+                None => {
+                    let params = json!({ "address": request.address });
+                    Some(self.make_request("/account/portfolio", Some(params)).await)
+                }
+            }
+        };
+        let (swaps, changes, portfolio) = tokio::join!(
+            self.fetch_pages("/account/defi/activities", swap_params, 100, max_pages),
+            self.fetch_pages("/account/balance_change", change_params, 100, max_pages),
+            portfolio,
+        );
+        let (swaps, changes) = (swaps?, changes?);
+        let complete = swaps.complete && changes.complete;
+
+        let mut warnings = Vec::new();
+        let holdings = match portfolio {
+            Some(Ok(portfolio)) => pnl::holdings(&portfolio),
+            Some(Err(e)) => {
+                warnings.push(format!(
+                    "No unrealized PnL, portfolio unavailable: {}",
+                    e.message
+                ));
+                HashMap::new()
+            }
+            None => HashMap::new(),
+        };
+
+        let mut swaps = Value::Array(swaps.items);
+        let mut changes = Value::Array(changes.items);
+        self.enrich_amounts(&mut swaps).await;
+        self.enrich_amounts(&mut changes).await;
+        let trades = pnl::trades(
+            swaps.as_array().map(Vec::as_slice).unwrap_or_default(),
+            changes.as_array().map(Vec::as_slice).unwrap_or_default(),
+        );
+
+        // Price the most traded tokens over the days they were traded
+        let points = trades
+            .iter()
+            .flat_map(|trade| trade.tokens().into_iter().map(|token| (token, trade.time)));
+        let (mut ranges, token_count) = prices::ranges(points, MAX_PRICED_TOKENS);
+        if token_count > MAX_PRICED_TOKENS {
+            warnings.push(format!(
+                "Only the {} most traded of {} tokens were priced",
+                MAX_PRICED_TOKENS, token_count
+            ));
+        }
+        // Held lots are valued at the price of `to_time`
+        if let Some(to_time) = request.to_time {
+            for range in ranges.values_mut() {
+                range.1 = range.1.max(to_time);
+            }
+        }
+
+        let (history, errors) = self.price_history(&ranges).await;
+        for (token, error) in errors {
+            warnings.push(format!("No price history for {}: {}", token, error));
+        }
+
+        let tokens = trades
+            .iter()
+            .flat_map(|trade| trade.tokens())
+            .map(str::to_string)
+            .collect();
+        let symbols = self.token_symbols(&tokens).await;
+
+        let valuation = match request.to_time {
+            Some(to_time) => Valuation::At(to_time),
+            None => Valuation::Current(&holdings),
+        };
+        let mut data = pnl::compute(&trades, &history, valuation, &symbols, method, period);
+        data["address"] = json!(request.address);
+        // False when older swaps or balance changes were left out by `max_pages`
+        data["complete"] = json!(complete);
+        if !warnings.is_empty() {
+            data["warnings"] = json!(warnings);
+        }
+
+        let response = json!({
+            "success": true,
+            "data": data,
+        });
+
+        let content = Content::json(response).map_err(|e| {
+            McpError::internal_error(
+                "Failed to serialize JSON response",
+                Some(json!({"error": e.message})),
+            )
+        })?;
+
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    // Tax Lot Export tool
+    #[tool(
+        description = "Export an account's transfers, swaps and optionally staking rewards as acquisition/disposal events valued in USD at the time, as CSV in a generic schema (with cost basis and gains) or the Koinly or CoinTracker import format. Events without a known price are flagged, not estimated"
+    )]
+    async fn tax_lot_export(
+        &self,
+        #[tool(aggr)] request: TaxLotExportRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let format = match &request.format {
+            Some(format) => format
+                .parse()
+                .map_err(|e: String| McpError::invalid_params(e, None))?,
+            None => TaxFormat::Generic,
+        };
+        let method = match &request.method {
+            Some(method) => method
+                .parse()
+                .map_err(|e: String| McpError::invalid_params(e, None))?,
+            None => CostMethod::Fifo,
+        };
+        let max_pages = request
+            .max_pages
+            .unwrap_or(DEFAULT_PNL_PAGES)
+            .clamp(1, MAX_PNL_PAGES);
+
+        let mut params = json!({
+            "address": request.address,
+            "sort_by": "block_time",
+            "sort_order": "desc",
+        });
+
+        if let Some(from_time) = request.from_time {
+            params["from_time"] = json!(from_time);
+        }
+
+        if let Some(to_time) = request.to_time {
+            params["to_time"] = json!(to_time);
+        }
+
+        let mut transfer_params = params.clone();
+        transfer_params["exclude_amount_zero"] = json!(true);
+        transfer_params["remove_spam"] = json!(true);
+        let mut swap_params = params;
+        swap_params["activity_type"] = json!(["ACTIVITY_TOKEN_SWAP", "ACTIVITY_AGG_TOKEN_SWAP"]);
+
+        let (transfers, swaps) = tokio::join!(
+            self.fetch_pages("/account/transfer", transfer_params, 100, max_pages),
+            self.fetch_pages("/account/defi/activities", swap_params, 100, max_pages),
+        );
+        let (transfers, swaps) = (transfers?, swaps?);
+        let complete = transfers.complete && swaps.complete;
+
+        let mut transfers = Value::Array(transfers.items);
+        let mut swaps = Value::Array(swaps.items);
+        self.enrich_amounts(&mut transfers).await;
+        self.enrich_amounts(&mut swaps).await;
+        let mut events = tax::events(
+            transfers.as_array().map(Vec::as_slice).unwrap_or_default(),
+            swaps.as_array().map(Vec::as_slice).unwrap_or_default(),
+        );
+
+        let mut warnings = Vec::new();
+        if request.include_rewards.unwrap_or(false) {
+            let mut params = json!({ "address": request.address });
+            if let Some(from_time) = request.from_time {
+                params["time_from"] = json!(from_time);
+            }
+            if let Some(to_time) = request.to_time {
+                params["time_to"] = json!(to_time);
+            }
+
+            let name = format!("rewards_{}", request.address);
+            let rewards = match self
+                .download_export("/account/reward/export", params, &name, false)
+                .await
+            {
+                Ok(export) => export.reader().and_then(|reader| {
+                    tax::reward_events(reader)
+                        .map_err(|e| McpError::internal_error(e.to_string(), None))
+                }),
+                Err(e) => Err(e),
+            };
+            match rewards {
+                Ok(rewards) => events.extend(rewards),
+                Err(e) => warnings.push(format!("Staking rewards left out: {}", e.message)),
+            }
+            events.sort_by_key(|event| event.time);
+        }
+
+        let points = events
+            .iter()
+            .flat_map(|event| event.tokens().map(|token| (token, event.time)));
+        let (ranges, token_count) = prices::ranges(points, MAX_PRICED_TOKENS);
+        if token_count > MAX_PRICED_TOKENS {
+            warnings.push(format!(
+                "Only the {} most used of {} tokens were priced",
+                MAX_PRICED_TOKENS, token_count
+            ));
+        }
+
+        let (history, errors) = self.price_history(&ranges).await;
+        for (token, error) in errors {
+            warnings.push(format!("No price history for {}: {}", token, error));
+        }
+
+        let tokens = events
+            .iter()
+            .flat_map(|event| event.tokens())
+            .map(str::to_string)
+            .collect();
+        let symbols = self.token_symbols(&tokens).await;
+
+        let (csv, summary) =
+            tax::render(&events, &history, &symbols, method, format).map_err(|e| {
+                McpError::internal_error(
+                    "Failed to write CSV export",
+                    Some(json!({"error": e.to_string()})),
+                )
+            })?;
+
+        // CoinTracker's layout has no column to flag a row without a known price
+        if format == TaxFormat::CoinTracker && summary.missing_prices > 0 {
+            return Err(McpError::invalid_params(
+                format!(
+                    "{} of {} events have no known price and the cointracker format cannot flag \
+                     them; use the generic or koinly format",
+                    summary.missing_prices, summary.events
+                ),
+                None,
+            ));
+        }
+
+        let response = json!({
+            "success": true,
+            "data": {
+                "address": request.address,
+                "events": summary.events,
+                // Rows written without a USD value because no price was known at the time
+                "missing_prices": summary.missing_prices,
+                // False when older transfers or swaps were left out by `max_pages`
+                "complete": complete,
+                "warnings": warnings,
+            },
+        });
+
+        let summary = Content::json(response).map_err(|e| {
+            McpError::internal_error(
+                "Failed to serialize JSON response",
+                Some(json!({"error": e.message})),
+            )
+        })?;
+
+        Ok(CallToolResult::success(vec![Content::text(csv), summary]))
+    }
+
+    // Token Holder Stats tool
+    #[tool(
+        description = "Concentration of a token's holders: top-10 and top-100 share of supply, Gini and Nakamoto coefficients of the largest holders read and holder counts per size bucket. Liquidity pools of the token's markets and accounts labeled as pools or programs are left out"
+    )]
+    async fn token_holder_stats(
+        &self,
+        #[tool(aggr)] request: TokenHolderStatsRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let limit = request
+            .limit
+            .unwrap_or(DEFAULT_HOLDER_LIMIT)
+            .clamp(1, MAX_HOLDER_LIMIT);
+
+        let mut warnings = Vec::new();
+
+        let meta = match self
+            .make_request(
+                "/token/meta",
+                Some(json!({ "token_address": request.token_address })),
+            )
+            .await
+        {
+            Ok(meta) => meta["data"].clone(),
+            Err(e) => {
+                warnings.push(format!("No token metadata: {}", e.message));
+                Value::Null
+            }
+        };
+        let decimals = meta.get("decimals").and_then(enrich::as_u32);
+        if decimals.is_none() {
+            warnings.push(
+                "Token decimals unknown: the supply and holder amounts without decimals of their \
+                 own are in raw units, so amounts and size buckets may be off"
+                    .to_string(),
+            );
+        }
+        let decimals = decimals.unwrap_or_default();
+        let supply = meta
+            .get("supply")
+            .and_then(enrich::as_f64)
+            .map(|supply| supply / 10f64.powi(decimals as i32));
+
+        let pages = self
+            .fetch_pages(
+                "/token/holders",
+                json!({ "token_address": request.token_address }),
+                HOLDER_PAGE_SIZE,
+                limit.div_ceil(HOLDER_PAGE_SIZE),
+            )
+            .await?;
+        let items = &pages.items[..pages.items.len().min(limit)];
+        let complete = pages.complete && items.len() == pages.items.len();
+
+        let pools = match self
+            .fetch_pages(
+                "/token/markets",
+                json!({ "token_address": request.token_address }),
+                100,
+                MAX_MARKET_PAGES,
+            )
+            .await
+        {
+            Ok(markets) => holders::pool_addresses(&markets.items),
+            Err(e) => {
+                warnings.push(format!("Liquidity pools not excluded: {}", e.message));
+                BTreeSet::new()
+            }
+        };
+
+        let categories: Vec<String> = match request.exclude_categories {
+            Some(categories) => categories,
+            None => EXCLUDED_HOLDER_CATEGORIES.map(str::to_string).to_vec(),
+        };
+        let labeled = self.labeled_accounts(&categories).await;
+        let (kept, excluded) =
+            holders::partition(holders::holders(items, decimals), &pools, &labeled);
+
+        let bounds = match request.bucket_bounds {
+            Some(bounds) => bounds,
+            None => match supply {
+                Some(supply) => DEFAULT_BUCKET_SHARES
+                    .iter()
+                    .map(|share| supply * share)
+                    .collect(),
+                None => Vec::new(),
+            },
+        };
+        let mut buckets = Vec::new();
+        if !bounds.is_empty() {
+            for (from, to) in holders::bucket_ranges(&bounds) {
+                let mut params = json!({
+                    "token_address": request.token_address,
+                    "page": 1,
+                    "page_size": 10,
+                    "from_amount": from.to_string(),
+                });
+                if let Some(to) = to {
+                    params["to_amount"] = json!(to.to_string());
+                }
+
+                match self.make_request("/token/holders", Some(params)).await {
+                    Ok(response) => buckets.push(Bucket {
+                        from,
+                        to,
+                        holders: response["data"]["total"].as_u64().unwrap_or_default(),
+                    }),
+                    Err(e) => {
+                        warnings.push(format!("Holder buckets left out: {}", e.message));
+                        buckets.clear();
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut data = holders::stats(&kept, &excluded, supply, &buckets);
+        data["token_address"] = json!(request.token_address);
+        data["supply"] = json!(supply);
+        data["holders"] = meta.get("holder").cloned().unwrap_or(Value::Null);
+        // False when smaller holders were left out by `limit`
+        data["complete"] = json!(complete);
+        data["warnings"] = json!(warnings);
+
+        let response = json!({
+            "success": true,
+            "data": data,
+        });
+
+        let content = Content::json(response).map_err(|e| {
+            McpError::internal_error(
+                "Failed to serialize JSON response",
+                Some(json!({"error": e.message})),
+            )
+        })?;
+
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    // Token Risk Report tool
+    #[tool(
+        description = "Rug-check a token: a scored checklist of mint and freeze authority, liquidity depth, number of pools, holder concentration, creator sales into the pools and token age, with the evidence behind each check"
+    )]
+    async fn token_risk_report(
+        &self,
+        #[tool(aggr)] request: TokenRiskReportRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let token = json!({ "token_address": request.token_address });
+
+        let (meta, markets, holder_pages) = tokio::join!(
+            self.make_request("/token/meta", Some(token.clone())),
+            self.fetch_pages("/token/markets", token.clone(), 100, MAX_MARKET_PAGES),
+            self.fetch_pages(
+                "/token/holders",
+                token,
+                HOLDER_PAGE_SIZE,
+                RISK_HOLDER_LIMIT.div_ceil(HOLDER_PAGE_SIZE),
+            ),
+        );
+        let meta = meta.map(|meta| meta["data"].clone());
+        let markets = markets.map(|markets| markets.items);
+
+        let decimals = meta
+            .as_ref()
+            .ok()
+            .and_then(|meta| meta.get("decimals"))
+            .and_then(enrich::as_u32)
+            .unwrap_or_default();
+        let pools = markets
+            .as_ref()
+            .map(|markets| holders::pool_addresses(markets))
+            .unwrap_or_default();
+        let labeled = self
+            .labeled_accounts(&EXCLUDED_HOLDER_CATEGORIES.map(str::to_string))
+            .await;
+        let holders = holder_pages.map(|pages| {
+            let items = &pages.items[..pages.items.len().min(RISK_HOLDER_LIMIT)];
+            holders::partition(holders::holders(items, decimals), &pools, &labeled)
+        });
+
+        let creator = meta
+            .as_ref()
+            .ok()
+            .and_then(|meta| str_field(meta, "creator"))
+            .map(str::to_string);
+        let creator_transfers = match creator {
+            Some(creator) => {
+                let params = json!({
+                    "address": request.token_address,
+                    "from": creator,
+                    "sort_by": "block_time",
+                    "sort_order": "desc",
+                });
+                let max_pages = request
+                    .max_pages
+                    .unwrap_or(DEFAULT_CREATOR_PAGES)
+                    .clamp(1, MAX_CREATOR_PAGES);
+
+                let transfers = match self
+                    .fetch_pages("/token/transfer", params, 100, max_pages)
+                    .await
+                {
+                    Ok(pages) => {
+                        let mut transfers = Value::Array(pages.items);
+                        self.enrich_amounts(&mut transfers).await;
+                        Ok(match transfers {
+                            Value::Array(transfers) => transfers,
+                            _ => Vec::new(),
+                        })
+                    }
+                    Err(e) => Err(e),
+                };
+                Some(transfers)
+            }
+            None => None,
+        };
+
+        let report = risk::report(
+            &request.token_address,
+            RiskInputs {
+                meta,
+                markets,
+                holders,
+                creator_transfers,
+                now: chrono::Utc::now().timestamp(),
+            },
+        );
+
+        let response = json!({
+            "success": true,
+            "data": report,
+        });
+
+        let content = Content::json(response).map_err(|e| {
+            McpError::internal_error(
+                "Failed to serialize JSON response",
+                Some(json!({"error": e.message})),
+            )
+        })?;
+
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    // Token Liquidity tool
+    #[tool(
+        description = "Compare the liquidity pools of a token across DEX markets: TVL, 24h volume and volume/TVL rankings, the price impact of a trade of a given USD size under a constant-product assumption, and totals per program (Raydium, Orca, Meteora...)"
+    )]
+    async fn token_liquidity(
+        &self,
+        #[tool(aggr)] request: TokenLiquidityRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let rank_by = match &request.sort_by {
+            Some(sort_by) => sort_by
+                .parse()
+                .map_err(|e: String| McpError::invalid_params(e, None))?,
+            None => RankBy::Tvl,
+        };
+        let trade_usd = request
+            .trade_size_usd
+            .filter(|size| *size > 0.0)
+            .unwrap_or(DEFAULT_TRADE_SIZE_USD);
+        let max_pools = request
+            .max_pools
+            .unwrap_or(DEFAULT_LIQUIDITY_POOLS)
+            .clamp(1, MAX_LIQUIDITY_POOLS);
+
+        let token = json!({ "token_address": request.token_address });
+        let (meta, markets) = tokio::join!(
+            self.make_request("/token/meta", Some(token.clone())),
+            self.fetch_pages("/token/markets", token, 100, MAX_LIQUIDITY_MARKET_PAGES),
+        );
+        let markets = markets?;
+
+        let mut warnings = Vec::new();
+        let price = match meta {
+            Ok(meta) => enrich::as_f64(&meta["data"]["price"]),
+            Err(e) => {
+                warnings.push(format!("No token price: {}", e.message));
+                None
+            }
+        };
+
+        // Look up the deepest pools by TVL
+        let mut deepest: Vec<&Value> = markets.items.iter().collect();
+        deepest.sort_by(|a, b| {
+            let tvl = |market: &Value| enrich::as_f64(&market["total_tvl"]).unwrap_or_default();
+            tvl(b).total_cmp(&tvl(a))
+        });
+
+        let mut lookups = tokio::task::JoinSet::new();
+        for address in deepest
+            .iter()
+            .filter_map(|market| {
+                str_field(market, "pool_id").or_else(|| str_field(market, "pool_address"))
+            })
+            .take(max_pools)
+        {
+            let api = self.clone();
+            let address = address.to_string();
+            lookups.spawn(async move {
+                let params = json!({ "address": address });
+                let (info, volume) = tokio::join!(
+                    api.make_request("/market/info", Some(params.clone())),
+                    api.make_request("/market/volume", Some(params)),
+                );
+                (address, info, volume)
+            });
+        }
+
+        let mut details: HashMap<String, (Option<Value>, Option<Value>)> = HashMap::new();
+        while let Some(lookup) = lookups.join_next().await {
+            match lookup {
+                Ok((address, info, volume)) => {
+                    let mut data = |name: &str, response: Result<Value, McpError>| match response {
+                        Ok(response) => Some(response["data"].clone()),
+                        Err(e) => {
+                            warnings.push(format!("No {} for {}: {}", name, address, e.message));
+                            None
+                        }
+                    };
+                    let info = data("market_info", info);
+                    let volume = data("market_volume", volume);
+                    details.insert(address, (info, volume));
+                }
+                Err(e) => tracing::warn!("Market lookup failed: {}", e),
+            }
+        }
+
+        let pools: Vec<Pool> = markets
+            .items
+            .iter()
+            .filter_map(|market| {
+                let address =
+                    str_field(market, "pool_id").or_else(|| str_field(market, "pool_address"))?;
+                let (info, volume) = match details.get(address) {
+                    Some((info, volume)) => (info.as_ref(), volume.as_ref()),
+                    None => (None, None),
+                };
+                Pool::new(&request.token_address, market, info, volume)
+            })
+            .collect();
+
+        let mut data = liquidity::compare(&pools, price, trade_usd, rank_by);
+        data["token_address"] = json!(request.token_address);
+        data["markets"] = json!(pools.len());
+        // Pools looked up with `market_info` and `market_volume`; the others only carry the
+        // figures of `token_markets`
+        data["pools_detailed"] = json!(details.len());
+        // False when markets were left out by the page limit
+        data["complete"] = json!(markets.complete);
+        data["warnings"] = json!(warnings);
+
+        let response = json!({
+            "success": true,
+            "data": data,
+        });
+
+        let content = Content::json(response).map_err(|e| {
+            McpError::internal_error(
+                "Failed to serialize JSON response",
+                Some(json!({"error": e.message})),
+            )
+        })?;
+
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    // Token Price Series tool
+    #[tool(
+        description = "Chart-ready price history of one or more tokens: daily prices over any range, fetched in 30-day requests per token and merged, resampled to daily, weekly or monthly OHLC candles on a common timeline, with returns, volatility and max drawdown per token"
+    )]
+    async fn token_price_series(
+        &self,
+        #[tool(aggr)] request: TokenPriceSeriesRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let mut tokens: Vec<String> = Vec::new();
+        for token in &request.address {
+            if !tokens.contains(token) {
+                tokens.push(token.clone());
+            }
+        }
+        if tokens.is_empty() || tokens.len() > MAX_SERIES_TOKENS {
+            return Err(McpError::invalid_params(
+                format!("pass between 1 and {} token addresses", MAX_SERIES_TOKENS),
+                None,
+            ));
+        }
+
+        let interval = match &request.interval {
+            Some(interval) => interval
+                .parse()
+                .map_err(|e: String| McpError::invalid_params(e, None))?,
+            None => Period::Day,
+        };
+
+        let to_time = request
+            .to_time
+            .unwrap_or_else(|| chrono::Utc::now().timestamp());
+        let from_time = request
+            .from_time
+            .unwrap_or(to_time - DEFAULT_SERIES_DAYS * 86_400);
+        if from_time > to_time {
+            return Err(McpError::invalid_params(
+                "from_time must not be after to_time",
+                None,
+            ));
+        }
+
+        let chunks = series::chunks(from_time, to_time, PRICE_CHUNK_DAYS);
+        if chunks.len() > MAX_PRICE_CHUNKS {
+            return Err(McpError::invalid_params(
+                format!(
+                    "time range too long, at most {} days per call",
+                    MAX_PRICE_CHUNKS as i64 * PRICE_CHUNK_DAYS
+                ),
+                None,
+            ));
+        }
+
+        let ranges: BTreeMap<String, (i64, i64)> = tokens
+            .iter()
+            .map(|token| (token.clone(), (from_time, to_time)))
+            .collect();
+        let (history, errors) = self.price_history(&ranges).await;
+        let mut warnings: Vec<String> = errors
+            .into_iter()
+            .map(|(token, error)| format!("{}: {}", token, error))
+            .collect();
+
+        let series: Vec<(String, BTreeMap<i64, f64>)> = tokens
+            .iter()
+            .map(|token| {
+                (
+                    token.clone(),
+                    history.prices(token).cloned().unwrap_or_default(),
+                )
+            })
+            .collect();
+        for (token, prices) in &series {
+            if prices.is_empty() {
+                warnings.push(format!("No price history for {}", token));
+            }
+        }
+
+        let symbols = self.token_symbols(&tokens.iter().cloned().collect()).await;
+        let mut data = series::align(&series, &symbols, interval);
+        data["from_time"] = json!(from_time);
+        data["to_time"] = json!(to_time);
+        data["warnings"] = json!(warnings);
+
+        let response = json!({
+            "success": true,
+            "data": data,
+        });
+
+        let content = Content::json(response).map_err(|e| {
+            McpError::internal_error(
+                "Failed to serialize JSON response",
+                Some(json!({"error": e.message})),
+            )
+        })?;
+
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    // Account Portfolio History tool
+    #[tool(
+        description = "Value a wallet over time: rebuilds the daily balance of every token backwards from the current portfolio and the balance change history, values each day with the token's daily price and returns a total USD series with the per-token composition. Answers what the wallet was worth on a past date"
+    )]
+    async fn account_portfolio_history(
+        &self,
+        #[tool(aggr)] request: AccountPortfolioHistoryRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let now = chrono::Utc::now().timestamp();
+        let to_time = request.to_time.unwrap_or(now).min(now);
+        let from_time = request
+            .from_time
+            .unwrap_or(to_time - DEFAULT_HISTORY_DAYS * 86_400);
+        if from_time > to_time {
+            return Err(McpError::invalid_params(
+                "from_time must not be after to_time",
+                None,
+            ));
+        }
+        let days = portfolio::days(from_time, to_time);
+        if days.len() as i64 > MAX_HISTORY_DAYS {
+            return Err(McpError::invalid_params(
+                format!("time range too long, at most {} days", MAX_HISTORY_DAYS),
+                None,
+            ));
+        }
+        let max_pages = request
+            .max_pages
+            .unwrap_or(DEFAULT_HISTORY_PAGES)
+            .clamp(1, MAX_HISTORY_PAGES);
+
+        // Every change since the start is undone from today's balances, so the changes are
+        // read up to now rather than up to `to_time`
+        let params = json!({
+            "address": request.address,
+            "from_time": from_time,
+            "remove_spam": true,
+            "sort_by": "block_time",
+            "sort_order": "desc",
+        });
+        let (current, changes) = tokio::join!(
+            self.make_request(
+                "/account/portfolio",
+                Some(json!({ "address": request.address }))
+            ),
+            self.fetch_pages("/account/balance_change", params, 100, max_pages),
+        );
+        let (current, changes) = (current?, changes?);
+
+        let current: HashMap<String, f64> = pnl::holdings(&current)
+            .into_iter()
+            .map(|(token, holding)| (token, holding.balance))
+            .collect();
+
+        let mut items = Value::Array(changes.items);
+        self.enrich_amounts(&mut items).await;
+        let balance_changes =
+            portfolio::balance_changes(items.as_array().map(Vec::as_slice).unwrap_or_default());
+        let balances = portfolio::daily_balances(&current, &balance_changes, &days);
+
+        let mut warnings = Vec::new();
+        // Balances before the oldest change read are only right when no change is missing
+        let reliable_from = match (changes.complete, balance_changes.last()) {
+            (false, Some(oldest)) => {
+                warnings.push(
+                    "Older balance changes were left out by max_pages; balances before \
+                     reliable_from are approximate"
+                        .to_string(),
+                );
+                Some(oldest.time)
+            }
+            _ => None,
+        };
+
+        let points = balances
+            .keys()
+            .flat_map(|token| [(token.as_str(), from_time), (token.as_str(), to_time)])
+            .chain(
+                balance_changes
+                    .iter()
+                    .map(|change| (change.token.as_str(), change.time)),
+            );
+        let (ranges, token_count) = prices::ranges(points, MAX_PRICED_TOKENS);
+        if token_count > MAX_PRICED_TOKENS {
+            warnings.push(format!(
+                "Only the {} most active of {} tokens were priced",
+                MAX_PRICED_TOKENS, token_count
+            ));
+        }
+
+        let (history, errors) = self.price_history(&ranges).await;
+        for (token, error) in errors {
+            warnings.push(format!("No price history for {}: {}", token, error));
+        }
+
+        let symbols = self
+            .token_symbols(&balances.keys().cloned().collect())
+            .await;
+        let mut data = portfolio::valuation(&days, &balances, &history, &symbols);
+        data["address"] = json!(request.address);
+        data["balance_changes"] = json!(balance_changes.len());
+        data["complete"] = json!(changes.complete);
+        data["reliable_from"] = json!(reliable_from);
+        data["warnings"] = json!(warnings);
+
+        let response = json!({
+            "success": true,
+            "data": data,
+        });
+
+        let content = Content::json(response).map_err(|e| {
+            McpError::internal_error(
+                "Failed to serialize JSON response",
+                Some(json!({"error": e.message})),
+            )
+        })?;
+
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    // Staking Summary tool
+    #[tool(
+        description = "Summarize a wallet's staking: stake per validator by status (active, activating, deactivating, inactive), rewards per epoch from each stake account's reward export, the effective APY of each stake account and validators compared by realized yield"
+    )]
+    async fn staking_summary(
+        &self,
+        #[tool(aggr)] request: StakingSummaryRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let epochs = request
+            .epochs
+            .unwrap_or(DEFAULT_APY_EPOCHS)
+            .clamp(1, MAX_APY_EPOCHS);
+        let max_accounts = request
+            .max_accounts
+            .unwrap_or(DEFAULT_STAKE_ACCOUNTS)
+            .clamp(1, MAX_STAKE_ACCOUNTS);
+
+        let stake = self
+            .fetch_pages(
+                "/account/stake",
+                json!({ "address": request.address }),
+                40,
+                MAX_STAKE_PAGES,
+            )
+            .await?;
+
+        // Largest stake accounts first, so the cap leaves out the ones that matter least
+        let mut accounts = stake.items;
+        accounts.sort_by(|a, b| {
+            let amount = |account: &Value| account["amount"].as_f64().unwrap_or_default();
+            amount(b).total_cmp(&amount(a))
+        });
+
+        let mut warnings = Vec::new();
+        if !stake.complete {
+            warnings.push(format!(
+                "Only the first {} stake accounts were read",
+                accounts.len()
+            ));
+        }
+
+        let mut lookups = tokio::task::JoinSet::new();
+        for address in accounts
+            .iter()
+            .filter_map(|account| str_field(account, "stake_account"))
+            .take(max_accounts)
+        {
+            let api = self.clone();
+            let mut params = json!({ "address": address });
+            if let Some(from_time) = request.from_time {
+                params["time_from"] = json!(from_time);
+            }
+            if let Some(to_time) = request.to_time {
+                params["time_to"] = json!(to_time);
+            }
+            let address = address.to_string();
+            lookups.spawn(async move {
+                let name = format!("rewards_{}", address);
+                let rows = match api
+                    .download_export("/account/reward/export", params, &name, false)
+                    .await
+                {
+                    Ok(export) => export.reader().and_then(|reader| {
+                        exports::reward_rows(reader)
+                            .map_err(|e| McpError::internal_error(e.to_string(), None))
+                    }),
+                    Err(e) => Err(e),
+                };
+                (address, rows)
+            });
+        }
+        if accounts.len() > max_accounts {
+            warnings.push(format!(
+                "Rewards were read for the {} largest of {} stake accounts",
+                max_accounts,
+                accounts.len()
+            ));
+        }
+
+        let mut rewards = HashMap::new();
+        while let Some(lookup) = lookups.join_next().await {
+            match lookup {
+                Ok((address, Ok(rows))) => {
+                    rewards.insert(address, rows);
+                }
+                Ok((address, Err(e))) => {
+                    warnings.push(format!("No rewards for {}: {}", address, e.message))
+                }
+                Err(e) => tracing::warn!("Reward export lookup failed: {}", e),
+            }
+        }
+
+        let mut data = staking::summary(&accounts, &rewards, epochs);
+        data["address"] = json!(request.address);
+        data["apy_epochs"] = json!(epochs);
+        data["complete"] = json!(stake.complete && accounts.len() <= max_accounts);
+        data["warnings"] = json!(warnings);
+
+        let response = json!({
+            "success": true,
+            "data": data,
+        });
+
+        let content = Content::json(response).map_err(|e| {
+            McpError::internal_error(
+                "Failed to serialize JSON response",
+                Some(json!({"error": e.message})),
+            )
+        })?;
+
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    // Transaction Explain tool
+    #[tool(
+        description = "Explain a transaction in plain words: merges transaction_detail and transaction_actions into a deterministic narrative of the signer and fee, the programs invoked, swaps with token symbols, transfers, accounts created and closed, and whether it succeeded or failed with the error"
+    )]
+    async fn transaction_explain(
+        &self,
+        #[tool(aggr)] request: TransactionExplainRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let params = json!({ "tx": request.tx });
+        let (detail, actions) = tokio::join!(
+            self.make_request("/transaction/detail", Some(params.clone())),
+            self.make_request("/transaction/actions", Some(params)),
+        );
+        let detail = detail?;
+
+        let mut warnings = Vec::new();
+        let actions = actions
+            .map_err(|e| {
+                warnings.push(format!(
+                    "Parsed actions unavailable, swaps and transfers left out: {}",
+                    e.message
+                ))
+            })
+            .ok();
+
+        let transaction = Transaction::parse(&detail, actions.as_ref());
+        warnings.extend(transaction.warnings());
+        let symbols = self.token_symbols(&transaction.tokens()).await;
+        let mut data = transaction.explain(&request.tx, &symbols);
+        data["warnings"] = json!(warnings);
+
+        let response = json!({
+            "success": true,
+            "data": data,
+        });
+
+        let content = Content::json(response).map_err(|e| {
+            McpError::internal_error(
+                "Failed to serialize JSON response",
+                Some(json!({"error": e.message})),
+            )
+        })?;
+
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    // Label Add tool
+    #[tool(
+        description = "Add or update a local label for an address, such as an exchange hot wallet, a known program or a treasury account. Labels annotate the addresses in every tool result"
+    )]
+    async fn label_add(
+        &self,
+        #[tool(aggr)] request: LabelAddRequest,
+    ) -> Result<CallToolResult, McpError> {
+        if !labels::is_address(&request.address) {
+            return Err(McpError::invalid_params(
+                "address must be a base58 Solana address",
+                Some(json!({ "address": request.address })),
+            ));
+        }
+
+        let name = request.name.trim().to_string();
+        if name.is_empty() {
+            return Err(McpError::invalid_params("name must not be empty", None));
+        }
+
+        let label = Label {
+            name,
+            category: request
+                .category
+                .filter(|category| !category.trim().is_empty()),
+        };
+
+        let persisted = self
+            .labels
+            .add(&request.address, label.clone())
+            .await
+            .map_err(|e| {
+                McpError::internal_error(
+                    "Failed to save label",
+                    Some(json!({"error": e.to_string()})),
+                )
+            })?;
+
+        let response = json!({
+            "success": true,
+            "data": {
+                "address": request.address,
+                "name": label.name,
+                "category": label.category,
+                "persisted": persisted,
+            },
+        });
+
+        let content = Content::json(response).map_err(|e| {
+            McpError::internal_error(
+                "Failed to serialize JSON response",
+                Some(json!({"error": e.message})),
+            )
+        })?;
+
+        Ok(CallToolResult::success(vec![content]))
+    }
+
+    // Label List tool
+    #[tool(
+        description = "List the local address labels, optionally filtered by a search term or a category"
+    )]
+    async fn label_list(
+        &self,
+        #[tool(aggr)] request: LabelListRequest,
+    ) -> Result<CallToolResult, McpError> {
+        let items: Vec<Value> = self
+            .labels
+            .list(request.query.as_deref(), request.category.as_deref())
+            .await
+            .into_iter()
+            .map(|(address, label)| {
+                json!({
+                    "address": address,
+                    "name": label.name,
+                    "category": label.category,
+                })
+            })
+            .collect();
+
+        let response = json!({
+            "success": true,
+            "data": items,
+        });
+
+        let content = Content::json(response).map_err(|e| {
+            McpError::internal_error(
+                "Failed to serialize JSON response",
+                Some(json!({"error": e.message})),
+            )
+        })?;
+
+        Ok(CallToolResult::success(vec![content]))
+    }
+}
+
+impl ServerHandler for SolscanApi {
+    async fn list_tools(
+        &self,
+        _: PaginatedRequestParam,
+        _: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        // Only advertise the tools enabled by the operator
+        let tools = Self::tool_box()
+            .list()
+            .into_iter()
+            .filter(|tool| self.policy.is_enabled(&tool.name))
+            .map(|tool| self.policy.describe(tool))
+            .map(OutputOptions::describe)
+            .collect();
+
+        Ok(ListToolsResult {
+            next_cursor: None,
+            tools,
+        })
+    }
+
+    async fn call_tool(
+        &self,
+        mut request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        if !self.policy.is_enabled(&request.name) {
+            return Err(McpError::invalid_params(
+                format!(
+                    "tool {} is disabled by the server configuration",
+                    request.name
+                ),
+                None,
+            ));
+        }
+
+        // Expensive endpoints only run when confirmed, otherwise return a cost estimate
+        let confirmed = match self
+            .policy
+            .check_cost(&request.name, &mut request.arguments)
+        {
+            Ok(confirmed) => confirmed,
+            Err(estimate) => return Ok(CallToolResult::success(vec![Content::json(estimate)?])),
+        };
+
+        let options = OutputOptions::take(&mut request.arguments, &self.config.output)?;
+        let tool = request.name.clone();
+
+        let api = Self {
+            confirmed,
+            ..self.clone()
+        };
+        let context = ToolCallContext::new(&api, request, context);
+        let result = Self::tool_box().call(context).await?;
+
+        self.process_output(&tool, &options, result).await
+    }
+
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo {
+            protocol_version: ProtocolVersion::V_2024_11_05,
+            capabilities: ServerCapabilities::builder()
+                .enable_tools()
+                .build(),
+            server_info: Implementation::from_build_env(),
+            instructions: Some("This server provides tools to access the Solscan API for Solana blockchain data. Use these tools to fetch token information, account activities, and transaction details on the Solana blockchain.".to_string()),
+        }
+    }
+}
+
+// Flatten request parameters into query pairs; arrays are sent as repeated `key[]` pairs,
+// which Solscan expects and `serde_urlencoded` cannot produce from a JSON value. Endpoints
+// taking arrays: `/token/meta/multi` and `/token/price/multi` (`address[]`), `/token/price`
+// and `/market/volume` (`time[]`), `/token/markets` (`program[]`), and the `activity_type[]`,
+// `amount[]`, `value[]`, `block_time[]`, `platform[]`, `source[]` and `price[]` filters of
+// the transfer, DeFi activity, balance change, export and NFT activity endpoints. Scalars
+// are unchanged.
+fn query_pairs(params: &Value) -> Vec<(String, String)> {
+    let Some(params) = params.as_object() else {
+        return Vec::new();
+    };
+
+    let mut pairs = Vec::new();
+    for (key, value) in params {
+        match value {
+            Value::Null => {}
+            Value::String(text) => pairs.push((key.clone(), text.clone())),
+            Value::Array(items) => {
+                for item in items {
+                    let item = match item {
+                        Value::String(text) => text.clone(),
+                        item => item.to_string(),
+                    };
+                    pairs.push((format!("{}[]", key), item));
+                }
+            }
+            value => pairs.push((key.clone(), value.to_string())),
+        }
+    }
+
+    pairs
+}
+
+// Prefix of every file the server writes to the exports directory; only these are removed
+// by the retention sweep
+const EXPORT_FILE_PREFIX: &str = "solscan_";
+
+fn is_export_file(file_name: &str) -> bool {
+    file_name.starts_with(EXPORT_FILE_PREFIX) && file_name.ends_with(".csv")
+}
+
+// Unique file name for an export, keeping only the characters safe in a path
+fn export_file_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let now = chrono::Utc::now();
+    format!(
+        "{}{}_{}.csv",
+        EXPORT_FILE_PREFIX,
+        name,
+        now.format("%Y%m%dT%H%M%S%.3f")
+    )
 }
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use crate::solscan_mcp::config::CacheConfig;
+
+/// In-memory TTL cache for successful Solscan responses, keyed by URL and query parameters
+pub struct ResponseCache {
+    enabled: bool,
+    ttl: Duration,
+    max_entries: usize,
+    entries: Mutex<HashMap<String, (Instant, Value)>>,
+}
+
+impl ResponseCache {
+    pub fn new(config: &CacheConfig) -> Self {
+        Self {
+            enabled: config.enabled && config.max_entries > 0 && config.ttl_secs > 0,
+            ttl: Duration::from_secs(config.ttl_secs),
+            max_entries: config.max_entries,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn key(url: &str, params: Option<&Value>) -> String {
+        match params {
+            Some(params) => format!("{}?{}", url, params),
+            None => url.to_string(),
+        }
+    }
+
+    pub async fn get(&self, key: &str) -> Option<Value> {
+        if !self.enabled {
+            return None;
+        }
+
+        let mut entries = self.entries.lock().await;
+        match entries.get(key) {
+            Some((inserted, value)) if inserted.elapsed() < self.ttl => Some(value.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub async fn insert(&self, key: String, value: Value) {
+        if !self.enabled {
+            return;
+        }
+
+        let mut entries = self.entries.lock().await;
+
+        // Drop expired entries first, then the oldest ones if the cache is still full
+        if entries.len() >= self.max_entries {
+            let ttl = self.ttl;
+            entries.retain(|_, (inserted, _)| inserted.elapsed() < ttl);
+        }
+
+        while entries.len() >= self.max_entries {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, (inserted, _))| *inserted)
+                .map(|(key, _)| key.clone());
+
+            match oldest {
+                Some(oldest) => entries.remove(&oldest),
+                None => break,
+            };
+        }
+
+        entries.insert(key, (Instant::now(), value));
+    }
+}
@@ -0,0 +1,457 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::solscan_mcp::output::OutputFormat;
+use crate::solscan_mcp::policy::ToolGroup;
+
+// Defaults used when neither the config file, the environment nor the command line set a value
+const DEFAULT_API_BASE_URL: &str = "https://pro-api.solscan.io/v2.0";
+const DEFAULT_PUBLIC_API_BASE_URL: &str = "https://public-api.solscan.io";
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_CACHE_TTL_SECS: u64 = 60;
+const DEFAULT_CACHE_MAX_ENTRIES: usize = 1000;
+const DEFAULT_TOKEN_META_TTL_SECS: u64 = 300;
+// `/account/metadata` lookups made to annotate a single tool result
+const DEFAULT_MAX_METADATA_LOOKUPS: usize = 10;
+// Estimated credit cost of the export endpoints, roughly ten regular lookups each
+const DEFAULT_EXPORT_CREDIT_COST: u32 = 1000;
+// Exports larger than this are saved to a file instead of being parsed in memory
+const DEFAULT_MAX_INLINE_EXPORT_BYTES: usize = 2 * 1024 * 1024;
+// Saved export files older than this are removed when the next one is saved
+const DEFAULT_EXPORT_RETENTION_HOURS: u64 = 24;
+
+const USAGE: &str = "Usage: solscan-mcp [--config <path>] [--base-url <url>] \
+[--public-base-url <url>] [--timeout-secs <secs>] [--rate-limit <requests per second>] \
+[--page-size <size>] [--labels <path>] [--no-cache] [--allow-expensive]";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("failed to read config file {path}: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("failed to parse config file {path}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("missing Solscan API key: set `api.key` in the config file or the SOLSCAN_API_KEY environment variable")]
+    MissingApiKey,
+    #[error("invalid value {value:?} for {name}: {reason}")]
+    InvalidValue {
+        name: String,
+        value: String,
+        reason: String,
+    },
+    #[error("missing value for {0}\n{USAGE}")]
+    MissingArgument(String),
+    #[error("unknown argument {0}\n{USAGE}")]
+    UnknownArgument(String),
+}
+
+/// Server settings, merged from the TOML config file, environment variables and
+/// command line flags (in increasing order of precedence).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub api: ApiConfig,
+    pub rate_limit: RateLimitConfig,
+    pub cache: CacheConfig,
+    pub defaults: DefaultsConfig,
+    pub tools: ToolsConfig,
+    pub safety: SafetyConfig,
+    pub output: OutputConfig,
+    pub labels: LabelsConfig,
+    pub exports: ExportsConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiConfig {
+    pub key: Option<String>,
+    pub base_url: String,
+    pub public_base_url: String,
+    pub timeout_secs: u64,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            key: None,
+            base_url: DEFAULT_API_BASE_URL.to_string(),
+            public_base_url: DEFAULT_PUBLIC_API_BASE_URL.to_string(),
+            timeout_secs: DEFAULT_TIMEOUT_SECS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// Maximum number of Solscan requests per second, unlimited when unset
+    pub requests_per_second: Option<u32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    pub enabled: bool,
+    pub ttl_secs: u64,
+    pub max_entries: usize,
+    /// How long token decimals and prices used to enrich amounts are kept
+    pub token_meta_ttl_secs: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ttl_secs: DEFAULT_CACHE_TTL_SECS,
+            max_entries: DEFAULT_CACHE_MAX_ENTRIES,
+            token_meta_ttl_secs: DEFAULT_TOKEN_META_TTL_SECS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DefaultsConfig {
+    /// Page size sent to paginated endpoints when the caller does not pass one
+    pub page_size: Option<i32>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ToolsConfig {
+    /// Tool groups to expose, all of them when unset
+    pub groups: Option<Vec<ToolGroup>>,
+    /// Individual tools to expose even if their group is disabled
+    pub allow: Vec<String>,
+    /// Individual tools to hide, takes precedence over `groups` and `allow`
+    pub deny: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SafetyConfig {
+    /// Call expensive tools without requiring `confirm: true` on each call
+    pub allow_expensive: bool,
+    /// Tools that need confirmation, with their estimated credit cost per call
+    pub expensive: HashMap<String, u32>,
+}
+
+impl Default for SafetyConfig {
+    fn default() -> Self {
+        Self {
+            allow_expensive: false,
+            expensive: HashMap::from([
+                (
+                    "account_transfer_export".to_string(),
+                    DEFAULT_EXPORT_CREDIT_COST,
+                ),
+                (
+                    "account_reward_export".to_string(),
+                    DEFAULT_EXPORT_CREDIT_COST,
+                ),
+            ]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    /// Maximum number of list items returned by a tool call
+    pub max_items: Option<usize>,
+    /// Maximum size of a tool response in bytes
+    pub max_response_bytes: Option<usize>,
+    /// Format used when a call does not pass `output_format`
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LabelsConfig {
+    /// JSON or CSV address book; `label_add` saves new labels to it
+    pub file: Option<PathBuf>,
+    /// Merge in the labels Solscan returns from `/account/metadata`. Off by default, since
+    /// every lookup is a paid API call.
+    pub solscan_metadata: bool,
+    /// Maximum number of `/account/metadata` lookups made to annotate one tool result
+    pub max_metadata_lookups: usize,
+}
+
+impl Default for LabelsConfig {
+    fn default() -> Self {
+        Self {
+            file: None,
+            solscan_metadata: false,
+            max_metadata_lookups: DEFAULT_MAX_METADATA_LOOKUPS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExportsConfig {
+    /// Directory large exports are saved to, `solscan-mcp-exports` in the temp dir when unset
+    pub dir: Option<PathBuf>,
+    /// Exports up to this size are parsed in memory; larger ones are streamed to a file
+    pub max_inline_bytes: usize,
+    /// Hours a saved export file is kept; 0 keeps them until removed by hand
+    pub retention_hours: u64,
+}
+
+impl Default for ExportsConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            max_inline_bytes: DEFAULT_MAX_INLINE_EXPORT_BYTES,
+            retention_hours: DEFAULT_EXPORT_RETENTION_HOURS,
+        }
+    }
+}
+
+impl ExportsConfig {
+    pub fn dir(&self) -> PathBuf {
+        self.dir
+            .clone()
+            .unwrap_or_else(|| std::env::temp_dir().join("solscan-mcp-exports"))
+    }
+}
+
+/// Command line flags; every flag overrides the matching config file and environment value
+#[derive(Debug, Default)]
+struct CliArgs {
+    config: Option<PathBuf>,
+    base_url: Option<String>,
+    public_base_url: Option<String>,
+    timeout_secs: Option<String>,
+    rate_limit: Option<String>,
+    page_size: Option<String>,
+    labels: Option<String>,
+    no_cache: bool,
+    allow_expensive: bool,
+}
+
+impl CliArgs {
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, ConfigError> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            // Accept both `--flag value` and `--flag=value`
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+
+            if flag == "--no-cache" {
+                parsed.no_cache = true;
+                continue;
+            }
+
+            if flag == "--allow-expensive" {
+                parsed.allow_expensive = true;
+                continue;
+            }
+
+            let slot = match flag.as_str() {
+                "--base-url" => &mut parsed.base_url,
+                "--public-base-url" => &mut parsed.public_base_url,
+                "--timeout-secs" => &mut parsed.timeout_secs,
+                "--rate-limit" => &mut parsed.rate_limit,
+                "--page-size" => &mut parsed.page_size,
+                "--labels" => &mut parsed.labels,
+                "--config" => {
+                    let value = inline_value
+                        .or_else(|| args.next())
+                        .ok_or_else(|| ConfigError::MissingArgument(flag.clone()))?;
+                    parsed.config = Some(PathBuf::from(value));
+                    continue;
+                }
+                _ => return Err(ConfigError::UnknownArgument(flag)),
+            };
+
+            let value = inline_value
+                .or_else(|| args.next())
+                .ok_or_else(|| ConfigError::MissingArgument(flag.clone()))?;
+            *slot = Some(value);
+        }
+
+        Ok(parsed)
+    }
+}
+
+impl Config {
+    /// Load the configuration from the process arguments and environment.
+    ///
+    /// The config file is taken from `--config` or `SOLSCAN_CONFIG`; without either, only
+    /// the environment and command line are used.
+    pub fn load() -> Result<Self, ConfigError> {
+        let args = CliArgs::parse(std::env::args().skip(1))?;
+
+        let path = args
+            .config
+            .clone()
+            .or_else(|| std::env::var_os("SOLSCAN_CONFIG").map(PathBuf::from));
+
+        let mut config = match path {
+            Some(path) => Self::from_file(&path)?,
+            None => Self::default(),
+        };
+
+        config.apply_env()?;
+        config.apply_args(args)?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let contents = std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        toml::from_str(&contents).map_err(|source| ConfigError::Parse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// The API key; only valid after `validate` has succeeded
+    pub fn api_key(&self) -> &str {
+        self.api.key.as_deref().unwrap_or_default()
+    }
+
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        if let Ok(api_key) = std::env::var("SOLSCAN_API_KEY") {
+            self.api.key = Some(api_key);
+        }
+
+        if let Ok(base_url) = std::env::var("SOLSCAN_BASE_URL") {
+            self.api.base_url = base_url;
+        }
+
+        if let Ok(public_base_url) = std::env::var("SOLSCAN_PUBLIC_BASE_URL") {
+            self.api.public_base_url = public_base_url;
+        }
+
+        if let Ok(timeout_secs) = std::env::var("SOLSCAN_TIMEOUT_SECS") {
+            self.api.timeout_secs = parse_value("SOLSCAN_TIMEOUT_SECS", &timeout_secs)?;
+        }
+
+        if let Ok(rate_limit) = std::env::var("SOLSCAN_RATE_LIMIT") {
+            self.rate_limit.requests_per_second =
+                Some(parse_value("SOLSCAN_RATE_LIMIT", &rate_limit)?);
+        }
+
+        if let Some(labels) = std::env::var_os("SOLSCAN_LABELS") {
+            self.labels.file = Some(PathBuf::from(labels));
+        }
+
+        Ok(())
+    }
+
+    fn apply_args(&mut self, args: CliArgs) -> Result<(), ConfigError> {
+        if let Some(base_url) = args.base_url {
+            self.api.base_url = base_url;
+        }
+
+        if let Some(public_base_url) = args.public_base_url {
+            self.api.public_base_url = public_base_url;
+        }
+
+        if let Some(timeout_secs) = args.timeout_secs {
+            self.api.timeout_secs = parse_value("--timeout-secs", &timeout_secs)?;
+        }
+
+        if let Some(rate_limit) = args.rate_limit {
+            self.rate_limit.requests_per_second = Some(parse_value("--rate-limit", &rate_limit)?);
+        }
+
+        if let Some(page_size) = args.page_size {
+            self.defaults.page_size = Some(parse_value("--page-size", &page_size)?);
+        }
+
+        if let Some(labels) = args.labels {
+            self.labels.file = Some(PathBuf::from(labels));
+        }
+
+        if args.no_cache {
+            self.cache.enabled = false;
+        }
+
+        if args.allow_expensive {
+            self.safety.allow_expensive = true;
+        }
+
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self
+            .api
+            .key
+            .as_deref()
+            .is_none_or(|key| key.trim().is_empty())
+        {
+            return Err(ConfigError::MissingApiKey);
+        }
+
+        for (name, url) in [
+            ("api.base_url", &self.api.base_url),
+            ("api.public_base_url", &self.api.public_base_url),
+        ] {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(invalid_value(name, url, "expected an http(s) URL"));
+            }
+        }
+
+        if self.api.timeout_secs == 0 {
+            return Err(invalid_value("api.timeout_secs", "0", "must be positive"));
+        }
+
+        if self.rate_limit.requests_per_second == Some(0) {
+            return Err(invalid_value(
+                "rate_limit.requests_per_second",
+                "0",
+                "must be positive, omit it to disable rate limiting",
+            ));
+        }
+
+        if let Some(page_size) = self.defaults.page_size {
+            if !(1..=100).contains(&page_size) {
+                return Err(invalid_value(
+                    "defaults.page_size",
+                    &page_size.to_string(),
+                    "must be between 1 and 100",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, ConfigError>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .trim()
+        .parse()
+        .map_err(|e: T::Err| invalid_value(name, value, &e.to_string()))
+}
+
+fn invalid_value(name: &str, value: &str, reason: &str) -> ConfigError {
+    ConfigError::InvalidValue {
+        name: name.to_string(),
+        value: value.to_string(),
+        reason: reason.to_string(),
+    }
+}
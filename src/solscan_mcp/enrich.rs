@@ -0,0 +1,237 @@
+use std::collections::{BTreeSet, HashMap};
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Map, Value};
+use tokio::sync::Mutex;
+
+use crate::solscan_mcp::output::format_units;
+
+// Native SOL has no token metadata of its own; wrapped SOL carries its price
+pub const SOL_ADDRESS: &str = "So11111111111111111111111111111111111111111";
+pub const WSOL_ADDRESS: &str = "So11111111111111111111111111111111111111112";
+pub const SOL_DECIMALS: u32 = 9;
+
+// Tools whose items get `ui_amount` and `usd_value_now` fields
+const ENRICHED_TOOLS: &[&str] = &[
+    "account_transfer",
+    "token_transfer",
+    "balance_change",
+    "account_defi_activities",
+    "token_defi_activities",
+];
+
+/// The parts of `/token/meta` needed to interpret raw amounts
+#[derive(Debug, Clone, Default)]
+pub struct TokenMeta {
+    pub symbol: Option<String>,
+    pub decimals: Option<u32>,
+    pub price: Option<f64>,
+}
+
+impl TokenMeta {
+    pub fn from_value(value: &Value) -> Self {
+        Self {
+            symbol: value
+                .get("symbol")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            decimals: value.get("decimals").and_then(as_u32),
+            price: value.get("price").and_then(as_f64),
+        }
+    }
+}
+
+/// Local cache of token metadata so repeated tool calls do not refetch the same tokens
+pub struct TokenMetaCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (Instant, TokenMeta)>>,
+}
+
+impl TokenMetaCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Split `addresses` into cached metadata and the addresses still to fetch
+    pub async fn lookup(
+        &self,
+        addresses: &BTreeSet<String>,
+    ) -> (HashMap<String, TokenMeta>, Vec<String>) {
+        let entries = self.entries.lock().await;
+        let mut found = HashMap::new();
+        let mut missing = Vec::new();
+
+        for address in addresses {
+            match entries.get(address) {
+                Some((inserted, meta)) if inserted.elapsed() < self.ttl => {
+                    found.insert(address.clone(), meta.clone());
+                }
+                _ => missing.push(address.clone()),
+            }
+        }
+
+        (found, missing)
+    }
+
+    pub async fn insert(&self, metas: &HashMap<String, TokenMeta>) {
+        let mut entries = self.entries.lock().await;
+        let now = Instant::now();
+
+        entries.retain(|_, (inserted, _)| inserted.elapsed() < self.ttl);
+        for (address, meta) in metas {
+            entries.insert(address.clone(), (now, meta.clone()));
+        }
+    }
+}
+
+pub fn is_enriched(tool: &str) -> bool {
+    ENRICHED_TOOLS.contains(&tool)
+}
+
+/// Token addresses referenced by the amount legs of a response
+pub fn token_addresses(value: &Value) -> BTreeSet<String> {
+    let mut addresses = BTreeSet::new();
+    walk(value, &mut |map| {
+        for leg in legs(map) {
+            if let Some(token) = map.get(leg.token).and_then(Value::as_str) {
+                addresses.insert(price_address(token).to_string());
+            }
+        }
+    });
+    addresses
+}
+
+/// Add `ui_amount` and `usd_value_now` next to every raw amount of a response.
+///
+/// Decimals come from the item itself when Solscan includes them, otherwise from the token
+/// metadata. `usd_value_now` uses the current token price, so it is not the value at the
+/// time of a past transfer and must not stand in for Solscan's historical `value`.
+pub fn apply(value: &mut Value, metas: &HashMap<String, TokenMeta>) {
+    walk_mut(value, &mut |map| {
+        let legs: Vec<&Leg> = legs(map).collect();
+        for leg in legs {
+            let Some(token) = map.get(leg.token).and_then(Value::as_str) else {
+                continue;
+            };
+            let meta = metas.get(price_address(token));
+
+            let decimals = map
+                .get(leg.decimals)
+                .and_then(as_u32)
+                .or_else(|| meta.and_then(|meta| meta.decimals))
+                .or_else(|| (token == SOL_ADDRESS).then_some(SOL_DECIMALS));
+
+            let Some(ui_amount) =
+                decimals.and_then(|decimals| ui_amount(map.get(leg.amount)?, decimals))
+            else {
+                continue;
+            };
+
+            map.insert(leg.ui_amount.to_string(), json!(ui_amount));
+            if let Some(price) = meta.and_then(|meta| meta.price) {
+                map.insert(leg.usd_value_now.to_string(), json!(ui_amount * price));
+            }
+        }
+    });
+}
+
+/// Scale a raw integer amount (number or numeric string) by `decimals`
+pub fn ui_amount(raw: &Value, decimals: u32) -> Option<f64> {
+    let raw = match raw {
+        Value::Number(number) if number.is_i64() || number.is_u64() => number.to_string(),
+        Value::String(text) => text.clone(),
+        _ => return None,
+    };
+
+    format_units(&raw, decimals)?.parse().ok()
+}
+
+/// Metadata for native SOL is looked up through wrapped SOL
+pub fn price_address(token: &str) -> &str {
+    if token == SOL_ADDRESS {
+        WSOL_ADDRESS
+    } else {
+        token
+    }
+}
+
+/// Field names of one amount in an item
+struct Leg {
+    token: &'static str,
+    amount: &'static str,
+    decimals: &'static str,
+    ui_amount: &'static str,
+    usd_value_now: &'static str,
+}
+
+const LEGS: &[Leg] = &[
+    // Transfers and balance changes
+    Leg {
+        token: "token_address",
+        amount: "amount",
+        decimals: "token_decimals",
+        ui_amount: "ui_amount",
+        usd_value_now: "usd_value_now",
+    },
+    // DeFi routers: the token swapped in and the token swapped out
+    Leg {
+        token: "token1",
+        amount: "amount1",
+        decimals: "token1_decimals",
+        ui_amount: "ui_amount1",
+        usd_value_now: "usd_value1_now",
+    },
+    Leg {
+        token: "token2",
+        amount: "amount2",
+        decimals: "token2_decimals",
+        ui_amount: "ui_amount2",
+        usd_value_now: "usd_value2_now",
+    },
+];
+
+fn legs(map: &Map<String, Value>) -> impl Iterator<Item = &'static Leg> + '_ {
+    LEGS.iter()
+        .filter(|leg| map.contains_key(leg.token) && map.contains_key(leg.amount))
+}
+
+fn walk(value: &Value, visit: &mut impl FnMut(&Map<String, Value>)) {
+    match value {
+        Value::Object(map) => {
+            visit(map);
+            map.values().for_each(|value| walk(value, visit));
+        }
+        Value::Array(items) => items.iter().for_each(|item| walk(item, visit)),
+        _ => {}
+    }
+}
+
+fn walk_mut(value: &mut Value, visit: &mut impl FnMut(&mut Map<String, Value>)) {
+    match value {
+        Value::Object(map) => {
+            visit(map);
+            map.values_mut().for_each(|value| walk_mut(value, visit));
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| walk_mut(item, visit)),
+        _ => {}
+    }
+}
+
+pub fn as_u32(value: &Value) -> Option<u32> {
+    match value {
+        Value::Number(number) => number.as_u64().and_then(|n| u32::try_from(n).ok()),
+        Value::String(text) => text.parse().ok(),
+        _ => None,
+    }
+}
+
+pub fn as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(text) => text.parse().ok(),
+        _ => None,
+    }
+}
@@ -0,0 +1,413 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tokio::sync::Mutex;
+
+use crate::solscan_mcp::config::LabelsConfig;
+
+// Base58 without 0, O, I and l; Solana addresses are 32 to 44 characters long
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+// Solscan metadata lookups remembered before the memo is reset
+const MAX_METADATA_ENTRIES: usize = 10_000;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LabelError {
+    #[error("failed to read label file {path}: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("failed to parse label file {path}: {reason}")]
+    Parse { path: PathBuf, reason: String },
+    #[error("failed to write label file {path}: {reason}")]
+    Write { path: PathBuf, reason: String },
+}
+
+/// A name for an address from the local address book
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Label {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+}
+
+/// Label and tags Solscan returns for an address from `/account/metadata`
+#[derive(Debug, Clone)]
+pub struct SolscanLabel {
+    pub name: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl SolscanLabel {
+    /// Parse the `data` of an `/account/metadata` response, `None` when Solscan knows nothing
+    pub fn from_value(data: &Value) -> Option<Self> {
+        let name = data
+            .get("account_label")
+            .and_then(Value::as_str)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string);
+        let tags: Vec<String> = data
+            .get("account_tags")
+            .and_then(Value::as_array)
+            .map(|tags| {
+                tags.iter()
+                    .filter_map(Value::as_str)
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        (name.is_some() || !tags.is_empty()).then_some(Self { name, tags })
+    }
+}
+
+/// One row of a label file, used for CSV files and the list form of JSON files
+#[derive(Debug, Serialize, Deserialize)]
+struct LabelRow {
+    address: String,
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    category: Option<String>,
+}
+
+/// Shapes accepted in a JSON label file
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LabelFile {
+    Rows(Vec<LabelRow>),
+    Map(BTreeMap<String, LabelEntry>),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LabelEntry {
+    Name(String),
+    Label(Label),
+}
+
+/// Address book loaded from the `[labels] file`, extended at runtime by `label_add`, plus the
+/// Solscan metadata labels looked up so far
+pub struct LabelStore {
+    path: Option<PathBuf>,
+    local: Mutex<BTreeMap<String, Label>>,
+    solscan: Mutex<HashMap<String, Option<SolscanLabel>>>,
+}
+
+impl LabelStore {
+    /// Load the configured label file; a file that does not exist yet starts an empty book
+    pub fn load(config: &LabelsConfig) -> Result<Self, LabelError> {
+        let local = match &config.file {
+            Some(path) if path.exists() => read_labels(path)?,
+            _ => BTreeMap::new(),
+        };
+
+        if let Some(path) = &config.file {
+            tracing::info!(
+                "Loaded {} address labels from {}",
+                local.len(),
+                path.display()
+            );
+        }
+
+        Ok(Self {
+            path: config.file.clone(),
+            local: Mutex::new(local),
+            solscan: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Add or replace a label, saving the book when a file is configured.
+    ///
+    /// Returns whether the label was written to disk; on a write error the book is unchanged.
+    pub async fn add(&self, address: &str, label: Label) -> Result<bool, LabelError> {
+        let mut local = self.local.lock().await;
+
+        let mut updated = local.clone();
+        updated.insert(address.to_string(), label);
+
+        if let Some(path) = &self.path {
+            write_labels(path, &updated).await?;
+        }
+
+        *local = updated;
+        Ok(self.path.is_some())
+    }
+
+    /// Local labels whose address, name or category contains `query`, optionally in one category
+    pub async fn list(&self, query: Option<&str>, category: Option<&str>) -> Vec<(String, Label)> {
+        let query = query.map(str::to_lowercase);
+        let local = self.local.lock().await;
+
+        local
+            .iter()
+            .filter(|(_, label)| {
+                category.is_none_or(|category| {
+                    label
+                        .category
+                        .as_deref()
+                        .is_some_and(|own| own.eq_ignore_ascii_case(category))
+                })
+            })
+            .filter(|(address, label)| {
+                query.as_deref().is_none_or(|query| {
+                    address.to_lowercase().contains(query)
+                        || label.name.to_lowercase().contains(query)
+                        || label
+                            .category
+                            .as_deref()
+                            .is_some_and(|category| category.to_lowercase().contains(query))
+                })
+            })
+            .map(|(address, label)| (address.clone(), label.clone()))
+            .collect()
+    }
+
+    /// Addresses of `addresses` whose Solscan metadata has not been looked up yet
+    pub async fn unknown_metadata(&self, addresses: &[String]) -> Vec<String> {
+        let solscan = self.solscan.lock().await;
+        addresses
+            .iter()
+            .filter(|address| !solscan.contains_key(*address))
+            .cloned()
+            .collect()
+    }
+
+    /// Remember the Solscan metadata of an address, including the lack of any
+    pub async fn insert_metadata(&self, address: &str, label: Option<SolscanLabel>) {
+        let mut solscan = self.solscan.lock().await;
+        if solscan.len() >= MAX_METADATA_ENTRIES {
+            solscan.clear();
+        }
+        solscan.insert(address.to_string(), label);
+    }
+
+    /// The `labels` object for a response: every known address with its local label merged
+    /// with the one from Solscan. Local names win; Solscan's name is kept when it differs.
+    pub async fn annotations(&self, addresses: &[String]) -> Map<String, Value> {
+        let local = self.local.lock().await;
+        let solscan = self.solscan.lock().await;
+        let mut annotations = Map::new();
+
+        for address in addresses {
+            let local = local.get(address);
+            let solscan = solscan.get(address).and_then(Option::as_ref);
+
+            let annotation = match (local, solscan) {
+                (None, None) => continue,
+                (Some(label), None) => json!({
+                    "name": label.name,
+                    "category": label.category,
+                    "source": "local",
+                }),
+                (None, Some(remote)) => json!({
+                    "name": remote.name,
+                    "tags": remote.tags,
+                    "source": "solscan",
+                }),
+                (Some(label), Some(remote)) => {
+                    let mut annotation = json!({
+                        "name": label.name,
+                        "category": label.category,
+                        "tags": remote.tags,
+                        "source": "local+solscan",
+                    });
+                    if remote.name.is_some() && remote.name.as_ref() != Some(&label.name) {
+                        annotation["solscan_name"] = json!(remote.name);
+                    }
+                    annotation
+                }
+            };
+
+            annotations.insert(address.clone(), prune_empty(annotation));
+        }
+
+        annotations
+    }
+}
+
+/// Every distinct account address in `value`, in order of appearance. Only address-typed
+/// fields are read (`*_address` and `ADDRESS_FIELDS`), so signatures and block hashes that
+/// happen to be base58 are not looked up.
+pub fn addresses(value: &Value) -> Vec<String> {
+    let mut found = Vec::new();
+    collect_addresses(value, false, &mut found);
+
+    let mut seen = HashSet::new();
+    found.retain(|address| seen.insert(*address));
+    found.into_iter().map(str::to_string).collect()
+}
+
+/// Drop the `labels` entries of addresses that no longer appear in `value`, such as the items
+/// trimmed to the response budget
+pub fn prune_labels(value: &mut Value) {
+    let Some(Value::Object(mut annotations)) =
+        value.as_object_mut().and_then(|map| map.remove("labels"))
+    else {
+        return;
+    };
+
+    let present: HashSet<String> = addresses(value).into_iter().collect();
+    annotations.retain(|address, _| present.contains(address));
+    if let (Value::Object(map), false) = (value, annotations.is_empty()) {
+        map.insert("labels".to_string(), Value::Object(annotations));
+    }
+}
+
+fn collect_addresses<'a>(value: &'a Value, is_address_field: bool, found: &mut Vec<&'a str>) {
+    match value {
+        Value::String(text) if is_address_field && is_address(text) => found.push(text),
+        Value::Object(map) => map
+            .iter()
+            .for_each(|(key, value)| collect_addresses(value, is_address_key(key), found)),
+        Value::Array(items) => items
+            .iter()
+            .for_each(|item| collect_addresses(item, is_address_field, found)),
+        _ => {}
+    }
+}
+
+/// Fields holding account addresses besides `*_address`, including the ones the analytics
+/// tools emit
+const ADDRESS_FIELDS: &[&str] = &[
+    "address",
+    "owner",
+    "signer",
+    "from",
+    "to",
+    "sender",
+    "funder",
+    "wallets",
+    "program_id",
+    "stake_account",
+];
+
+fn is_address_key(key: &str) -> bool {
+    key.ends_with("_address") || ADDRESS_FIELDS.contains(&key)
+}
+
+pub fn is_address(text: &str) -> bool {
+    (32..=44).contains(&text.len()) && text.chars().all(|c| BASE58_ALPHABET.contains(c))
+}
+
+fn prune_empty(mut annotation: Value) -> Value {
+    if let Value::Object(map) = &mut annotation {
+        map.retain(|_, value| match value {
+            Value::Null => false,
+            Value::Array(items) => !items.is_empty(),
+            _ => true,
+        });
+    }
+    annotation
+}
+
+fn is_csv(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"))
+}
+
+fn read_labels(path: &Path) -> Result<BTreeMap<String, Label>, LabelError> {
+    let contents = std::fs::read_to_string(path).map_err(|source| LabelError::Read {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let parse_error = |reason: String| LabelError::Parse {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    let rows = if is_csv(path) {
+        csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(contents.as_bytes())
+            .deserialize::<LabelRow>()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| parse_error(e.to_string()))?
+    } else {
+        match serde_json::from_str(&contents).map_err(|e| parse_error(e.to_string()))? {
+            LabelFile::Rows(rows) => rows,
+            LabelFile::Map(entries) => entries
+                .into_iter()
+                .map(|(address, entry)| match entry {
+                    LabelEntry::Name(name) => LabelRow {
+                        address,
+                        name,
+                        category: None,
+                    },
+                    LabelEntry::Label(label) => LabelRow {
+                        address,
+                        name: label.name,
+                        category: label.category,
+                    },
+                })
+                .collect(),
+        }
+    };
+
+    let mut labels = BTreeMap::new();
+    for row in rows {
+        if !is_address(&row.address) {
+            return Err(parse_error(format!(
+                "{:?} is not a Solana address",
+                row.address
+            )));
+        }
+
+        let category = row.category.filter(|category| !category.is_empty());
+        labels.insert(
+            row.address,
+            Label {
+                name: row.name,
+                category,
+            },
+        );
+    }
+
+    Ok(labels)
+}
+
+/// Save the book next to `path` and rename it into place, so a failed or interrupted write
+/// leaves the previous file intact
+async fn write_labels(path: &Path, labels: &BTreeMap<String, Label>) -> Result<(), LabelError> {
+    let write_error = |reason: String| LabelError::Write {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    let contents = if is_csv(path) {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer
+            .write_record(["address", "name", "category"])
+            .map_err(|e| write_error(e.to_string()))?;
+        for (address, label) in labels {
+            writer
+                .write_record([
+                    address.as_str(),
+                    label.name.as_str(),
+                    label.category.as_deref().unwrap_or_default(),
+                ])
+                .map_err(|e| write_error(e.to_string()))?;
+        }
+        let rows = writer
+            .into_inner()
+            .map_err(|e| write_error(e.to_string()))?;
+        String::from_utf8_lossy(&rows).into_owned()
+    } else {
+        serde_json::to_string_pretty(labels).map_err(|e| write_error(e.to_string()))? + "\n"
+    };
+
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    tokio::fs::write(&temp_path, contents)
+        .await
+        .map_err(|e| write_error(e.to_string()))?;
+    if let Err(e) = tokio::fs::rename(&temp_path, path).await {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(write_error(e.to_string()));
+    }
+
+    Ok(())
+}
@@ -1,4 +1,13 @@
+mod analytics;
 mod api;
+mod cache;
+pub mod config;
+mod enrich;
+pub mod labels;
+mod output;
+mod policy;
+mod rate_limit;
 mod requests;
+mod schema;
 
 pub use api::SolscanApi;
@@ -0,0 +1,175 @@
+use serde_json::{json, Map, Value};
+
+use super::{escape_pointer, main_list_pointer};
+
+/// Limits applied to a tool result before it is returned to the model
+#[derive(Debug, Clone, Default)]
+pub struct Budget {
+    pub max_items: Option<usize>,
+    pub max_bytes: Option<usize>,
+    /// Items of the main list to skip, used to fetch the rest of a trimmed page
+    pub item_offset: usize,
+}
+
+impl Budget {
+    fn is_unlimited(&self) -> bool {
+        self.max_items.is_none() && self.max_bytes.is_none() && self.item_offset == 0
+    }
+}
+
+/// Trim `value` to fit the budget.
+///
+/// The main list of the response (`data`, or the longest array directly under `data`) is
+/// trimmed first; if the response is still too large, the largest nested arrays are halved
+/// until it fits. Returns a report of what was omitted, `None` when nothing was cut.
+pub fn apply(value: &mut Value, budget: &Budget) -> Option<Value> {
+    if budget.is_unlimited() {
+        return None;
+    }
+
+    let mut report = Map::new();
+    let main_list = main_list_pointer(value);
+
+    if let Some(pointer) = &main_list {
+        if let Some(list) = trim_main_list(value, pointer, budget) {
+            report.insert("list".to_string(), list);
+        }
+    }
+
+    if let Some(max_bytes) = budget.max_bytes {
+        let mut nested = Vec::new();
+
+        while serialized_len(value) > max_bytes {
+            let Some((pointer, len)) = largest_array(value, main_list.as_deref()) else {
+                break;
+            };
+
+            let kept = len / 2;
+            if let Some(Value::Array(items)) = value.pointer_mut(&pointer) {
+                items.truncate(kept);
+            }
+
+            nested.push(json!({
+                "path": pointer,
+                "total_items": len,
+                "returned_items": kept,
+            }));
+        }
+
+        if !nested.is_empty() {
+            report.insert("nested".to_string(), Value::Array(nested));
+        }
+
+        let size = serialized_len(value);
+        if size > max_bytes {
+            report.insert(
+                "over_budget".to_string(),
+                json!({ "bytes": size, "max_response_bytes": max_bytes }),
+            );
+        }
+    }
+
+    if report.is_empty() {
+        None
+    } else {
+        Some(Value::Object(report))
+    }
+}
+
+/// Skip `item_offset` items, then cut the main list to `max_items` and to what fits in
+/// `max_bytes`, keeping at least one item so nested trimming can take over
+fn trim_main_list(value: &mut Value, pointer: &str, budget: &Budget) -> Option<Value> {
+    let Some(Value::Array(list)) = value.pointer_mut(pointer) else {
+        return None;
+    };
+
+    let total = list.len();
+    let offset = budget.item_offset.min(total);
+    let mut items: Vec<Value> = list.drain(..).skip(offset).collect();
+
+    if let Some(max_items) = budget.max_items {
+        items.truncate(max_items);
+    }
+
+    let kept = match budget.max_bytes {
+        Some(max_bytes) if !items.is_empty() => {
+            // Binary search the largest prefix that fits
+            let (mut low, mut high) = (1, items.len());
+            while low < high {
+                let mid = (low + high).div_ceil(2);
+                set_list(value, pointer, items[..mid].to_vec());
+                if serialized_len(value) <= max_bytes {
+                    low = mid;
+                } else {
+                    high = mid - 1;
+                }
+            }
+            low
+        }
+        _ => items.len(),
+    };
+
+    items.truncate(kept);
+    set_list(value, pointer, items);
+
+    let remaining = total - offset - kept;
+    if offset == 0 && remaining == 0 {
+        return None;
+    }
+
+    let mut list = json!({
+        "path": pointer,
+        "total_items": total,
+        "skipped_items": offset,
+        "returned_items": kept,
+        "omitted_items": remaining,
+    });
+
+    if remaining > 0 {
+        list["next_item_offset"] = json!(offset + kept);
+    }
+
+    Some(list)
+}
+
+fn set_list(value: &mut Value, pointer: &str, items: Vec<Value>) {
+    if let Some(list) = value.pointer_mut(pointer) {
+        *list = Value::Array(items);
+    }
+}
+
+/// The non-empty array with the largest serialized size, skipping the main list
+fn largest_array(value: &Value, skip: Option<&str>) -> Option<(String, usize)> {
+    let mut arrays = Vec::new();
+    collect_arrays(value, String::new(), &mut arrays);
+
+    arrays
+        .into_iter()
+        .filter(|(pointer, len, _)| *len > 0 && Some(pointer.as_str()) != skip)
+        .max_by_key(|(_, _, size)| *size)
+        .map(|(pointer, len, _)| (pointer, len))
+}
+
+/// Collect every array in `value` as (pointer, length, serialized size)
+fn collect_arrays(value: &Value, pointer: String, arrays: &mut Vec<(String, usize, usize)>) {
+    match value {
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                collect_arrays(item, format!("{}/{}", pointer, index), arrays);
+            }
+            arrays.push((pointer, items.len(), serialized_len(value)));
+        }
+        Value::Object(map) => {
+            for (key, item) in map {
+                collect_arrays(item, format!("{}/{}", pointer, escape_pointer(key)), arrays);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn serialized_len(value: &Value) -> usize {
+    serde_json::to_vec(value)
+        .map(|bytes| bytes.len())
+        .unwrap_or(0)
+}
@@ -0,0 +1,383 @@
+use std::collections::{BTreeSet, HashMap};
+
+use rmcp::{model::Content, Error as McpError};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use super::main_list_pointer;
+use super::normalize::normalize;
+
+/// How tool results are rendered for the model
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Solscan's JSON as is
+    #[default]
+    Json,
+    /// JSON with abbreviated keys and a legend, nulls dropped
+    Compact,
+    /// Markdown table for lists, key/value table otherwise
+    Markdown,
+    /// CSV of the main list
+    Csv,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_ascii_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "compact" => Ok(Self::Compact),
+            "markdown" | "md" => Ok(Self::Markdown),
+            "csv" => Ok(Self::Csv),
+            _ => Err(format!(
+                "unknown output format {:?}, expected json, compact, markdown or csv",
+                format
+            )),
+        }
+    }
+}
+
+/// Render a processed tool result in the requested format
+pub fn render(mut value: Value, format: OutputFormat) -> Result<Vec<Content>, McpError> {
+    // Raw JSON stays exactly as Solscan returned it
+    if format != OutputFormat::Json {
+        normalize(&mut value);
+    }
+
+    match format {
+        OutputFormat::Json => Ok(vec![Content::json(value)?]),
+        OutputFormat::Compact => Ok(vec![Content::json(to_compact(value))?]),
+        OutputFormat::Markdown => Ok(vec![Content::text(to_markdown(value))]),
+        OutputFormat::Csv => {
+            let Rows { rows, rest, .. } = split_rows(value);
+            let mut contents = vec![Content::text(to_csv(&rows)?)];
+            if let Some(rest) = rest {
+                contents.push(Content::json(rest)?);
+            }
+            Ok(contents)
+        }
+    }
+}
+
+/// Rows of a response: the items of its main list, or a single row for an object
+struct Rows {
+    rows: Vec<Map<String, Value>>,
+    is_list: bool,
+    /// Whatever else is worth keeping, such as totals and truncation notes
+    rest: Option<Value>,
+}
+
+fn split_rows(mut value: Value) -> Rows {
+    if let Value::Object(map) = &mut value {
+        // `success: true` carries no information once we have a result
+        if map.get("success") == Some(&Value::Bool(true)) {
+            map.remove("success");
+        }
+    }
+
+    let list = main_list_pointer(&value).and_then(|pointer| match pointer.as_str() {
+        "" => Some(value.take()),
+        pointer => value.pointer_mut(pointer).map(Value::take),
+    });
+
+    let (rows, is_list) = match list {
+        Some(Value::Array(items)) => (items.into_iter().map(flatten_row).collect(), true),
+        _ => {
+            let row = match value.get_mut("data") {
+                Some(data) => data.take(),
+                None => value.take(),
+            };
+            (vec![flatten_row(row)], false)
+        }
+    };
+
+    Rows {
+        rows,
+        is_list,
+        rest: prune(value),
+    }
+}
+
+/// Drop emptied containers left behind after the rows were taken out
+fn prune(value: Value) -> Option<Value> {
+    match value {
+        Value::Null => None,
+        Value::Object(map) => {
+            let map: Map<String, Value> = map
+                .into_iter()
+                .filter_map(|(key, value)| prune(value).map(|value| (key, value)))
+                .collect();
+            (!map.is_empty()).then_some(Value::Object(map))
+        }
+        Value::Array(items) if items.is_empty() => None,
+        value => Some(value),
+    }
+}
+
+/// Flatten nested objects into dotted column names; arrays become compact JSON cells
+fn flatten_row(value: Value) -> Map<String, Value> {
+    let mut row = Map::new();
+    match value {
+        Value::Object(map) => flatten_into(&mut row, "", map),
+        value => {
+            row.insert("value".to_string(), value);
+        }
+    }
+    row
+}
+
+fn flatten_into(row: &mut Map<String, Value>, prefix: &str, map: Map<String, Value>) {
+    for (key, value) in map {
+        let key = match prefix {
+            "" => key,
+            prefix => format!("{}.{}", prefix, key),
+        };
+
+        match value {
+            Value::Object(nested) => flatten_into(row, &key, nested),
+            value => {
+                row.insert(key, value);
+            }
+        }
+    }
+}
+
+fn columns(rows: &[Map<String, Value>]) -> Vec<String> {
+    let mut seen = BTreeSet::new();
+    let mut columns = Vec::new();
+
+    for row in rows {
+        for key in row.keys() {
+            if seen.insert(key.clone()) {
+                columns.push(key.clone());
+            }
+        }
+    }
+
+    columns
+}
+
+fn cell(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(text)) => text.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
+fn to_csv(rows: &[Map<String, Value>]) -> Result<String, McpError> {
+    let columns = columns(rows);
+    let mut writer = csv::Writer::from_writer(Vec::new());
+
+    let csv_error =
+        |e: csv::Error| McpError::internal_error(format!("Failed to write CSV: {}", e), None);
+
+    writer.write_record(&columns).map_err(csv_error)?;
+    for row in rows {
+        writer
+            .write_record(columns.iter().map(|column| cell(row.get(column))))
+            .map_err(csv_error)?;
+    }
+
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| McpError::internal_error(format!("Failed to write CSV: {}", e), None))?;
+
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn to_markdown(value: Value) -> String {
+    let Rows {
+        rows,
+        is_list,
+        rest,
+    } = split_rows(value);
+    let mut output = String::new();
+
+    if !is_list {
+        // Single object: two-column key/value table
+        output.push_str("| field | value |\n| --- | --- |\n");
+        for (key, value) in &rows[0] {
+            output.push_str(&format!(
+                "| {} | {} |\n",
+                escape_markdown(key),
+                escape_markdown(&cell(Some(value)))
+            ));
+        }
+    } else if rows.is_empty() {
+        output.push_str("_No results_\n");
+    } else {
+        let columns = columns(&rows);
+        output.push_str(&format!(
+            "| {} |\n",
+            columns
+                .iter()
+                .map(|column| escape_markdown(column))
+                .collect::<Vec<_>>()
+                .join(" | ")
+        ));
+        output.push_str(&format!("|{}\n", " --- |".repeat(columns.len())));
+
+        for row in &rows {
+            output.push_str(&format!(
+                "| {} |\n",
+                columns
+                    .iter()
+                    .map(|column| escape_markdown(&cell(row.get(column))))
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            ));
+        }
+    }
+
+    if let Some(mut rest) = rest {
+        output.push('\n');
+
+        if let Some(truncated) = rest
+            .as_object_mut()
+            .and_then(|rest| rest.remove("truncated"))
+        {
+            output.push_str(&truncation_note(&truncated));
+        }
+
+        for (key, value) in flatten_row(rest) {
+            output.push_str(&format!("- **{}**: {}\n", key, cell(Some(&value))));
+        }
+    }
+
+    output
+}
+
+/// One-line summary of the budget report, with the call that fetches the rest
+fn truncation_note(truncated: &Value) -> String {
+    let mut note = String::new();
+
+    if let Some(list) = truncated.get("list") {
+        note.push_str(&format!(
+            "_Showing {} of {} items ({} omitted)._",
+            cell(list.get("returned_items")),
+            cell(list.get("total_items")),
+            cell(list.get("omitted_items")),
+        ));
+    }
+
+    if let Some(nested) = truncated.get("nested").and_then(Value::as_array) {
+        note.push_str(&format!(" _{} nested lists were shortened._", nested.len()));
+    }
+
+    if let Some(next_call) = truncated.get("next_call") {
+        note.push_str(&format!(
+            " Next: `{}` with `{}`",
+            cell(next_call.get("tool")),
+            cell(next_call.get("arguments"))
+        ));
+    }
+
+    format!("{}\n", note.trim_start())
+}
+
+/// Abbreviate the keys of the response payload and add their legend under `keys`; top-level
+/// keys, the truncation report and the address labels keep their names so follow-up calls
+/// stay usable
+fn to_compact(value: Value) -> Value {
+    let mut legend = Legend::default();
+
+    let compact = match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(key, value)| key != "success" && !value.is_null())
+                .map(|(key, value)| match key.as_str() {
+                    "truncated" | "labels" => (key, value),
+                    _ => {
+                        let value = legend.abbreviate(value);
+                        (key, value)
+                    }
+                })
+                .collect(),
+        ),
+        value => legend.abbreviate(value),
+    };
+
+    json!({
+        "keys": legend.into_value(),
+        "result": compact,
+    })
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('|', "\\|").replace(['\n', '\r'], " ")
+}
+
+/// Short key names for the compact format, e.g. `block_time` -> `bt`
+#[derive(Default)]
+struct Legend {
+    short: HashMap<String, String>,
+    used: BTreeSet<String>,
+}
+
+impl Legend {
+    fn abbreviate(&mut self, value: Value) -> Value {
+        match value {
+            Value::Object(map) => Value::Object(
+                map.into_iter()
+                    .filter(|(_, value)| !value.is_null())
+                    .map(|(key, value)| (self.short_key(&key), self.abbreviate(value)))
+                    .collect(),
+            ),
+            Value::Array(items) => Value::Array(
+                items
+                    .into_iter()
+                    .map(|item| self.abbreviate(item))
+                    .collect(),
+            ),
+            value => value,
+        }
+    }
+
+    fn short_key(&mut self, key: &str) -> String {
+        if let Some(short) = self.short.get(key) {
+            return short.clone();
+        }
+
+        // Initials of snake_case and camelCase words; short keys stay as they are
+        let mut base: String = key
+            .split('_')
+            .filter(|word| !word.is_empty())
+            .flat_map(|word| {
+                word.chars().take(1).chain(
+                    word.chars()
+                        .skip(1)
+                        .filter(|c| c.is_ascii_uppercase() || c.is_ascii_digit()),
+                )
+            })
+            .collect::<String>()
+            .to_ascii_lowercase();
+
+        if key.len() <= 4 || base.is_empty() {
+            base = key.to_string();
+        }
+
+        let mut short = base.clone();
+        let mut suffix = 2;
+        while self.used.contains(&short) {
+            short = format!("{}{}", base, suffix);
+            suffix += 1;
+        }
+
+        self.used.insert(short.clone());
+        self.short.insert(key.to_string(), short.clone());
+        short
+    }
+
+    fn into_value(self) -> Value {
+        Value::Object(
+            self.short
+                .into_iter()
+                .filter(|(key, short)| key != short)
+                .map(|(key, short)| (short, Value::String(key)))
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,252 @@
+mod budget;
+mod format;
+mod normalize;
+mod select;
+
+use rmcp::{
+    model::{Content, JsonObject, Tool},
+    Error as McpError,
+};
+use serde_json::{json, Value};
+
+use crate::solscan_mcp::config::OutputConfig;
+use crate::solscan_mcp::schema::add_argument;
+
+use budget::Budget;
+use select::Selection;
+
+pub use format::OutputFormat;
+pub use normalize::format_units;
+
+// Arguments handled by the output layer instead of the tools themselves
+const MAX_ITEMS_ARGUMENT: &str = "max_items";
+const MAX_RESPONSE_BYTES_ARGUMENT: &str = "max_response_bytes";
+const ITEM_OFFSET_ARGUMENT: &str = "item_offset";
+const FIELDS_ARGUMENT: &str = "fields";
+const OUTPUT_FORMAT_ARGUMENT: &str = "output_format";
+
+/// Per-call output settings, merged with the `[output]` config section
+#[derive(Debug, Clone)]
+pub struct OutputOptions {
+    selection: Option<Selection>,
+    budget: Budget,
+    format: OutputFormat,
+    /// Arguments of the call as sent by the client, used to describe follow-up calls
+    arguments: Option<JsonObject>,
+}
+
+impl OutputOptions {
+    /// Remove the output arguments from a tool call and merge them with the configured
+    /// limits; the configured limits act as a cap on per-call values.
+    pub fn take(
+        arguments: &mut Option<JsonObject>,
+        config: &OutputConfig,
+    ) -> Result<Self, McpError> {
+        let original = arguments.clone();
+
+        let max_items = take_usize(arguments, MAX_ITEMS_ARGUMENT)?;
+        let max_bytes = take_usize(arguments, MAX_RESPONSE_BYTES_ARGUMENT)?;
+        let item_offset = take_usize(arguments, ITEM_OFFSET_ARGUMENT)?;
+        let selection = take_selection(arguments)?;
+        let format = take_format(arguments)?.unwrap_or(config.format);
+
+        Ok(Self {
+            selection,
+            format,
+            budget: Budget {
+                max_items: min_limit(max_items, config.max_items),
+                max_bytes: min_limit(max_bytes, config.max_response_bytes),
+                item_offset: item_offset.unwrap_or(0),
+            },
+            arguments: original,
+        })
+    }
+
+    /// Advertise the output arguments in a tool's input schema
+    pub fn describe(tool: Tool) -> Tool {
+        let tool = add_argument(
+            tool,
+            MAX_ITEMS_ARGUMENT,
+            json!({
+                "type": "integer",
+                "minimum": 0,
+                "description": "Maximum number of list items to return",
+            }),
+        );
+        let tool = add_argument(
+            tool,
+            MAX_RESPONSE_BYTES_ARGUMENT,
+            json!({
+                "type": "integer",
+                "minimum": 0,
+                "description": "Maximum size of the response in bytes; lists are trimmed to fit",
+            }),
+        );
+        let tool = add_argument(
+            tool,
+            FIELDS_ARGUMENT,
+            json!({
+                "type": ["string", "array"],
+                "items": { "type": "string" },
+                "description": "Only return these fields, as comma-separated paths relative to each list item \
+                    (e.g. \"trans_id,amount,from_address\"); use [*] to iterate arrays and a leading $. for paths from the response root",
+            }),
+        );
+        let tool = add_argument(
+            tool,
+            OUTPUT_FORMAT_ARGUMENT,
+            json!({
+                "type": "string",
+                "enum": ["json", "compact", "markdown", "csv"],
+                "description": "Output format; markdown, csv and compact normalize token amounts by their decimals and render timestamps as UTC dates",
+            }),
+        );
+        add_argument(
+            tool,
+            ITEM_OFFSET_ARGUMENT,
+            json!({
+                "type": "integer",
+                "minimum": 0,
+                "description": "Number of list items to skip, use the `next_call` of a trimmed response to continue",
+            }),
+        )
+    }
+
+    /// Render a processed tool result in the requested format
+    pub fn render(&self, value: Value) -> Result<Vec<Content>, McpError> {
+        format::render(value, self.format)
+    }
+
+    /// Keep only the requested fields of a tool result
+    pub fn select(&self, value: &mut Value) {
+        if let Some(selection) = &self.selection {
+            selection.apply(value);
+        }
+    }
+
+    /// Trim a tool result to the budget and report what was cut under `truncated`. An array
+    /// result is wrapped as `{"data": [...]}` when trimmed so the report has somewhere to go.
+    pub fn trim(&self, tool: &str, value: &mut Value) {
+        let is_array = value.is_array();
+        if is_array {
+            *value = json!({ "data": value.take() });
+        }
+
+        let Some(mut report) = budget::apply(value, &self.budget) else {
+            if is_array {
+                *value = value["data"].take();
+            }
+            return;
+        };
+
+        // Describe how to fetch the items that did not fit
+        if let Some(next_offset) = report.pointer("/list/next_item_offset").cloned() {
+            let mut arguments = self.arguments.clone().unwrap_or_default();
+            arguments.insert(ITEM_OFFSET_ARGUMENT.to_string(), next_offset);
+            report["next_call"] = json!({
+                "tool": tool,
+                "arguments": arguments,
+            });
+        }
+
+        if let Value::Object(map) = value {
+            map.insert("truncated".to_string(), report);
+        }
+    }
+}
+
+fn take_usize(arguments: &mut Option<JsonObject>, name: &str) -> Result<Option<usize>, McpError> {
+    let Some(value) = arguments
+        .as_mut()
+        .and_then(|arguments| arguments.remove(name))
+    else {
+        return Ok(None);
+    };
+
+    match value {
+        Value::Null => Ok(None),
+        value => value
+            .as_u64()
+            .map(|value| Some(value as usize))
+            .ok_or_else(|| {
+                McpError::invalid_params(
+                    format!("{} must be a non-negative integer", name),
+                    Some(json!({ "value": value })),
+                )
+            }),
+    }
+}
+
+fn take_selection(arguments: &mut Option<JsonObject>) -> Result<Option<Selection>, McpError> {
+    let Some(value) = arguments
+        .as_mut()
+        .and_then(|arguments| arguments.remove(FIELDS_ARGUMENT))
+    else {
+        return Ok(None);
+    };
+
+    let selection = match &value {
+        Value::Null => return Ok(None),
+        Value::String(fields) => Selection::parse(fields.split(',')),
+        Value::Array(fields) => {
+            match fields.iter().map(Value::as_str).collect::<Option<Vec<_>>>() {
+                Some(fields) => Selection::parse(fields),
+                None => Err("fields must be strings".to_string()),
+            }
+        }
+        _ => Err("fields must be a string or an array of strings".to_string()),
+    };
+
+    selection
+        .map(Some)
+        .map_err(|e| McpError::invalid_params(e, Some(json!({ "fields": value }))))
+}
+
+fn take_format(arguments: &mut Option<JsonObject>) -> Result<Option<OutputFormat>, McpError> {
+    let Some(value) = arguments
+        .as_mut()
+        .and_then(|arguments| arguments.remove(OUTPUT_FORMAT_ARGUMENT))
+    else {
+        return Ok(None);
+    };
+
+    match &value {
+        Value::Null => Ok(None),
+        Value::String(format) => format
+            .parse()
+            .map(Some)
+            .map_err(|e: String| McpError::invalid_params(e, None)),
+        _ => Err(McpError::invalid_params(
+            "output_format must be a string",
+            Some(json!({ "output_format": value })),
+        )),
+    }
+}
+
+fn min_limit(requested: Option<usize>, configured: Option<usize>) -> Option<usize> {
+    match (requested, configured) {
+        (Some(requested), Some(configured)) => Some(requested.min(configured)),
+        (requested, configured) => requested.or(configured),
+    }
+}
+
+/// JSON pointer of the list a paginated Solscan response is about
+fn main_list_pointer(value: &Value) -> Option<String> {
+    if value.is_array() {
+        return Some(String::new());
+    }
+
+    match value.get("data")? {
+        Value::Array(_) => Some("/data".to_string()),
+        Value::Object(data) => data
+            .iter()
+            .filter_map(|(key, value)| value.as_array().map(|items| (key, items.len())))
+            .max_by_key(|(_, len)| *len)
+            .map(|(key, _)| format!("/data/{}", escape_pointer(key))),
+        _ => None,
+    }
+}
+
+fn escape_pointer(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
@@ -0,0 +1,116 @@
+use chrono::DateTime;
+use serde_json::{Map, Value};
+
+use crate::solscan_mcp::enrich::as_u32;
+
+// Raw amount fields scaled by the `token_decimals` / `decimals` field of the same object
+const AMOUNT_KEYS: &[&str] = &["amount", "pre_balance", "post_balance", "change_amount"];
+
+// Unix timestamps outside this range (2000-01-01 to 2100-01-01) are left untouched
+const MIN_TIMESTAMP: i64 = 946_684_800;
+const MAX_TIMESTAMP: i64 = 4_102_444_800;
+
+/// Rewrite raw token amounts as decimal strings and Unix timestamps as UTC dates, for the
+/// human-oriented output formats
+pub fn normalize(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            normalize_amounts(map);
+            normalize_timestamps(map);
+            map.values_mut().for_each(normalize);
+        }
+        Value::Array(items) => items.iter_mut().for_each(normalize),
+        _ => {}
+    }
+}
+
+fn normalize_amounts(map: &mut Map<String, Value>) {
+    let decimals = map
+        .get("token_decimals")
+        .or_else(|| map.get("decimals"))
+        .and_then(as_u32);
+
+    if let Some(decimals) = decimals {
+        for key in AMOUNT_KEYS {
+            scale(map, key, decimals);
+        }
+    }
+
+    // DeFi routers carry numbered legs: `amount1` with `token1_decimals`, ...
+    for leg in 1..=4 {
+        if let Some(decimals) = map.get(&format!("token{}_decimals", leg)).and_then(as_u32) {
+            scale(map, &format!("amount{}", leg), decimals);
+        }
+    }
+}
+
+fn scale(map: &mut Map<String, Value>, key: &str, decimals: u32) {
+    let Some(raw) = map.get(key).and_then(raw_integer) else {
+        return;
+    };
+
+    if let Some(formatted) = format_units(&raw, decimals) {
+        map.insert(key.to_string(), Value::String(formatted));
+    }
+}
+
+fn normalize_timestamps(map: &mut Map<String, Value>) {
+    for (key, value) in map.iter_mut() {
+        if !is_time_key(key) {
+            continue;
+        }
+
+        if let Some(formatted) = value
+            .as_i64()
+            .filter(|secs| (MIN_TIMESTAMP..MAX_TIMESTAMP).contains(secs))
+            .and_then(format_timestamp)
+        {
+            *value = Value::String(formatted);
+        }
+    }
+}
+
+fn is_time_key(key: &str) -> bool {
+    key == "time" || key.ends_with("_time") || key.ends_with("Time")
+}
+
+/// Render a Unix timestamp as `YYYY-MM-DD HH:MM:SS UTC`
+pub fn format_timestamp(secs: i64) -> Option<String> {
+    DateTime::from_timestamp(secs, 0).map(|time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+}
+
+/// Shift a raw integer amount by `decimals` places without going through floats,
+/// e.g. ("1500000", 6) -> "1.5"
+pub fn format_units(raw: &str, decimals: u32) -> Option<String> {
+    let (negative, digits) = match raw.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, raw),
+    };
+
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let decimals = decimals as usize;
+    let padded = format!("{:0>width$}", digits, width = decimals + 1);
+    let (whole, fraction) = padded.split_at(padded.len() - decimals);
+
+    let whole = whole.trim_start_matches('0');
+    let whole = if whole.is_empty() { "0" } else { whole };
+    let fraction = fraction.trim_end_matches('0');
+
+    let sign = if negative { "-" } else { "" };
+    Some(match fraction.is_empty() {
+        true => format!("{}{}", sign, whole),
+        false => format!("{}{}.{}", sign, whole, fraction),
+    })
+}
+
+/// Integer amounts come as JSON numbers or numeric strings depending on the endpoint
+fn raw_integer(value: &Value) -> Option<String> {
+    match value {
+        Value::Number(number) if number.is_i64() || number.is_u64() => Some(number.to_string()),
+        Value::String(text) if !text.contains('.') => Some(text.clone()),
+        _ => None,
+    }
+}
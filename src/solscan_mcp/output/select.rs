@@ -0,0 +1,252 @@
+use std::collections::BTreeMap;
+
+use serde_json::{Map, Value};
+
+use super::main_list_pointer;
+
+/// Field projection parsed from the `fields` argument.
+///
+/// Each path is a dot-separated list of keys, with `[*]` (or `[]`) to iterate an array,
+/// `[n]` to pick one element and `*` to match every key of an object, e.g.
+/// `block_time,from_address,routers.token1`. Paths are relative to every item of the
+/// response's main list (or to `data` when there is no list); a leading `$.` makes a path
+/// absolute, e.g. `$.data.total`. Keys applied to an array match inside each element.
+#[derive(Debug, Clone, Default)]
+pub struct Selection {
+    relative: Node,
+    absolute: Node,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Segment {
+    Key(String),
+    Index(usize),
+    Any,
+}
+
+/// Trie of the selected paths; a node that ends a path keeps its whole subtree
+#[derive(Debug, Clone, Default)]
+struct Node {
+    end: bool,
+    children: BTreeMap<Segment, Node>,
+}
+
+impl Selection {
+    pub fn parse<'a>(paths: impl IntoIterator<Item = &'a str>) -> Result<Self, String> {
+        let mut selection = Self::default();
+
+        for path in paths
+            .into_iter()
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+        {
+            let (root, path) = match path.strip_prefix('$') {
+                Some(rest) => (&mut selection.absolute, rest.trim_start_matches('.')),
+                None => (&mut selection.relative, path),
+            };
+
+            let mut node = root;
+            for segment in parse_path(path)? {
+                node = node.children.entry(segment).or_default();
+            }
+            node.end = true;
+        }
+
+        Ok(selection)
+    }
+
+    pub fn apply(&self, value: &mut Value) {
+        let mut selected = match self.absolute.is_empty() {
+            true => None,
+            false => Some(project(value, &self.absolute).unwrap_or(Value::Null)),
+        };
+
+        if !self.relative.is_empty() {
+            let pointer = main_list_pointer(value);
+            let base = match &pointer {
+                Some(pointer) => value.pointer(pointer),
+                None => value.get("data"),
+            };
+
+            let projected = match (&pointer, base) {
+                (Some(_), Some(Value::Array(items))) => Value::Array(
+                    items
+                        .iter()
+                        .map(|item| project(item, &self.relative).unwrap_or(Value::Null))
+                        .collect(),
+                ),
+                (_, Some(base)) => project(base, &self.relative).unwrap_or(Value::Null),
+                (_, None) => project(value, &self.relative).unwrap_or(Value::Null),
+            };
+
+            // Keep the surrounding response (counts, totals, success flag) around the
+            // projected list unless absolute paths already define the whole shape
+            let target = selected.get_or_insert_with(|| value.clone());
+            let pointer = pointer.or_else(|| value.get("data").map(|_| "/data".to_string()));
+            match pointer {
+                Some(pointer) if !pointer.is_empty() => {
+                    insert_at(target, &pointer, projected);
+                }
+                _ => *target = projected,
+            }
+        }
+
+        if let Some(selected) = selected {
+            *value = selected;
+        }
+    }
+}
+
+impl Node {
+    fn is_empty(&self) -> bool {
+        !self.end && self.children.is_empty()
+    }
+}
+
+fn parse_path(path: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+
+    for part in path.split('.') {
+        let (key, mut rest) = match part.find('[') {
+            Some(index) => part.split_at(index),
+            None => (part, ""),
+        };
+
+        match key {
+            "" if rest.is_empty() => return Err(format!("empty segment in path {:?}", path)),
+            "" => {}
+            "*" => segments.push(Segment::Any),
+            key => segments.push(Segment::Key(key.to_string())),
+        }
+
+        while !rest.is_empty() {
+            let end = rest
+                .find(']')
+                .filter(|_| rest.starts_with('['))
+                .ok_or_else(|| format!("unbalanced brackets in path {:?}", path))?;
+
+            let segment = match &rest[1..end] {
+                "" | "*" => Segment::Any,
+                index => Segment::Index(
+                    index
+                        .parse()
+                        .map_err(|_| format!("invalid index {:?} in path {:?}", index, path))?,
+                ),
+            };
+
+            segments.push(segment);
+            rest = &rest[end + 1..];
+        }
+    }
+
+    Ok(segments)
+}
+
+/// Keep only the selected parts of `value`, `None` when nothing matches
+fn project(value: &Value, node: &Node) -> Option<Value> {
+    if node.end {
+        return Some(value.clone());
+    }
+
+    match value {
+        Value::Object(map) => {
+            let mut projected = Map::new();
+
+            for (segment, child) in &node.children {
+                match segment {
+                    Segment::Key(key) => {
+                        if let Some(selected) = map.get(key).and_then(|v| project(v, child)) {
+                            merge_into(&mut projected, key, selected);
+                        }
+                    }
+                    Segment::Any => {
+                        for (key, item) in map {
+                            if let Some(selected) = project(item, child) {
+                                merge_into(&mut projected, key, selected);
+                            }
+                        }
+                    }
+                    Segment::Index(_) => {}
+                }
+            }
+
+            (!projected.is_empty()).then_some(Value::Object(projected))
+        }
+        Value::Array(items) => {
+            let mut projected = Vec::new();
+
+            for (index, item) in items.iter().enumerate() {
+                let mut selected: Option<Value> = None;
+
+                for (segment, child) in &node.children {
+                    let matched = match segment {
+                        Segment::Any => project(item, child),
+                        Segment::Index(wanted) if *wanted == index => project(item, child),
+                        Segment::Index(_) => None,
+                        // Keys look inside each element of the array
+                        Segment::Key(_) => project(
+                            item,
+                            &Node {
+                                end: false,
+                                children: BTreeMap::from([(segment.clone(), child.clone())]),
+                            },
+                        ),
+                    };
+
+                    if let Some(matched) = matched {
+                        selected = Some(match selected {
+                            Some(Value::Object(mut current)) => {
+                                if let Value::Object(extra) = matched {
+                                    for (key, value) in extra {
+                                        merge_into(&mut current, &key, value);
+                                    }
+                                }
+                                Value::Object(current)
+                            }
+                            _ => matched,
+                        });
+                    }
+                }
+
+                if let Some(selected) = selected {
+                    projected.push(selected);
+                }
+            }
+
+            (!projected.is_empty()).then_some(Value::Array(projected))
+        }
+        _ => None,
+    }
+}
+
+/// Insert `value` under `key`, merging objects selected by overlapping paths
+fn merge_into(map: &mut Map<String, Value>, key: &str, value: Value) {
+    match (map.get_mut(key), value) {
+        (Some(Value::Object(existing)), Value::Object(extra)) => {
+            for (key, value) in extra {
+                merge_into(existing, &key, value);
+            }
+        }
+        (_, value) => {
+            map.insert(key.to_string(), value);
+        }
+    }
+}
+
+/// Replace the value at `pointer` in `target`, creating intermediate objects as needed
+fn insert_at(target: &mut Value, pointer: &str, value: Value) {
+    let mut current = target;
+
+    for key in pointer.trim_start_matches('/').split('/') {
+        let key = key.replace("~1", "/").replace("~0", "~");
+        if !current.is_object() {
+            *current = Value::Object(Map::new());
+        }
+        let Value::Object(map) = current else {
+            return;
+        };
+        current = map.entry(key).or_insert(Value::Null);
+    }
+
+    *current = value;
+}
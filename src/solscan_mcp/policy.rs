@@ -0,0 +1,251 @@
+use std::collections::{HashMap, HashSet};
+
+use rmcp::model::{JsonObject, Tool};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::solscan_mcp::config::Config;
+use crate::solscan_mcp::schema::add_argument;
+
+// Argument the caller passes to run an expensive tool for real
+const CONFIRM_ARGUMENT: &str = "confirm";
+
+// Credit-expensive endpoints, priced like the tool that exposes each of them
+const EXPENSIVE_ENDPOINTS: &[(&str, &str)] = &[
+    ("/account/transfer/export", "account_transfer_export"),
+    ("/account/reward/export", "account_reward_export"),
+];
+
+// Stake accounts whose rewards `staking_summary` reads, one reward export each
+pub(crate) const DEFAULT_STAKE_ACCOUNTS: usize = 20;
+pub(crate) const MAX_STAKE_ACCOUNTS: usize = 50;
+
+/// Namespaces operators can switch on and off as a whole
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolGroup {
+    Token,
+    Account,
+    Transaction,
+    Block,
+    Market,
+    Nft,
+    Export,
+    Labels,
+}
+
+impl ToolGroup {
+    /// The group a tool belongs to, `None` for names this server does not expose
+    pub fn of(tool: &str) -> Option<Self> {
+        let group = match tool {
+            "token_meta"
+            | "token_meta_multi"
+            | "token_markets"
+            | "token_holders"
+            | "token_list"
+            | "token_top"
+            | "token_trending"
+            | "token_price"
+            | "token_price_multi"
+            | "token_transfer"
+            | "token_defi_activities"
+            | "token_holder_stats"
+            | "token_risk_report"
+            | "token_liquidity"
+            | "token_price_series" => Self::Token,
+            "account_transfer"
+            | "account_detail"
+            | "balance_change"
+            | "account_defi_activities"
+            | "account_transactions"
+            | "account_portfolio"
+            | "account_token_accounts"
+            | "account_stake"
+            | "account_metadata"
+            | "wallet_profile"
+            | "trace_funds"
+            | "transfer_graph"
+            | "account_counterparties"
+            | "cluster_wallets"
+            | "detect_address_poisoning"
+            | "detect_sandwiches"
+            | "wallet_pnl"
+            | "tax_lot_export"
+            | "account_portfolio_history"
+            | "staking_summary" => Self::Account,
+            "transaction_detail"
+            | "transaction_last"
+            | "transaction_actions"
+            | "transaction_explain" => Self::Transaction,
+            "chain_info" | "block_last" | "block_transactions" | "block_detail" => Self::Block,
+            "market_list" | "market_info" | "market_volume" => Self::Market,
+            "nft_news" | "nft_activities" | "nft_collection_lists" | "nft_collection_items" => {
+                Self::Nft
+            }
+            "account_reward_export" | "account_transfer_export" => Self::Export,
+            "label_add" | "label_list" => Self::Labels,
+            _ => return None,
+        };
+
+        Some(group)
+    }
+}
+
+/// Decides which tools are advertised and callable, and which need an explicit confirmation
+#[derive(Debug, Clone)]
+pub struct ToolPolicy {
+    groups: Option<HashSet<ToolGroup>>,
+    allow: HashSet<String>,
+    deny: HashSet<String>,
+    allow_expensive: bool,
+    expensive: HashMap<String, u32>,
+}
+
+impl ToolPolicy {
+    pub fn new(config: &Config) -> Self {
+        let tools = &config.tools;
+        let safety = &config.safety;
+
+        for name in tools
+            .allow
+            .iter()
+            .chain(&tools.deny)
+            .chain(safety.expensive.keys())
+        {
+            if ToolGroup::of(name).is_none() {
+                tracing::warn!("Unknown tool {:?} in the tools configuration", name);
+            }
+        }
+
+        Self {
+            groups: tools
+                .groups
+                .as_ref()
+                .map(|groups| groups.iter().copied().collect()),
+            allow: tools.allow.iter().cloned().collect(),
+            deny: tools.deny.iter().cloned().collect(),
+            allow_expensive: safety.allow_expensive,
+            expensive: safety.expensive.clone(),
+        }
+    }
+
+    /// A tool is enabled unless denied by name, and only if its group is enabled or it is
+    /// allowed by name
+    pub fn is_enabled(&self, tool: &str) -> bool {
+        if self.deny.contains(tool) {
+            return false;
+        }
+
+        if self.allow.contains(tool) {
+            return true;
+        }
+
+        match (&self.groups, ToolGroup::of(tool)) {
+            (None, _) => true,
+            (Some(groups), Some(group)) => groups.contains(&group),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Estimated credit cost of an expensive tool, `None` for regular tools
+    pub fn credit_cost(&self, tool: &str) -> Option<u32> {
+        self.expensive.get(tool).copied()
+    }
+
+    /// Estimated credit cost of an expensive endpoint, `None` for regular endpoints
+    pub fn endpoint_cost(&self, endpoint: &str) -> Option<u32> {
+        EXPENSIVE_ENDPOINTS
+            .iter()
+            .find(|(expensive, _)| *expensive == endpoint)
+            .and_then(|(_, tool)| self.credit_cost(tool))
+    }
+
+    /// Estimated credits of one call of a tool with these arguments: the cost of an expensive
+    /// tool, or of the expensive endpoints a tool calls along the way
+    fn call_cost(&self, tool: &str, arguments: Option<&JsonObject>) -> Option<u32> {
+        let flag = |name: &str| {
+            arguments
+                .and_then(|arguments| arguments.get(name))
+                .and_then(Value::as_bool)
+                .unwrap_or(false)
+        };
+
+        match tool {
+            "tax_lot_export" if flag("include_rewards") => {
+                self.endpoint_cost("/account/reward/export")
+            }
+            // One reward export per stake account read, at most `max_accounts`
+            "staking_summary" => {
+                let accounts = arguments
+                    .and_then(|arguments| arguments.get("max_accounts"))
+                    .and_then(Value::as_u64)
+                    .map_or(DEFAULT_STAKE_ACCOUNTS, |accounts| accounts as usize)
+                    .clamp(1, MAX_STAKE_ACCOUNTS);
+                self.endpoint_cost("/account/reward/export")
+                    .map(|credits| credits.saturating_mul(accounts as u32))
+            }
+            tool => self.credit_cost(tool),
+        }
+    }
+
+    /// Whether a tool may call an expensive endpoint, and so takes a `confirm` argument
+    fn may_be_expensive(&self, tool: &str) -> bool {
+        match tool {
+            "tax_lot_export" | "staking_summary" => {
+                self.endpoint_cost("/account/reward/export").is_some()
+            }
+            tool => self.credit_cost(tool).is_some(),
+        }
+    }
+
+    /// Add the `confirm` argument to the input schema of expensive tools
+    pub fn describe(&self, tool: Tool) -> Tool {
+        if self.allow_expensive || !self.may_be_expensive(&tool.name) {
+            return tool;
+        }
+
+        add_argument(
+            tool,
+            CONFIRM_ARGUMENT,
+            json!({
+                "type": "boolean",
+                "description": "Set to true to run this credit-expensive call; without it a cost estimate is returned instead",
+            }),
+        )
+    }
+
+    /// Check an expensive call and strip its `confirm` argument.
+    ///
+    /// Returns a dry-run estimate when the call is expensive and was not confirmed.
+    /// Otherwise returns whether expensive endpoints may be called while running it.
+    pub fn check_cost(
+        &self,
+        tool: &str,
+        arguments: &mut Option<JsonObject>,
+    ) -> Result<bool, Value> {
+        let confirmed = arguments
+            .as_mut()
+            .and_then(|arguments| arguments.remove(CONFIRM_ARGUMENT))
+            .and_then(|confirm| confirm.as_bool())
+            .unwrap_or(false);
+
+        if self.allow_expensive || confirmed {
+            return Ok(true);
+        }
+        let Some(credits) = self.call_cost(tool, arguments.as_ref()) else {
+            return Ok(false);
+        };
+
+        Err(json!({
+            "dry_run": true,
+            "tool": tool,
+            "estimated_credits": credits,
+            "arguments": arguments,
+            "message": format!(
+                "{} calls a credit-expensive endpoint (about {} credits per call) and was not \
+                 run. Call it again with \"confirm\": true to run it.",
+                tool, credits
+            ),
+        }))
+    }
+}
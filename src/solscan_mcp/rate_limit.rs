@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Spaces outgoing Solscan requests evenly so that at most `requests_per_second` are sent
+pub struct RateLimiter {
+    interval: Option<Duration>,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: Option<u32>) -> Self {
+        Self {
+            interval: requests_per_second
+                .filter(|rps| *rps > 0)
+                .map(|rps| Duration::from_secs(1) / rps),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait until the next request slot is available
+    pub async fn acquire(&self) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+
+        let mut next_slot = self.next_slot.lock().await;
+        let now = Instant::now();
+
+        if *next_slot > now {
+            tokio::time::sleep_until(*next_slot).await;
+        }
+
+        *next_slot = (*next_slot).max(now) + interval;
+    }
+}
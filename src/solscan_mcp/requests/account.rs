@@ -40,26 +40,6 @@ pub struct TokenPriceRequest {
     pub to_time: Option<i64>,
 }
 
-#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
-pub struct TokenAccountsRequest {
-    pub account: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub page: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub page_size: Option<i32>,
-}
-
-#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
-pub struct DefiActivitiesRequest {
-    pub account: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub page: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub page_size: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub before_tx: Option<String>,
-}
-
 #[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
 pub struct BalanceChangeRequest {
     pub account: String,
@@ -198,6 +178,10 @@ pub struct AccountRewardExportRequest {
     pub time_from: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub time_to: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub save_to_file: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
@@ -223,9 +207,187 @@ pub struct AccountTransferExportRequest {
     pub exclude_amount_zero: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub flow: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub counterparty: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_amount: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub save_to_file: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
 pub struct AccountMetadataRequest {
     pub address: String,
 }
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct WalletProfileRequest {
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_n: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct TraceFundsRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub direction: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hops: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_amount: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_nodes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_requests: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct TransferGraphRequest {
+    pub addresses: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_pages: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label_lookups: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct AccountCounterpartiesRequest {
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flow: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_pages: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct ClusterWalletsRequest {
+    pub addresses: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_window: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_shared_counterparties: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_hub_degree: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_funder_degree: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct DetectAddressPoisoningRequest {
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dust_usd: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_match: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_pages: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct DetectSandwichesRequest {
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_block_pages: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_requests: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct WalletPnlRequest {
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub period: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_pages: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct TaxLotExportRequest {
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_rewards: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_pages: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct AccountPortfolioHistoryRequest {
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_pages: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct StakingSummaryRequest {
+    pub address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub epochs: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_accounts: Option<usize>,
+}
@@ -1,5 +1,6 @@
 pub mod account;
 pub mod block;
+pub mod label;
 pub mod market;
 pub mod nft;
 pub mod token;
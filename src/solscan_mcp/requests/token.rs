@@ -90,3 +90,43 @@ pub struct TokenTrendingRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub limit: Option<i32>,
 }
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct TokenHolderStatsRequest {
+    pub token_address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bucket_bounds: Option<Vec<f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_categories: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct TokenRiskReportRequest {
+    pub token_address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_pages: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct TokenLiquidityRequest {
+    pub token_address: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trade_size_usd: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_pools: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct TokenPriceSeriesRequest {
+    pub address: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_time: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interval: Option<String>,
+}
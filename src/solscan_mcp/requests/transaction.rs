@@ -16,3 +16,8 @@ pub struct TransactionLastRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub filter: Option<String>,
 }
+
+#[derive(Debug, Deserialize, Serialize, schemars::JsonSchema)]
+pub struct TransactionExplainRequest {
+    pub tx: String,
+}
@@ -0,0 +1,19 @@
+use std::sync::Arc;
+
+use rmcp::model::Tool;
+use serde_json::{json, Value};
+
+/// Advertise an argument handled by the server itself in a tool's input schema
+pub fn add_argument(mut tool: Tool, name: &str, schema: Value) -> Tool {
+    let mut input_schema = (*tool.input_schema).clone();
+    let properties = input_schema
+        .entry("properties")
+        .or_insert_with(|| json!({}));
+
+    if let Some(properties) = properties.as_object_mut() {
+        properties.insert(name.to_string(), schema);
+    }
+
+    tool.input_schema = Arc::new(input_schema);
+    tool
+}